@@ -0,0 +1,71 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+//! Counts the allocations of decoding a series of images with a new decoder per image and with
+//! one decoder that is reset for every image and so reuses its buffers: cargo bench --bench allocations
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use charls_rs::{Decoder, Encoder, FrameInfo, Height, InterleaveMode, Width};
+
+struct CountingAllocator;
+
+static ALLOCATION_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATION_COUNT.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATION_COUNT.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const IMAGE_COUNT: usize = 100;
+
+fn count_allocations(decode_all: impl FnOnce()) -> usize {
+    let start = ALLOCATION_COUNT.load(Ordering::Relaxed);
+    decode_all();
+    ALLOCATION_COUNT.load(Ordering::Relaxed) - start
+}
+
+fn main() {
+    for interleave_mode in [InterleaveMode::None, InterleaveMode::Sample] {
+        let frame_info = FrameInfo::new(Width(256), Height(256), 12, 3);
+        let source: Vec<u8> = (0..256 * 256 * 3)
+            .flat_map(|index: u32| ((index * 7 % 4096) as u16).to_ne_bytes())
+            .collect();
+        let mut encoder = Encoder::new(frame_info);
+        encoder.set_interleave_mode(interleave_mode);
+        let encoded = encoder.encode(&source).unwrap();
+        let mut destination = vec![0; source.len()];
+
+        let new_decoders = count_allocations(|| {
+            for _ in 0..IMAGE_COUNT {
+                Decoder::new(encoded.as_slice()).decode_into(&mut destination).unwrap();
+            }
+        });
+
+        let mut decoder = Decoder::new(encoded.as_slice());
+        let reset_decoder = count_allocations(|| {
+            for _ in 0..IMAGE_COUNT {
+                decoder.reset(encoded.as_slice());
+                decoder.decode_into(&mut destination).unwrap();
+            }
+        });
+
+        println!("{:?}: {} images, {} allocations with new decoders, {} allocations with a reset decoder",
+                 interleave_mode, IMAGE_COUNT, new_decoders, reset_decoder);
+    }
+}
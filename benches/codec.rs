@@ -0,0 +1,47 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+//! Measures the encoding and decoding speed of synthetic images. Run with and without the simd
+//! feature to compare the SIMD and the scalar line computations:
+//! cargo bench --bench codec [--features simd].
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use charls_rs::{Decoder, Encoder, FrameInfo, Height, Width};
+
+/// Creates a noisy gradient, which is mostly coded in regular mode.
+fn create_source(frame_info: &FrameInfo) -> Vec<u8> {
+    let maximum_sample_value = (1_u32 << frame_info.bits_per_sample()) - 1;
+    let mut seed = 7_u32;
+    let mut source = Vec::new();
+    for y in 0..frame_info.height() {
+        for x in 0..frame_info.width() {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let sample = ((x + y) * 4 + (seed >> 16) % 32) & maximum_sample_value;
+            if frame_info.bits_per_sample() <= 8 {
+                source.push(sample as u8);
+            } else {
+                source.extend_from_slice(&(sample as u16).to_ne_bytes());
+            }
+        }
+    }
+    source
+}
+
+fn codec_benchmark(criterion: &mut Criterion) {
+    for bits_per_sample in [8, 16] {
+        let frame_info = FrameInfo::new(Width(512), Height(512), bits_per_sample, 1);
+        let source = create_source(&frame_info);
+        let encoded = Encoder::new(frame_info.clone()).encode(&source).unwrap();
+
+        criterion.bench_function(&format!("encode 512x512 {}-bit", bits_per_sample), |bencher| {
+            bencher.iter(|| Encoder::new(frame_info.clone()).encode(black_box(&source)).unwrap())
+        });
+        criterion.bench_function(&format!("decode 512x512 {}-bit", bits_per_sample), |bencher| {
+            bencher.iter(|| Decoder::new(black_box(encoded.as_slice())).decode().unwrap())
+        });
+    }
+}
+
+criterion_group!(benches, codec_benchmark);
+criterion_main!(benches);
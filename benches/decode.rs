@@ -0,0 +1,57 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+//! Measures the separate stages of decoding the conformance fixtures: walking the markers, reading
+//! the header, decoding grayscale and sample interleaved RGB images and decoding without output
+//! (decode_to_sink), which leaves out the cost of writing the image: cargo bench --bench decode
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use charls_rs::{read_header, Decoder};
+
+const GRAY8_16X16: &[u8] = include_bytes!("../tests/fixtures/gray8_16x16.jls");
+const BIAS16_64X64: &[u8] = include_bytes!("../tests/fixtures/bias16_64x64.jls");
+const RGB8_SAMPLE_16X16: &[u8] = include_bytes!("../tests/fixtures/rgb8_sample_16x16.jls");
+const RGB8_PLANAR_16X16: &[u8] = include_bytes!("../tests/fixtures/rgb8_planar_16x16.jls");
+
+fn markers_benchmark(criterion: &mut Criterion) {
+    criterion.bench_function("markers rgb8 planar 16x16", |bencher| {
+        bencher.iter(|| Decoder::new(black_box(RGB8_PLANAR_16X16)).markers().count())
+    });
+}
+
+fn read_header_benchmark(criterion: &mut Criterion) {
+    criterion.bench_function("read_header gray8 16x16", |bencher| {
+        bencher.iter(|| read_header(black_box(GRAY8_16X16)).unwrap())
+    });
+}
+
+fn decode_gray_benchmark(criterion: &mut Criterion) {
+    let mut destination = vec![0; 64 * 64 * 2];
+    criterion.bench_function("decode gray8 16x16", |bencher| {
+        bencher.iter(|| Decoder::new(black_box(GRAY8_16X16)).decode_into(&mut destination[..16 * 16]).unwrap())
+    });
+    criterion.bench_function("decode gray16 64x64", |bencher| {
+        bencher.iter(|| Decoder::new(black_box(BIAS16_64X64)).decode_into(&mut destination).unwrap())
+    });
+}
+
+fn decode_rgb_benchmark(criterion: &mut Criterion) {
+    let mut destination = vec![0; 16 * 16 * 3];
+    criterion.bench_function("decode rgb8 sample 16x16", |bencher| {
+        bencher.iter(|| Decoder::new(black_box(RGB8_SAMPLE_16X16)).decode_into(&mut destination).unwrap())
+    });
+}
+
+fn decode_to_sink_benchmark(criterion: &mut Criterion) {
+    criterion.bench_function("decode_to_sink gray16 64x64", |bencher| {
+        bencher.iter(|| Decoder::new(black_box(BIAS16_64X64)).decode_to_sink().unwrap())
+    });
+    criterion.bench_function("decode_to_sink rgb8 sample 16x16", |bencher| {
+        bencher.iter(|| Decoder::new(black_box(RGB8_SAMPLE_16X16)).decode_to_sink().unwrap())
+    });
+}
+
+criterion_group!(benches, markers_benchmark, read_header_benchmark, decode_gray_benchmark, decode_rgb_benchmark,
+                 decode_to_sink_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,360 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use std::future::poll_fn;
+use std::io::ErrorKind;
+use std::pin::Pin;
+use std::task::Poll;
+
+pub use futures_io::AsyncRead;
+
+use crate::decoder::Decoder;
+use crate::decoding_error::DecodingError;
+use crate::frame_info::FrameInfo;
+use crate::header_info::{read_header, HeaderInfo};
+use crate::jpeg_marker_code::JpegMarkerCode;
+use crate::jpeg_stream_reader::JPEG_MARKER_START_BYTE;
+
+const READ_CHUNK_SIZE: usize = 4096;
+
+/// Decodes a JPEG-LS stream from a source that implements the AsyncRead trait of the futures-io
+/// crate, like the readers of async-std and smol. Tokio readers are adapted with the compat
+/// module of tokio-util. The methods mirror those of Decoder but await on reads: the stream is
+/// buffered while it arrives and decoded when it is complete.
+#[derive(Debug)]
+pub struct AsyncDecoder<R: AsyncRead + Unpin> {
+    reader: R,
+    data: Vec<u8>,
+    end_of_stream: bool,
+    header_position: usize,
+    header_info: Option<HeaderInfo>,
+    byte_limit: Option<u64>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncDecoder<R> {
+    pub fn new(r: R) -> AsyncDecoder<R> {
+        AsyncDecoder {
+            reader: r,
+            data: Vec::new(),
+            end_of_stream: false,
+            header_position: 0,
+            header_info: None,
+            byte_limit: None,
+        }
+    }
+
+    /// Creates a decoder that buffers at most max_bytes bytes from r, for streams from untrusted
+    /// sources that may never end. Reading fails with InputTooLarge when more bytes arrive.
+    pub fn new_with_limit(r: R, max_bytes: u64) -> AsyncDecoder<R> {
+        let mut decoder = AsyncDecoder::new(r);
+        decoder.byte_limit = Some(max_bytes);
+        decoder
+    }
+
+    /// Reads the segments up to the first scan, awaiting more bytes until they are available. The
+    /// header is parsed once all its segments are buffered.
+    pub async fn read_header(&mut self) -> Result<HeaderInfo, DecodingError> {
+        if let Some(header_info) = &self.header_info {
+            return Ok(header_info.clone());
+        }
+
+        loop {
+            if !self.end_of_stream {
+                if let Some(position) = incomplete_header_position(&self.data, self.header_position) {
+                    self.header_position = position;
+                    self.read_chunk().await?;
+                    continue;
+                }
+            }
+
+            match read_header(&self.data) {
+                Err(DecodingError::UnexpectedEndOfStream) if !self.end_of_stream => self.read_chunk().await?,
+                result => {
+                    let header_info = result?;
+                    self.header_info = Some(header_info.clone());
+                    return Ok(header_info);
+                }
+            }
+        }
+    }
+
+    /// The frame info of the SOF segment. Only valid after read_header.
+    pub fn frame_info(&self) -> Option<&FrameInfo> {
+        self.header_info.as_ref().map(HeaderInfo::frame_info)
+    }
+
+    /// Awaits the rest of the stream and decodes it with the default options of Decoder.
+    pub async fn decode(&mut self) -> Result<Vec<u8>, DecodingError> {
+        self.decoder().await?.decode()
+    }
+
+    /// Awaits the rest of the stream and returns a Decoder over the buffered bytes, for the
+    /// options and methods of Decoder that AsyncDecoder doesn't mirror.
+    pub async fn decoder(&mut self) -> Result<Decoder<&[u8]>, DecodingError> {
+        self.read_header().await?;
+        while !self.end_of_stream {
+            self.read_chunk().await?;
+        }
+
+        Ok(Decoder::new(self.data.as_slice()))
+    }
+
+    async fn read_chunk(&mut self) -> Result<(), DecodingError> {
+        let available = self.data.len();
+        // One byte more than the limit allows is requested, to detect a stream that is too large.
+        let chunk_size = match self.byte_limit {
+            Some(byte_limit) => READ_CHUNK_SIZE.min((byte_limit.saturating_sub(available as u64) + 1) as usize),
+            None => READ_CHUNK_SIZE,
+        };
+        self.data.resize(available + chunk_size, 0);
+
+        let reader = &mut self.reader;
+        let buffer = &mut self.data[available..];
+        let result = poll_fn(|cx| loop {
+            match Pin::new(&mut *reader).poll_read(cx, buffer) {
+                Poll::Ready(Err(error)) if error.kind() == ErrorKind::Interrupted => continue,
+                poll => return poll,
+            }
+        })
+        .await;
+
+        match result {
+            Ok(bytes_read) => {
+                self.data.truncate(available + bytes_read);
+                self.end_of_stream = bytes_read == 0;
+                if self.byte_limit.is_some_and(|byte_limit| self.data.len() as u64 > byte_limit) {
+                    return Err(DecodingError::InputTooLarge);
+                }
+
+                Ok(())
+            }
+            Err(_) => {
+                self.data.truncate(available);
+                Err(DecodingError::IoError)
+            }
+        }
+    }
+}
+
+/// Skips the complete segments of the header in data, starting at position (0 or the position
+/// of a segment). Returns the position of the first incomplete segment, to continue there when
+/// more bytes are buffered, or None when the header is complete up to the end of the SOS segment
+/// or isn't a sequence of segments, which read_header then reports.
+fn incomplete_header_position(data: &[u8], mut position: usize) -> Option<usize> {
+    if position == 0 {
+        if data.len() < 2 {
+            return Some(0);
+        }
+
+        position = 2; // SOI
+    }
+
+    loop {
+        // A marker may be preceded by fill bytes (0xFF).
+        let mut marker_position = position;
+        while data.get(marker_position) == Some(&JPEG_MARKER_START_BYTE) {
+            marker_position += 1;
+        }
+
+        if marker_position == data.len() {
+            return Some(position);
+        }
+
+        if marker_position == position {
+            return None;
+        }
+
+        match JpegMarkerCode::try_from(data[marker_position]) {
+            Ok(marker_code) if marker_code.segment_has_length() => {
+                let Some(size) = data.get(marker_position + 1..marker_position + 3) else {
+                    return Some(position);
+                };
+                let end = marker_position + 1 + u16::from_be_bytes([size[0], size[1]]) as usize;
+                if end > data.len() {
+                    return Some(position);
+                }
+
+                if marker_code == JpegMarkerCode::StartOfScan {
+                    return None;
+                }
+
+                position = end;
+            }
+            _ => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::Encoder;
+    use crate::frame_info::{Height, Width};
+    use std::future::Future;
+    use std::io;
+    use std::task::{Context, Waker};
+
+    /// Returns Pending on every other poll and at most 7 bytes per read, like a slow socket.
+    struct SlowReader {
+        data: Vec<u8>,
+        position: usize,
+        ready: bool,
+    }
+
+    impl AsyncRead for SlowReader {
+        fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+            self.ready = !self.ready;
+            if !self.ready {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+
+            let count = buf.len().min(7).min(self.data.len() - self.position);
+            buf[..count].copy_from_slice(&self.data[self.position..self.position + count]);
+            self.position += count;
+            Poll::Ready(Ok(count))
+        }
+    }
+
+    struct FailingReader;
+
+    impl AsyncRead for FailingReader {
+        fn poll_read(self: Pin<&mut Self>, _cx: &mut Context<'_>, _buf: &mut [u8]) -> Poll<io::Result<usize>> {
+            Poll::Ready(Err(io::Error::other("connection reset")))
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    fn encode_gray8() -> (Vec<u8>, Vec<u8>) {
+        let source: Vec<u8> = (0..32 * 16).map(|i| (i * 7 % 251) as u8).collect();
+        let encoded = Encoder::new(FrameInfo::new(Width(32), Height(16), 8, 1)).encode(&source).unwrap();
+        (source, encoded)
+    }
+
+    #[test]
+    fn decode_from_async_reader() {
+        let (source, encoded) = encode_gray8();
+        let mut decoder = AsyncDecoder::new(SlowReader { data: encoded, position: 0, ready: false });
+
+        let header_info = block_on(decoder.read_header()).unwrap();
+        let decoded = block_on(decoder.decode()).unwrap();
+
+        assert_eq!(header_info.frame_info(), &FrameInfo::new(Width(32), Height(16), 8, 1));
+        assert_eq!(decoder.frame_info(), Some(&FrameInfo::new(Width(32), Height(16), 8, 1)));
+        assert_eq!(decoded, source);
+    }
+
+    /// Returns the stream of encode_gray8 with a COM segment of comment_size bytes after the SOI marker.
+    fn insert_comment(encoded: &[u8], comment_size: usize) -> Vec<u8> {
+        let mut stream = encoded[..2].to_vec();
+        stream.extend_from_slice(&[0xFF, 0xFE]);
+        stream.extend_from_slice(&(comment_size as u16 + 2).to_be_bytes());
+        stream.resize(stream.len() + comment_size, b'c');
+        stream.extend_from_slice(&encoded[2..]);
+        stream
+    }
+
+    #[test]
+    fn decode_with_large_comment_from_async_reader() {
+        let (source, encoded) = encode_gray8();
+        let stream = insert_comment(&encoded, 60000);
+        let mut decoder = AsyncDecoder::new(SlowReader { data: stream, position: 0, ready: false });
+
+        let header_info = block_on(decoder.read_header()).unwrap();
+        let decoded = block_on(decoder.decode()).unwrap();
+
+        assert_eq!(header_info.frame_info(), &FrameInfo::new(Width(32), Height(16), 8, 1));
+        assert_eq!(decoded, source);
+    }
+
+    #[test]
+    fn incomplete_header_position_is_start_of_incomplete_segment() {
+        let (_, encoded) = encode_gray8();
+        let stream = insert_comment(&encoded, 100);
+        let end_of_comment = 2 + 4 + 100;
+
+        assert_eq!(incomplete_header_position(&stream[..1], 0), Some(0));
+        assert_eq!(incomplete_header_position(&stream[..2], 0), Some(2));
+        assert_eq!(incomplete_header_position(&stream[..5], 0), Some(2));
+        assert_eq!(incomplete_header_position(&stream[..end_of_comment - 1], 0), Some(2));
+        assert_eq!(incomplete_header_position(&stream[..end_of_comment + 5], 0), Some(end_of_comment));
+        assert_eq!(incomplete_header_position(&stream[..end_of_comment + 5], 2), Some(end_of_comment));
+        assert_eq!(incomplete_header_position(&stream, 0), None);
+        assert_eq!(incomplete_header_position(&stream, end_of_comment), None);
+    }
+
+    #[test]
+    fn incomplete_header_position_of_invalid_header_is_none() {
+        assert_eq!(incomplete_header_position(&[0xFF, 0xD8, 0x12, 0x34], 0), None);
+        assert_eq!(incomplete_header_position(&[0xFF, 0xD8, 0xFF, 0xD9], 0), None);
+    }
+
+    #[test]
+    fn decode_from_slice_matches_decoder() {
+        let (source, encoded) = encode_gray8();
+
+        let decoded = block_on(AsyncDecoder::new(encoded.as_slice()).decode()).unwrap();
+
+        assert_eq!(decoded, source);
+    }
+
+    #[test]
+    fn read_header_of_truncated_stream_fails() {
+        let (_, encoded) = encode_gray8();
+        let mut decoder = AsyncDecoder::new(&encoded[..10]);
+
+        assert_eq!(block_on(decoder.read_header()).unwrap_err(), DecodingError::UnexpectedEndOfStream);
+    }
+
+    /// Returns the bytes of data followed by 0 bytes forever, like a peer that never closes the connection.
+    struct EndlessReader<'a> {
+        data: &'a [u8],
+    }
+
+    impl AsyncRead for EndlessReader<'_> {
+        fn poll_read(mut self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+            let size = self.data.len().min(buf.len());
+            buf[..size].copy_from_slice(&self.data[..size]);
+            buf[size..].fill(0);
+            self.data = &self.data[size..];
+            Poll::Ready(Ok(buf.len()))
+        }
+    }
+
+    #[test]
+    fn decode_endless_stream_with_limit_fails() {
+        let (_, encoded) = encode_gray8();
+        let stream = EndlessReader { data: &encoded[..encoded.len() - 2] };
+        let mut decoder = AsyncDecoder::new_with_limit(stream, 64 * 1024);
+
+        assert_eq!(block_on(decoder.decode()).unwrap_err(), DecodingError::InputTooLarge);
+        assert!(decoder.data.len() <= 64 * 1024 + 1);
+    }
+
+    #[test]
+    fn decode_stream_of_exactly_the_limit_from_async_reader() {
+        let (source, encoded) = encode_gray8();
+        let mut decoder = AsyncDecoder::new_with_limit(encoded.as_slice(), encoded.len() as u64);
+
+        assert_eq!(block_on(decoder.decode()).unwrap(), source);
+
+        let mut decoder = AsyncDecoder::new_with_limit(encoded.as_slice(), encoded.len() as u64 - 1);
+        assert_eq!(block_on(decoder.decode()).unwrap_err(), DecodingError::InputTooLarge);
+    }
+
+    #[test]
+    fn read_error_returns_io_error() {
+        let mut decoder = AsyncDecoder::new(FailingReader);
+
+        assert_eq!(block_on(decoder.decode()).unwrap_err(), DecodingError::IoError);
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use std::io::Read;
+
+use crate::decoding_error::DecodingError;
+use crate::jpeg_stream_reader::{JpegStreamReader, JPEG_MARKER_START_BYTE};
+
+const CACHE_BITS: i32 = u64::BITS as i32;
+const MAX_READABLE_CACHE_BITS: i32 = CACHE_BITS - 8;
+
+/// Reads the bits of the entropy coded data of a scan (see ISO/IEC 14495-1, A.1 and 9.1).
+/// A 0xFF byte is followed by a stuffed 0 bit; a 0xFF byte followed by a byte with the
+/// high bit set is a marker, which ends the entropy coded data. A 0xFF byte directly before
+/// the 0xFF of a marker is a fill byte (see ISO/IEC 10918-1, B.1.1.2) and is not read as data.
+pub(crate) struct BitReader<'a, R: Read> {
+    source: &'a mut JpegStreamReader<R>,
+    read_cache: u64,
+    valid_bits: i32,
+}
+
+impl<'a, R: Read> BitReader<'a, R> {
+    pub(crate) fn new(source: &'a mut JpegStreamReader<R>) -> BitReader<'a, R> {
+        BitReader {
+            source,
+            read_cache: 0,
+            valid_bits: 0,
+        }
+    }
+
+    pub(crate) fn read_bit(&mut self) -> Result<bool, DecodingError> {
+        if self.valid_bits <= 0 {
+            self.fill_read_cache()?;
+        }
+
+        let set = (self.read_cache & (1 << (CACHE_BITS - 1))) != 0;
+        self.skip(1)?;
+        Ok(set)
+    }
+
+    /// Reads a value of length bits, most significant bit first.
+    pub(crate) fn read_value(&mut self, length: i32) -> Result<i32, DecodingError> {
+        debug_assert!(length > 0 && length <= 32);
+        if self.valid_bits < length {
+            self.fill_read_cache()?;
+            if self.valid_bits < length {
+                return Err(DecodingError::UnexpectedEndOfStream);
+            }
+        }
+
+        let result = (self.read_cache >> (CACHE_BITS - length)) as i32;
+        self.skip(length)?;
+        Ok(result)
+    }
+
+    /// Reads the unary coded high bits of a Golomb code: counts the 0 bits before the next 1 bit.
+    /// Fails with InvalidEncodedData as soon as limit 0 bits are counted, which bounds the bits
+    /// that corrupt data (like an endless run of 0 bytes) makes the decoder read.
+    pub(crate) fn read_high_bits(&mut self, limit: i32) -> Result<i32, DecodingError> {
+        if self.valid_bits < 16 {
+            self.fill_read_cache()?;
+        }
+
+        let count = self.read_cache.leading_zeros() as i32;
+        if count < self.valid_bits.min(16) {
+            if count >= limit {
+                return Err(DecodingError::InvalidEncodedData);
+            }
+
+            self.skip(count + 1)?;
+            return Ok(count);
+        }
+
+        self.read_high_bits_slow(limit)
+    }
+
+    /// Skips the padding bits at the end of the entropy coded data. The stream reader is then
+    /// positioned at the marker that follows the scan, or at the end of a stream without EOI
+    /// marker.
+    pub(crate) fn end_scan(mut self) -> Result<(), DecodingError> {
+        loop {
+            self.read_cache = 0;
+            self.valid_bits = 0;
+            self.fill_read_cache()?;
+            if self.valid_bits == 0 {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_high_bits_slow(&mut self, limit: i32) -> Result<i32, DecodingError> {
+        let mut count = 0;
+        loop {
+            if self.read_bit()? {
+                return Ok(count);
+            }
+
+            count += 1;
+            if count >= limit {
+                return Err(DecodingError::InvalidEncodedData);
+            }
+        }
+    }
+
+    fn skip(&mut self, length: i32) -> Result<(), DecodingError> {
+        self.valid_bits -= length;
+        if self.valid_bits < 0 {
+            return Err(DecodingError::UnexpectedEndOfStream);
+        }
+
+        self.read_cache <<= length;
+        Ok(())
+    }
+
+    fn fill_read_cache(&mut self) -> Result<(), DecodingError> {
+        while self.valid_bits < MAX_READABLE_CACHE_BITS {
+            let value = match self.source.peek_byte(0)? {
+                Some(value) => value,
+                None => break,
+            };
+
+            if value == JPEG_MARKER_START_BYTE {
+                // A 0xFF byte followed by a byte with the high bit set is a marker: stop before it.
+                match self.source.peek_byte(1)? {
+                    Some(next) if next & 0x80 == 0 => {}
+                    _ => break,
+                }
+            }
+
+            self.source.skip_byte();
+            self.read_cache |= (value as u64) << (MAX_READABLE_CACHE_BITS - self.valid_bits);
+            self.valid_bits += 8;
+
+            if value == JPEG_MARKER_START_BYTE {
+                // The next byte has a stuffed 0 bit as its most significant bit.
+                self.valid_bits -= 1;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use crate::encoding_error::EncodingError;
+use crate::jpeg_stream_reader::JPEG_MARKER_START_BYTE;
+use crate::jpeg_stream_writer::JpegStreamWriter;
+
+/// Writes the bits of the entropy coded data of a scan (see ISO/IEC 14495-1, A.1 and 9.1).
+/// After a 0xFF byte a 0 bit is inserted (bit stuffing), which ensures that the entropy coded
+/// data never contains a byte sequence that a decoder would detect as a marker.
+pub(crate) struct BitWriter<'a, 'b> {
+    destination: &'a mut JpegStreamWriter<'b>,
+    bit_buffer: u64,
+    bit_count: u32,
+    is_ff_written: bool,
+}
+
+impl<'a, 'b> BitWriter<'a, 'b> {
+    pub(crate) fn new(destination: &'a mut JpegStreamWriter<'b>) -> BitWriter<'a, 'b> {
+        BitWriter {
+            destination,
+            bit_buffer: 0,
+            bit_count: 0,
+            is_ff_written: false,
+        }
+    }
+
+    /// Writes the length least significant bits of value, most significant bit first.
+    pub(crate) fn write_bits(&mut self, value: u32, length: u32) -> Result<(), EncodingError> {
+        debug_assert!(length <= 32);
+        debug_assert!(length == 32 || value >> length == 0);
+        self.bit_buffer = (self.bit_buffer << length) | value as u64;
+        self.bit_count += length;
+
+        while self.bit_count >= self.next_byte_bit_count() {
+            self.write_byte()?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes count 0 bits followed by a 1 bit.
+    pub(crate) fn write_unary(&mut self, mut count: u32) -> Result<(), EncodingError> {
+        while count > 31 {
+            self.write_bits(0, 31)?;
+            count -= 31;
+        }
+
+        self.write_bits(1, count + 1)
+    }
+
+    /// Pads the last byte with 0 bits. When the last byte is 0xFF, a 0 byte is added, as the
+    /// marker that follows the scan must not be read as the stuffed byte after 0xFF.
+    pub(crate) fn end_scan(mut self) -> Result<(), EncodingError> {
+        if self.bit_count > 0 {
+            self.write_bits(0, self.next_byte_bit_count() - self.bit_count)?;
+        }
+
+        if self.is_ff_written {
+            self.write_bits(0, 7)?;
+        }
+
+        Ok(())
+    }
+
+    /// The number of bits stored in the next byte: after a 0xFF byte, the most significant bit
+    /// of the next byte is a stuffed 0 bit.
+    fn next_byte_bit_count(&self) -> u32 {
+        if self.is_ff_written { 7 } else { 8 }
+    }
+
+    fn write_byte(&mut self) -> Result<(), EncodingError> {
+        let length = self.next_byte_bit_count();
+        self.bit_count -= length;
+        let value = (self.bit_buffer >> self.bit_count) as u8 & (0xFF >> (8 - length));
+        self.bit_buffer &= (1 << self.bit_count) - 1;
+
+        self.destination.write_byte(value)?;
+        self.is_ff_written = value == JPEG_MARKER_START_BYTE;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bit_reader::BitReader;
+    use crate::jpeg_stream_reader::JpegStreamReader;
+
+    fn write(values: &[(u32, u32)]) -> Vec<u8> {
+        let mut destination = vec![0; values.len() * 5 + 1];
+        let mut writer = JpegStreamWriter::new(&mut destination);
+        let mut bit_writer = BitWriter::new(&mut writer);
+        for &(value, length) in values {
+            bit_writer.write_bits(value, length).unwrap();
+        }
+        bit_writer.end_scan().unwrap();
+        let bytes_written = writer.bytes_written();
+        destination.truncate(bytes_written);
+        destination
+    }
+
+    /// Reads the values back with the BitReader; the data is followed by an EOI marker.
+    fn read(data: &[u8], values: &[(u32, u32)]) {
+        let mut stream = data.to_vec();
+        stream.extend_from_slice(&[0xFF, 0xD9]);
+        let mut reader = JpegStreamReader::new(stream.as_slice());
+        let mut bit_reader = BitReader::new(&mut reader);
+        for &(value, length) in values {
+            assert_eq!(bit_reader.read_value(length as i32).unwrap(), value as i32);
+        }
+        bit_reader.end_scan().unwrap();
+
+        assert_eq!(reader.read_next_marker_code().unwrap(), crate::jpeg_marker_code::JpegMarkerCode::EndOfImage);
+    }
+
+    fn assert_stuffing_bits_present(data: &[u8]) {
+        for pair in data.windows(2) {
+            if pair[0] == 0xFF {
+                assert_eq!(pair[1] & 0x80, 0, "byte after 0xFF must start with a 0 bit: {:02X?}", data);
+            }
+        }
+    }
+
+    #[test]
+    fn write_bits_inserts_zero_bit_after_ff() {
+        let values = [(0xFF, 8), (0x7F, 7), (0x1, 1)];
+
+        let data = write(&values);
+
+        assert_eq!(data, [0xFF, 0x7F, 0x80]);
+        assert_stuffing_bits_present(&data);
+        read(&data, &values);
+    }
+
+    #[test]
+    fn write_bits_with_all_ones() {
+        let values = [(0xFFFF_FFFF, 32), (0xFFFF_FFFF, 32), (0x3FFF, 14)];
+
+        let data = write(&values);
+
+        assert_eq!(data, [0xFF, 0x7F, 0xFF, 0x7F, 0xFF, 0x7F, 0xFF, 0x7F, 0xFF, 0x7F, 0xE0]);
+        assert_stuffing_bits_present(&data);
+        read(&data, &values);
+    }
+
+    #[test]
+    fn end_scan_after_ff_adds_zero_byte() {
+        let values = [(0x3, 2), (0x3F, 6)];
+
+        let data = write(&values);
+
+        assert_eq!(data, [0xFF, 0x00]);
+        read(&data, &values);
+    }
+
+    #[test]
+    fn end_scan_pads_last_byte_with_zero_bits() {
+        let values = [(0x5, 3)];
+
+        let data = write(&values);
+
+        assert_eq!(data, [0xA0]);
+        read(&data, &values);
+    }
+
+    #[test]
+    fn write_unary_longer_than_32_bits() {
+        let mut destination = [0xAA; 7];
+        let mut writer = JpegStreamWriter::new(&mut destination);
+        let mut bit_writer = BitWriter::new(&mut writer);
+        bit_writer.write_unary(40).unwrap();
+        bit_writer.end_scan().unwrap();
+
+        assert_eq!(writer.bytes_written(), 6);
+        assert_eq!(destination, [0, 0, 0, 0, 0, 0x80, 0xAA]);
+    }
+
+    #[test]
+    fn write_bits_beyond_destination_returns_error() {
+        let mut destination = [0; 2];
+        let mut writer = JpegStreamWriter::new(&mut destination);
+        let mut bit_writer = BitWriter::new(&mut writer);
+        bit_writer.write_bits(0xFFFF, 16).unwrap();
+
+        assert_eq!(bit_writer.write_bits(0x1, 8), Err(EncodingError::DestinationTooSmall));
+    }
+
+    #[test]
+    fn random_bit_patterns_round_trip() {
+        // A simple linear congruential generator biased towards 1 bits, to produce many 0xFF bytes.
+        let mut seed: u32 = 12345;
+        let mut values = Vec::new();
+        for _ in 0..2000 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let length = (seed >> 16) % 24 + 1;
+            let value = if (seed >> 8) & 3 != 0 { (1u32 << length) - 1 } else { (seed >> 4) & ((1 << length) - 1) };
+            values.push((value, length));
+        }
+
+        let data = write(&values);
+
+        assert!(data.iter().filter(|&&value| value == 0xFF).count() > 100);
+        assert_stuffing_bits_present(&data);
+        read(&data, &values);
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+/// Defines the order in which the decoder stores the first 3 components of images with 3 or 4
+/// components. The 4th (alpha) component is always stored last (see Decoder::set_alpha_component_id).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChannelOrder {
+    Rgb, // The components are stored in the order of the frame: R, G, B (A).
+    Bgr, // The first and third components are swapped: B, G, R (A).
+}
+
+impl ChannelOrder {
+    /// Swaps the R and B samples of a row of pixel interleaved samples with 1 or 2 bytes per
+    /// sample, if the order is Bgr.
+    pub(crate) fn apply_to_row(&self, row: &mut [u8], component_count: usize, bytes_per_sample: usize) {
+        debug_assert!(component_count == 3 || component_count == 4);
+        if *self == ChannelOrder::Rgb {
+            return;
+        }
+
+        for pixel in row.chunks_exact_mut(component_count * bytes_per_sample) {
+            for byte in 0..bytes_per_sample {
+                pixel.swap(byte, 2 * bytes_per_sample + byte);
+            }
+        }
+    }
+
+    /// The position of the plane of a component in the output of an image with interleave mode None.
+    pub(crate) fn plane_index(&self, component: usize) -> usize {
+        match (self, component) {
+            (ChannelOrder::Bgr, 0) => 2,
+            (ChannelOrder::Bgr, 2) => 0,
+            _ => component,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_bgr_to_row() {
+        let mut row = [1, 2, 3, 4, 5, 6];
+        ChannelOrder::Bgr.apply_to_row(&mut row, 3, 1);
+        assert_eq!(row, [3, 2, 1, 6, 5, 4]);
+
+        let mut row = [1, 2, 3, 4, 5, 6, 7, 8];
+        ChannelOrder::Bgr.apply_to_row(&mut row, 4, 2);
+        assert_eq!(row, [5, 6, 3, 4, 1, 2, 7, 8]);
+
+        ChannelOrder::Rgb.apply_to_row(&mut row, 4, 2);
+        assert_eq!(row, [5, 6, 3, 4, 1, 2, 7, 8]);
+    }
+}
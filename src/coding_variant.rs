@@ -0,0 +1,9 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+/// Defines the JPEG-LS coding process of a frame, as signalled by its SOF marker.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CodingVariant {
+    Baseline, // SOF_55: JPEG-LS as defined by ISO/IEC 14495-1.
+    Extended, // SOF_57: JPEG-LS extensions as defined by ISO/IEC 14495-2 (not supported for decoding).
+}
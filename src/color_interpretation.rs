@@ -0,0 +1,74 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use crate::color_transformation::ColorTransformation;
+
+// The SPIFF color space values (S) that identify a known interpretation (see ISO/IEC 10918-3, F.2.1.2).
+const SPIFF_COLOR_SPACE_YCBCR_ITU_BT_709: u8 = 1;
+const SPIFF_COLOR_SPACE_NONE: u8 = 2;
+const SPIFF_COLOR_SPACE_YCBCR_ITU_BT_601_1_RGB: u8 = 3;
+const SPIFF_COLOR_SPACE_YCBCR_ITU_BT_601_1_VIDEO: u8 = 4;
+const SPIFF_COLOR_SPACE_GRAYSCALE: u8 = 8;
+const SPIFF_COLOR_SPACE_RGB: u8 = 10;
+
+/// Defines how the decoded components of an image should be interpreted for display.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ColorInterpretation {
+    Grayscale,                             // A single component with luminance samples.
+    Rgb,                                   // 3 components with red, green and blue samples.
+    RgbWithTransform(ColorTransformation), // RGB encoded with a color transformation, inverted by the decoder.
+    YCbCr,                                 // 3 components with luminance and chrominance samples.
+    Unknown,                               // The stream doesn't define an interpretation that is known.
+}
+
+impl ColorInterpretation {
+    /// Combines the color space of the SPIFF header, if present, with the component count and the
+    /// color transformation. Without a SPIFF header (or with color space "none") the interpretation
+    /// is implied by the component count: 1 is grayscale and 3 is RGB.
+    pub(crate) fn new(spiff_color_space: Option<u8>, component_count: u8,
+                      color_transformation: ColorTransformation) -> ColorInterpretation {
+        let rgb = if color_transformation == ColorTransformation::None {
+            ColorInterpretation::Rgb
+        } else {
+            ColorInterpretation::RgbWithTransform(color_transformation)
+        };
+
+        match (spiff_color_space.unwrap_or(SPIFF_COLOR_SPACE_NONE), component_count) {
+            (SPIFF_COLOR_SPACE_GRAYSCALE, 1) => ColorInterpretation::Grayscale,
+            (SPIFF_COLOR_SPACE_RGB, 3) => rgb,
+            (SPIFF_COLOR_SPACE_YCBCR_ITU_BT_709 |
+             SPIFF_COLOR_SPACE_YCBCR_ITU_BT_601_1_RGB |
+             SPIFF_COLOR_SPACE_YCBCR_ITU_BT_601_1_VIDEO, 3) => ColorInterpretation::YCbCr,
+            (SPIFF_COLOR_SPACE_NONE, 1) => ColorInterpretation::Grayscale,
+            (SPIFF_COLOR_SPACE_NONE, 3) => rgb,
+            _ => ColorInterpretation::Unknown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpretation_without_spiff_header_is_implied_by_component_count() {
+        assert_eq!(ColorInterpretation::new(None, 1, ColorTransformation::None), ColorInterpretation::Grayscale);
+        assert_eq!(ColorInterpretation::new(None, 3, ColorTransformation::None), ColorInterpretation::Rgb);
+        assert_eq!(ColorInterpretation::new(None, 3, ColorTransformation::Hp2),
+                   ColorInterpretation::RgbWithTransform(ColorTransformation::Hp2));
+        assert_eq!(ColorInterpretation::new(None, 4, ColorTransformation::None), ColorInterpretation::Unknown);
+    }
+
+    #[test]
+    fn interpretation_with_spiff_color_space() {
+        assert_eq!(ColorInterpretation::new(Some(8), 1, ColorTransformation::None), ColorInterpretation::Grayscale);
+        assert_eq!(ColorInterpretation::new(Some(10), 3, ColorTransformation::Hp1),
+                   ColorInterpretation::RgbWithTransform(ColorTransformation::Hp1));
+        assert_eq!(ColorInterpretation::new(Some(3), 3, ColorTransformation::None), ColorInterpretation::YCbCr);
+        assert_eq!(ColorInterpretation::new(Some(2), 3, ColorTransformation::None), ColorInterpretation::Rgb);
+
+        // A color space that doesn't match the component count, and CMYK.
+        assert_eq!(ColorInterpretation::new(Some(8), 3, ColorTransformation::None), ColorInterpretation::Unknown);
+        assert_eq!(ColorInterpretation::new(Some(12), 4, ColorTransformation::None), ColorInterpretation::Unknown);
+    }
+}
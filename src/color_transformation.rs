@@ -0,0 +1,100 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use std::convert::TryFrom;
+
+/// Defines the reversible color transformations of the HP LOCO-I implementation, as used by CharLS.
+/// These transformations are not part of ISO/IEC 14495-1 and are only defined for 8 and 16 bit
+/// images with 3 (or 4, the 4th component is not transformed) interleaved components.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ColorTransformation {
+    None = 0, // No color space transformation has been applied.
+    Hp1 = 1,  // v1 = R - G, v2 = G, v3 = B - G
+    Hp2 = 2,  // v1 = R - G, v2 = G, v3 = B - ((R + G) >> 1)
+    Hp3 = 3,  // v1 = G + ((v2 + v3) >> 2), v2 = B - G, v3 = R - G
+}
+
+impl TryFrom<u8> for ColorTransformation {
+    type Error = ();
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        match v {
+            x if x == ColorTransformation::None as u8 => Ok(ColorTransformation::None),
+            x if x == ColorTransformation::Hp1 as u8 => Ok(ColorTransformation::Hp1),
+            x if x == ColorTransformation::Hp2 as u8 => Ok(ColorTransformation::Hp2),
+            x if x == ColorTransformation::Hp3 as u8 => Ok(ColorTransformation::Hp3),
+            _ => Err(()),
+        }
+    }
+}
+
+impl ColorTransformation {
+    /// Applies the inverse transformation to a row of pixel interleaved samples with 1 (8 bit)
+    /// or 2 (16 bit, native endian) bytes per sample. The arithmetic wraps around at the range
+    /// of the sample type, which makes the transformation reversible (identical to CharLS).
+    pub(crate) fn inverse_transform_row(&self, row: &mut [u8], component_count: usize, bytes_per_sample: usize) {
+        debug_assert!(component_count == 3 || component_count == 4);
+        if *self == ColorTransformation::None {
+            return;
+        }
+
+        if bytes_per_sample == 1 {
+            for pixel in row.chunks_exact_mut(component_count) {
+                let (r, g, b) = self.inverse_transform(pixel[0] as i32, pixel[1] as i32, pixel[2] as i32, 1 << 8);
+                pixel[0] = r as u8;
+                pixel[1] = g as u8;
+                pixel[2] = b as u8;
+            }
+        } else {
+            for pixel in row.chunks_exact_mut(component_count * 2) {
+                let sample = |index: usize| u16::from_ne_bytes([pixel[index * 2], pixel[index * 2 + 1]]) as i32;
+                let (r, g, b) = self.inverse_transform(sample(0), sample(1), sample(2), 1 << 16);
+                pixel[0..2].copy_from_slice(&(r as u16).to_ne_bytes());
+                pixel[2..4].copy_from_slice(&(g as u16).to_ne_bytes());
+                pixel[4..6].copy_from_slice(&(b as u16).to_ne_bytes());
+            }
+        }
+    }
+
+    /// Computes R, G and B from the transformed values v1, v2 and v3. The results must still be
+    /// reduced modulo range by the caller.
+    fn inverse_transform(&self, v1: i32, v2: i32, v3: i32, range: i32) -> (i32, i32, i32) {
+        let mask = range - 1;
+        match self {
+            ColorTransformation::None => (v1, v2, v3),
+            ColorTransformation::Hp1 => (v1 + v2 - range / 2, v2, v3 + v2 - range / 2),
+            ColorTransformation::Hp2 => {
+                let r = (v1 + v2 - range / 2) & mask;
+                let g = v2;
+                (r, g, v3 + ((r + g) >> 1) - range / 2)
+            }
+            ColorTransformation::Hp3 => {
+                let g = v1 - ((v3 + v2) >> 2) + range / 4;
+                (v3 + g - range / 2, g, v2 + g - range / 2)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inverse_hp1_wraps_around() {
+        let mut row = [0x10, 0x90, 0xF0];
+
+        ColorTransformation::Hp1.inverse_transform_row(&mut row, 3, 1);
+
+        assert_eq!(row, [0x20, 0x90, 0x00]);
+    }
+
+    #[test]
+    fn inverse_transform_leaves_4th_component_untouched() {
+        let mut row = [0x80, 0x40, 0x80, 0x12];
+
+        ColorTransformation::Hp2.inverse_transform_row(&mut row, 4, 1);
+
+        assert_eq!(row, [0x40, 0x40, 0x40, 0x12]);
+    }
+}
@@ -0,0 +1,42 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+/// The minimum sample precision (P) supported by JPEG-LS (see ISO/IEC 14495-1, C.2.2).
+pub const MINIMUM_BITS_PER_SAMPLE: u8 = 2;
+
+/// The maximum sample precision (P) supported by JPEG-LS (see ISO/IEC 14495-1, C.2.2).
+pub const MAXIMUM_BITS_PER_SAMPLE: u8 = 16;
+
+/// The maximum number of components (Nf) in a frame (see ISO/IEC 10918-1, B.2.2). Images with
+/// more than 4 components can only be encoded and decoded with interleave mode None.
+pub const MAXIMUM_COMPONENT_COUNT: u8 = 255;
+
+/// The maximum number of components (Ns) in a scan (see ISO/IEC 14495-1, C.2.3).
+pub(crate) const MAXIMUM_COMPONENT_COUNT_IN_SCAN: u8 = 4;
+
+/// Returns the largest sample value that can be stored with the given number of bits.
+/// bits_per_sample must be in the range MINIMUM_BITS_PER_SAMPLE..=MAXIMUM_BITS_PER_SAMPLE.
+pub fn maximum_sample_value(bits_per_sample: u8) -> u32 {
+    debug_assert!((MINIMUM_BITS_PER_SAMPLE..=MAXIMUM_BITS_PER_SAMPLE).contains(&bits_per_sample));
+    (1 << bits_per_sample) - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maximum_sample_value_at_minimum_bits_per_sample() {
+        assert_eq!(maximum_sample_value(MINIMUM_BITS_PER_SAMPLE), 3);
+    }
+
+    #[test]
+    fn maximum_sample_value_at_maximum_bits_per_sample() {
+        assert_eq!(maximum_sample_value(MAXIMUM_BITS_PER_SAMPLE), 65535);
+    }
+
+    #[test]
+    fn maximum_sample_value_for_8_bits_per_sample() {
+        assert_eq!(maximum_sample_value(8), 255);
+    }
+}
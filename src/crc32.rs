@@ -0,0 +1,58 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+//! The CRC-32 of ISO 3309 (as used by zlib and PNG), computed byte by byte with a lookup table.
+
+/// The value of the CRC register before the first byte.
+pub(crate) const CRC32_INITIAL_VALUE: u32 = 0xFFFF_FFFF;
+
+const CRC32_POLYNOMIAL: u32 = 0xEDB8_8320;
+
+const CRC32_TABLE: [u32; 256] = create_crc32_table();
+
+const fn create_crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut value = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            value = if value & 1 != 0 { (value >> 1) ^ CRC32_POLYNOMIAL } else { value >> 1 };
+            bit += 1;
+        }
+
+        table[index] = value;
+        index += 1;
+    }
+
+    table
+}
+
+/// Adds a byte to the CRC register crc.
+pub(crate) fn update_crc32(crc: u32, value: u8) -> u32 {
+    CRC32_TABLE[((crc ^ value as u32) & 0xFF) as usize] ^ (crc >> 8)
+}
+
+/// Computes the CRC-32 from the CRC register after the last byte.
+pub(crate) fn finish_crc32(crc: u32) -> u32 {
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crc32(bytes: &[u8]) -> u32 {
+        finish_crc32(bytes.iter().fold(CRC32_INITIAL_VALUE, |crc, &value| update_crc32(crc, value)))
+    }
+
+    #[test]
+    fn crc32_of_check_string() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn crc32_of_empty_input_is_zero() {
+        assert_eq!(crc32(&[]), 0);
+    }
+}
@@ -0,0 +1,53 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use ndarray::{Array3, ShapeBuilder};
+
+use crate::decoding_error::DecodingError;
+use crate::frame_info::FrameInfo;
+
+/// A decoded image as an array with the shape (height, width, component count) in standard
+/// (row major) layout, returned by Decoder::decode_to_ndarray.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DecodedArray {
+    U8(Array3<u8>),   // 2 to 8 bits per sample
+    U16(Array3<u16>), // 9 to 16 bits per sample
+}
+
+impl DecodedArray {
+    /// Creates the array from the samples returned by Decoder::decode: planar samples (interleave
+    /// mode None) are stored component by component, other samples pixel by pixel.
+    pub(crate) fn new(frame_info: &FrameInfo, planar: bool, decoded: Vec<u8>) -> Result<DecodedArray, DecodingError> {
+        if frame_info.bits_per_sample() <= 8 {
+            Ok(DecodedArray::U8(to_array3(frame_info, planar, decoded)?))
+        } else {
+            let samples = decoded.chunks_exact(2).map(|bytes| u16::from_ne_bytes([bytes[0], bytes[1]])).collect();
+            Ok(DecodedArray::U16(to_array3(frame_info, planar, samples)?))
+        }
+    }
+
+    /// The shape of the array: (height, width, component count).
+    pub fn shape(&self) -> (usize, usize, usize) {
+        let shape = match self {
+            DecodedArray::U8(array) => array.shape(),
+            DecodedArray::U16(array) => array.shape(),
+        };
+        (shape[0], shape[1], shape[2])
+    }
+}
+
+fn to_array3<T: Clone>(frame_info: &FrameInfo, planar: bool, samples: Vec<T>) -> Result<Array3<T>, DecodingError> {
+    let height = frame_info.height() as usize;
+    let width = frame_info.width() as usize;
+    let component_count = frame_info.component_count() as usize;
+    let array = if planar {
+        // Component c of pixel (y, x) is at c * height * width + y * width + x.
+        Array3::from_shape_vec((height, width, component_count).strides((width, 1, height * width)), samples)
+    } else {
+        Array3::from_shape_vec((height, width, component_count), samples)
+    };
+
+    // The decoder returns exactly height * width * component count samples.
+    let array = array.map_err(|_| DecodingError::UnknownError)?;
+    Ok(array.as_standard_layout().into_owned())
+}
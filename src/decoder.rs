@@ -1,36 +1,2855 @@
 // Copyright (c) Team CharLS.
 // SPDX-License-Identifier: BSD-3-Clause
 
-use std::io::{Read};
-
-#[warn(unused_variables)]
-
+use std::io::Read;
 
+use crate::bit_reader::BitReader;
+use crate::channel_order::ChannelOrder;
+use crate::coding_variant::CodingVariant;
+use crate::color_interpretation::ColorInterpretation;
+use crate::color_transformation::ColorTransformation;
+#[cfg(feature = "ndarray")]
+use crate::decoded_array::DecodedArray;
+use crate::decoder_buffers::DecoderBuffers;
+use crate::decoding_error::DecodingError;
+use crate::encoder::Encoder;
+use crate::endianness::Endianness;
+use crate::error_policy::ErrorPolicy;
+use crate::frame_info::FrameInfo;
+use crate::interleave_mode::InterleaveMode;
+use crate::jpeg_stream_reader::JpegStreamReader;
+use crate::mapping_table::MappingTable;
+use crate::marker_iter::MarkerIter;
+use crate::reader_state_public::ReaderStatePublic;
+use crate::scan_decoder::{RowCallback, ScanDecoder};
+use crate::scan_info::ScanInfo;
+use crate::spiff_header::SpiffHeader;
+use crate::table_set::TableSet;
 
 
+/// Decodes a JPEG-LS image.
+///
+/// The decoded samples are stored with 1 byte per sample for precisions up to 8 bits
+/// and 2 bytes (native endian) for higher precisions. Images encoded with interleave mode
+/// None are stored component by component (planar), other images are stored pixel interleaved.
+///
+/// A decoder owns all its state and uses no shared or thread local data: it is Send when its
+/// reader is Send and Sync when its reader is Sync, so it can be moved to another thread.
 #[derive(Debug)]
 pub struct Decoder<R: Read> {
-    reader: R,
-    width:           u32,
-    height:          u32,
-    bits_per_sample: u8,
-    component_count: u8
+    reader: JpegStreamReader<R>,
+    header_read: bool,
+    decoded_row_count: u32,
+    scan_infos: Vec<ScanInfo>,
+    error_policy: ErrorPolicy,
+    fill_value: u16,
+    sample_offset: i32,
+    recovered_error: Option<DecodingError>,
+    channel_order: ChannelOrder,
+    alpha_component_id: Option<u8>,
+    buffers: DecoderBuffers,
+    row_callback: Option<RowCallback>,
+    packed: bool,
+    collect_value_ranges: bool,
+    component_value_ranges: Vec<(u32, u32)>,
+    expected_parameters: Option<(u8, u8)>,
+    scans: Vec<ScanInfo>,
 }
 
 
 impl<R: Read> Decoder<R> {
     pub fn new(r: R) -> Decoder<R> {
-        let width = 0;
-        let height = 0;
-        let bits_per_sample = 0;
-        let component_count = 0;
+        Decoder::with_buffers(r, DecoderBuffers::new())
+    }
+
+    /// Creates a decoder that reads at most max_bytes bytes from r, for streams from untrusted
+    /// sources that may never end. Decoding fails with InputTooLarge when more bytes are needed.
+    /// Like the other options the limit is removed by reset.
+    pub fn new_with_limit(r: R, max_bytes: u64) -> Decoder<R> {
+        let mut decoder = Decoder::new(r);
+        decoder.reader.set_byte_limit(max_bytes);
+        decoder
+    }
+
+    /// Creates a decoder for an abbreviated image stream, which selects mapping tables that a
+    /// tables-only stream read with read_tables defines. A table that the image stream defines
+    /// itself replaces the table of table_set with the same ID.
+    pub fn with_tables(r: R, table_set: TableSet) -> Decoder<R> {
+        let mut decoder = Decoder::new(r);
+        decoder.reader.set_mapping_tables(table_set.mapping_tables);
+        decoder
+    }
 
+    /// Creates a decoder that uses the memory of buffers, typically returned by into_buffers of a
+    /// previous decoder, for its line buffers and context arrays.
+    pub fn with_buffers(r: R, buffers: DecoderBuffers) -> Decoder<R> {
         Decoder {
-            reader: r,
-            width: width,
-            height: height,
-            bits_per_sample: bits_per_sample,
-            component_count: component_count
+            reader: JpegStreamReader::new(r),
+            header_read: false,
+            decoded_row_count: 0,
+            scan_infos: Vec::new(),
+            error_policy: ErrorPolicy::Abort,
+            fill_value: 0,
+            sample_offset: 0,
+            recovered_error: None,
+            channel_order: ChannelOrder::Rgb,
+            alpha_component_id: None,
+            buffers,
+            row_callback: None,
+            packed: false,
+            collect_value_ranges: false,
+            component_value_ranges: Vec::new(),
+            expected_parameters: None,
+            scans: Vec::new(),
+        }
+    }
+
+    /// Sets the component identifier (Ci in the SOF segment) of the alpha component of images with
+    /// 4 components. The decoder stores that component as the 4th (alpha) channel and the other
+    /// components in frame order before it. Without an alpha component ID (the default), or if no
+    /// component has the ID, the components are stored in frame order.
+    pub fn set_alpha_component_id(&mut self, alpha_component_id: u8) {
+        self.alpha_component_id = Some(alpha_component_id);
+    }
+
+    /// Sets a function that the decoder calls after every decoded row of a scan, with the index of
+    /// the row, for example to report progress. For images encoded with interleave mode None the
+    /// rows of every component scan are reported. Without a callback (the default) nothing is called.
+    pub fn set_row_callback(&mut self, row_callback: Box<dyn FnMut(u32) + Send + Sync>) {
+        self.row_callback = Some(RowCallback(row_callback));
+    }
+
+    /// Sets whether the samples of images with 2 to 4 bits per sample are packed MSB-first into
+    /// bytes, multiple samples per byte, instead of stored as 1 byte per sample (the default).
+    /// Every row starts at a byte boundary: the unused low bits of the last byte of a row are
+    /// set to 0. For images with more than 4 bits per sample the option has no effect.
+    pub fn set_packed(&mut self, packed: bool) {
+        self.packed = packed;
+    }
+
+    /// Sets whether the minimum and maximum decoded sample value of every component are collected
+    /// while decoding, for component_value_ranges. The default is false.
+    pub fn set_collect_value_ranges(&mut self, collect_value_ranges: bool) {
+        self.collect_value_ranges = collect_value_ranges;
+    }
+
+    /// The (minimum, maximum) decoded sample values of the components, in frame order, of the last
+    /// decode call. The values are those after the inverse color transformation, before mapping
+    /// tables are applied; rows filled by the BestEffort error policy are not included. Empty when
+    /// collecting is not enabled; (u32::MAX, 0) for components of which no samples were decoded.
+    pub fn component_value_ranges(&self) -> Vec<(u32, u32)> {
+        self.component_value_ranges.clone()
+    }
+
+    /// Sets whether a hash of the entropy coded data (the CRC-32 of ISO 3309, as used by zlib) is
+    /// computed while it is decoded, which doesn't depend on the segments of the stream. Enable
+    /// it before decoding: the hash then covers the entropy coded data of all scans, without the
+    /// SOS segments between them and without fill bytes. Like the other options it is removed by
+    /// reset.
+    pub fn set_compute_payload_hash(&mut self, compute_payload_hash: bool) {
+        self.reader.set_compute_payload_hash(compute_payload_hash);
+    }
+
+    /// The hash of the entropy coded data, once the whole image has been decoded. None when
+    /// the option isn't enabled or the EOI marker hasn't been read yet.
+    pub fn payload_hash(&self) -> Option<u32> {
+        self.reader.payload_hash()
+    }
+
+    /// Sets that the payload of the first APPn segment with the given n (0 to 15) is captured while
+    /// the header is read, for application_data. Call it before read_header, once for every n of
+    /// interest. Like the other options it is removed by reset.
+    pub fn set_capture_application_data(&mut self, n: u8) {
+        self.reader.set_capture_application_data(n);
+    }
+
+    /// The payload (without the marker and the segment size) of the first APPn segment with the
+    /// given n. None when capturing n is not enabled or the stream has no such segment.
+    pub fn application_data(&self, n: u8) -> Option<&[u8]> {
+        self.reader.application_data(n)
+    }
+
+    /// Limits the payload size of the COM and APPn segments, to bound the memory used for a stream
+    /// with a giant comment or application data segment. In strict mode a larger segment fails with
+    /// InvalidMarkerSegmentSize, in lenient mode it is skipped without capturing it.
+    pub fn set_maximum_segment_size(&mut self, maximum_segment_size: usize) {
+        self.reader.set_maximum_segment_size(maximum_segment_size);
+    }
+
+    /// Returns the buffers of the decoder, to reuse them with Decoder::with_buffers.
+    pub fn into_buffers(self) -> DecoderBuffers {
+        self.buffers
+    }
+
+    /// Prepares the decoder for a new stream, as a new decoder with the default options that keeps
+    /// the buffers of this decoder.
+    pub fn reset(&mut self, r: R) {
+        *self = Decoder::with_buffers(r, std::mem::take(&mut self.buffers));
+    }
+
+    /// Enables or disables strict mode, which is enabled by default. In strict mode inconsistencies
+    /// between the optional parts of the stream (SPIFF header) and the mandatory segments (SOF)
+    /// and reserved values (LSE IDs) are reported as errors. In lenient mode the values of the
+    /// mandatory segments are used, segments with reserved values are skipped and APPn segments
+    /// are also accepted between the SOF and the SOS segment.
+    pub fn set_strict_mode(&mut self, strict_mode: bool) {
+        self.reader.set_strict_mode(strict_mode);
+    }
+
+    /// Enables or disables scanning for the SOI marker, which is disabled by default. When enabled
+    /// in lenient mode the bytes before the SOI marker are skipped, for example for a JPEG-LS stream
+    /// embedded in a container format. In strict mode the stream must start with the SOI marker.
+    pub fn set_scan_for_soi(&mut self, scan_for_soi: bool) {
+        self.reader.set_scan_for_soi(scan_for_soi);
+    }
+
+    /// Sets how errors in the entropy coded data are handled, the default is Abort.
+    /// With BestEffort a desynchronized bit stream doesn't fail the decode call: the rows of
+    /// the scan that cannot be decoded are filled with the fill value, decoding continues
+    /// with the next scan and the error is reported by recovered_error.
+    pub fn set_error_policy(&mut self, error_policy: ErrorPolicy) {
+        self.error_policy = error_policy;
+    }
+
+    /// Sets the sample value used by the BestEffort error policy for rows that cannot be decoded.
+    /// The default is 0; values larger than MAXVAL are limited to MAXVAL.
+    pub fn set_fill_value(&mut self, fill_value: u16) {
+        self.fill_value = fill_value;
+    }
+
+    /// Sets the offset that decode_signed_into subtracts from every decoded sample, for data that
+    /// was offset into the unsigned range of JPEG-LS samples. The default is 0.
+    pub fn set_sample_offset(&mut self, sample_offset: i32) {
+        self.sample_offset = sample_offset;
+    }
+
+    /// Sets the order in which the R, G and B components of images with 3 or 4 components are
+    /// stored, the default is Rgb. The order is applied after the inverse color transformation.
+    /// For images encoded with interleave mode None the planes of the first and third component
+    /// are swapped.
+    pub fn set_channel_order(&mut self, channel_order: ChannelOrder) {
+        self.channel_order = channel_order;
+    }
+
+    /// Sets the bits per sample and the component count that the stream must have, for
+    /// containers like DICOM that store these parameters outside the stream. read_header (and
+    /// every decode call) fails with ParameterMismatch when the SOF segment has other values.
+    pub fn set_expected_parameters(&mut self, bits_per_sample: u8, component_count: u8) {
+        self.expected_parameters = Some((bits_per_sample, component_count));
+    }
+
+    /// Reads the header of the JPEG-LS stream, up to the start of the first scan.
+    pub fn read_header(&mut self) -> Result<(), DecodingError> {
+        if !self.header_read {
+            self.reader.read_header()?;
+            self.header_read = true;
+        }
+
+        match self.expected_parameters {
+            Some((bits_per_sample, component_count)) if bits_per_sample != self.frame_info().bits_per_sample() ||
+                component_count != self.frame_info().component_count() => Err(DecodingError::ParameterMismatch),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads only the start of the stream: the SOI marker and the SPIFF header, if present.
+    /// Returns None for streams without a SPIFF header. The frame is not parsed; a later call
+    /// to read_header or decode continues with the segments after the SPIFF header.
+    pub fn read_spiff_header(&mut self) -> Result<Option<SpiffHeader>, DecodingError> {
+        if !self.header_read {
+            self.reader.read_spiff_header()?;
+        }
+
+        Ok(self.spiff_header().cloned())
+    }
+
+    /// The frame info of the image. Only valid after the header has been read.
+    pub fn frame_info(&self) -> &FrameInfo {
+        self.reader.frame_info()
+    }
+
+    /// The SPIFF header, if the stream has one. Only valid after the header has been read.
+    pub fn spiff_header(&self) -> Option<&SpiffHeader> {
+        self.reader.spiff_header()
+    }
+
+    /// The JPEG-LS coding process of the frame. Only valid after the header has been read.
+    /// Frames encoded with the extended (ISO/IEC 14495-2) process cannot be decoded.
+    pub fn coding_variant(&self) -> CodingVariant {
+        self.reader.coding_variant()
+    }
+
+    /// The parameters of the scan that was read last: after read_header the first scan,
+    /// after decoding the last scan of the image.
+    pub fn scan_info(&self) -> Option<&ScanInfo> {
+        self.reader.scan_info()
+    }
+
+    /// The parameters of the scans that were decoded by the last decode call, in stream order.
+    /// For images encoded with interleave mode None every component has its own scan, and each
+    /// scan can use a different NEAR value.
+    pub fn scan_infos(&self) -> &[ScanInfo] {
+        &self.scan_infos
+    }
+
+    /// Reads the parameters of all scans without decoding them (see scans()): the entropy coded data
+    /// of every scan is skipped up to the marker that follows it, up to the EOI marker. The image
+    /// can't be decoded afterwards by this decoder.
+    pub fn read_scans(&mut self) -> Result<(), DecodingError> {
+        self.read_header()?;
+        self.scans.clear();
+        let component_count = self.frame_info().component_count() as usize;
+        let mut scanned_component_count = 0;
+        loop {
+            let scan_info = self.reader.scan_info().cloned().ok_or(DecodingError::UnknownError)?;
+            scanned_component_count += scan_info.component_ids().len();
+            self.scans.push(scan_info);
+            BitReader::new(&mut self.reader).end_scan()?;
+            if scanned_component_count == component_count {
+                return self.reader.read_end_of_image();
+            }
+
+            self.reader.read_next_start_of_scan()?;
+        }
+    }
+
+    /// The parameters of the scans of the stream, in stream order, as read by read_scans.
+    pub fn scans(&self) -> &[ScanInfo] {
+        &self.scans
+    }
+
+    /// Returns where the decoder is in the structure of the stream, for example to find out which
+    /// part of the stream was read when a call failed.
+    pub fn state(&self) -> ReaderStatePublic {
+        self.reader.state()
+    }
+
+    /// MAXVAL: the largest sample value of the image, which an LSE segment can set to less than
+    /// 2^bits_per_sample - 1. Only valid after the header has been read.
+    pub fn maximum_sample_value(&self) -> u16 {
+        self.reader.maximum_sample_value() as u16
+    }
+
+    /// The NEAR parameter of the scan of every component, in frame order, as decoded by the last
+    /// decode call: for interleaved images all values are equal. Components of which the scan
+    /// wasn't decoded (see decode_components_subset) are left out; empty before decoding.
+    pub fn near_lossless_per_component(&self) -> Vec<u8> {
+        self.reader.component_ids().iter()
+            .filter_map(|id| self.scan_infos.iter().find(|scan_info| scan_info.component_ids().contains(id)))
+            .map(ScanInfo::near_lossless)
+            .collect()
+    }
+
+    /// The NEAR parameter of the first scan. Only valid after the header has been read.
+    pub fn near_lossless(&self) -> u8 {
+        self.reader.near_lossless()
+    }
+
+    /// The interleave mode of the first scan. Only valid after the header has been read.
+    pub fn interleave_mode(&self) -> InterleaveMode {
+        self.reader.interleave_mode()
+    }
+
+    /// The offset in bytes from the start of the stream to the entropy coded data, which starts
+    /// directly after the SOS segment. Only available after the header has been read.
+    /// For images with multiple scans this is the offset of the scan that was read last.
+    pub fn bitstream_offset(&self) -> Option<u64> {
+        self.reader.bitstream_offset()
+    }
+
+    /// The color transformation that was applied by the encoder. The decoder applies the
+    /// inverse transformation to images with 3 or 4 interleaved components.
+    /// Only valid after the header has been read.
+    pub fn color_transformation(&self) -> ColorTransformation {
+        self.reader.color_transformation()
+    }
+
+    /// How the decoded components should be interpreted for display, combining the color space of
+    /// the SPIFF header (if present), the component count and the color transformation.
+    /// Only valid after the header has been read.
+    pub fn effective_color_interpretation(&self) -> ColorInterpretation {
+        ColorInterpretation::new(self.spiff_header().map(|header| header.color_space()),
+                                 self.frame_info().component_count(), self.color_transformation())
+    }
+
+    /// Returns the mapping table with the given ID (see ISO/IEC 14495-1, C.2.4.1.2), if it was
+    /// defined in the segments read so far.
+    pub fn mapping_table(&self, table_id: u8) -> Option<&MappingTable> {
+        self.reader.mapping_table(table_id)
+    }
+
+    /// Returns an iterator over the markers of the stream and the byte ranges of their segments,
+    /// starting at the current position, without decoding. The decoder can't decode the image
+    /// after the iteration: use a separate decoder for walking the markers.
+    pub fn markers(&mut self) -> MarkerIter<'_, R> {
+        MarkerIter::new(&mut self.reader)
+    }
+
+    /// The size in bytes needed to store the decoded image.
+    pub fn destination_size(&self) -> usize {
+        self.output_stride() * self.frame_info().height() as usize * self.plane_count()
+    }
+
+    /// The number of rows that were completely decoded by the last decode call.
+    /// When decoding fails, for example with UnexpectedEndOfStream because the image is truncated,
+    /// the rows before this count contain valid samples.
+    /// For images encoded with interleave mode None this is the count of the last decoded component.
+    pub fn decoded_row_count(&self) -> u32 {
+        self.decoded_row_count
+    }
+
+    /// The first error that the last decode call recovered from with the BestEffort error policy.
+    /// None if the image was decoded without errors.
+    pub fn recovered_error(&self) -> Option<&DecodingError> {
+        self.recovered_error.as_ref()
+    }
+
+    /// The size of the decoded image with 1 or 2 bytes per sample divided by the size of the
+    /// stream from the SOI to the EOI marker, once the image has been decoded. None before the
+    /// EOI marker has been read.
+    pub fn compression_ratio(&self) -> Option<f64> {
+        let compressed_size = self.reader.compressed_size()?;
+        let frame_info = self.frame_info();
+        let uncompressed_size = frame_info.sample_count() as f64 * frame_info.bytes_per_sample() as f64;
+        Some(uncompressed_size / compressed_size as f64)
+    }
+
+    /// Decodes the image and returns the decoded samples.
+    pub fn decode(&mut self) -> Result<Vec<u8>, DecodingError> {
+        self.read_header()?;
+        let mut destination = vec![0; self.destination_size()];
+        self.decode_into(&mut destination)?;
+        Ok(destination)
+    }
+
+    /// Decodes the image as decode does, but stores samples of 2 bytes (9 to 16 bits per sample)
+    /// little endian on every platform, as the pixel data of DICOM requires.
+    pub fn decode_dicom_le(&mut self) -> Result<Vec<u8>, DecodingError> {
+        let mut decoded = self.decode()?;
+        if self.frame_info().bytes_per_sample() == 2 {
+            Endianness::Little.convert_from_native(&mut decoded);
+        }
+
+        Ok(decoded)
+    }
+
+    /// Decodes the image into a buffer owned by the decoder and returns the decoded samples, as
+    /// decode does but without allocating when the buffer is large enough. The buffer is part of
+    /// the DecoderBuffers of the decoder: the next decode_borrowed call (after reset or with a
+    /// decoder created with these buffers) overwrites it.
+    pub fn decode_borrowed(&mut self) -> Result<&[u8], DecodingError> {
+        self.read_header()?;
+        let mut output = std::mem::take(&mut self.buffers.output);
+        output.resize(self.destination_size(), 0);
+        let result = self.decode_into(&mut output);
+        self.buffers.output = output;
+        result?;
+        Ok(&self.buffers.output)
+    }
+
+    /// Decodes the image, encodes the decoded samples again and checks that this reproduces the
+    /// stream byte for byte, which proves that the image was decoded exactly. Only streams with
+    /// the segments that Encoder writes can be reproduced: no COM, APPn or LSE segments and no
+    /// color transformation. The samples are returned as by decode, with the channel order Rgb
+    /// and without packing. Fails with VerificationFailed when the stream isn't reproduced or when
+    /// the start of the stream is no longer buffered because image data was already decoded.
+    pub fn decode_verified(&mut self) -> Result<Vec<u8>, DecodingError> {
+        if !self.reader.start_recording() {
+            return Err(DecodingError::VerificationFailed);
+        }
+
+        let channel_order = std::mem::replace(&mut self.channel_order, ChannelOrder::Rgb);
+        let alpha_component_id = self.alpha_component_id.take();
+        let result = self.decode_unpacked();
+        self.channel_order = channel_order;
+        self.alpha_component_id = alpha_component_id;
+        let original = self.reader.take_recording().ok_or(DecodingError::UnknownError)?;
+        let decoded = result?;
+
+        let mut encoder = Encoder::new(self.frame_info().clone());
+        encoder.set_interleave_mode(self.interleave_mode());
+        encoder.set_near_lossless(self.near_lossless());
+        if let Some(spiff_header) = self.spiff_header() {
+            encoder.write_spiff_header(spiff_header.clone()).map_err(|_| DecodingError::VerificationFailed)?;
+        }
+
+        match encoder.encode(&decoded) {
+            Ok(encoded) if encoded == original => Ok(decoded),
+            _ => Err(DecodingError::VerificationFailed),
+        }
+    }
+
+    /// Decodes the image into destination with every sample zero-extended to a u32, independent of
+    /// the bits per sample. Destination must have exactly width * height * component_count elements;
+    /// the samples are ordered as by decode.
+    pub fn decode_to_u32_into(&mut self, destination: &mut [u32]) -> Result<(), DecodingError> {
+        self.read_header()?;
+        let frame_info = self.frame_info();
+        if destination.len() != frame_info.width() as usize * frame_info.height() as usize *
+            frame_info.component_count() as usize {
+            return Err(DecodingError::InvalidParameterDestinationSize);
+        }
+
+        let decoded = self.decode_unpacked()?;
+        if self.frame_info().bytes_per_sample() == 1 {
+            for (sample, &value) in destination.iter_mut().zip(&decoded) {
+                *sample = value as u32;
+            }
+        } else {
+            for (sample, bytes) in destination.iter_mut().zip(decoded.chunks_exact(2)) {
+                *sample = u16::from_ne_bytes([bytes[0], bytes[1]]) as u32;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Decodes the image into destination as signed values: every sample minus the sample offset.
+    /// T is typically i16 or i32. Destination must have exactly width * height * component_count
+    /// elements; the samples are ordered as by decode. Fails with InvalidParameterSampleOffset if
+    /// the range 0..=MAXVAL minus the sample offset doesn't fit in T.
+    pub fn decode_signed_into<T: TryFrom<i32>>(&mut self, destination: &mut [T]) -> Result<(), DecodingError> {
+        self.read_header()?;
+        let maximum_sample_value = self.reader.maximum_sample_value();
+        let sample_offset = self.sample_offset;
+        let to_signed = |sample: i32| {
+            sample.checked_sub(sample_offset)
+                .and_then(|value| T::try_from(value).ok())
+                .ok_or(DecodingError::InvalidParameterSampleOffset)
+        };
+        to_signed(0)?;
+        to_signed(maximum_sample_value)?;
+
+        let mut samples = vec![0; destination.len()];
+        self.decode_to_u32_into(&mut samples)?;
+        for (value, &sample) in destination.iter_mut().zip(&samples) {
+            *value = to_signed(sample as i32)?;
+        }
+
+        Ok(())
+    }
+
+    /// Decodes the image into an array with the shape (height, width, component count), with u8
+    /// elements for 2 to 8 bits per sample and u16 elements for 9 to 16 bits per sample. The
+    /// array is in standard layout for all interleave modes; the samples are never packed.
+    #[cfg(feature = "ndarray")]
+    pub fn decode_to_ndarray(&mut self) -> Result<DecodedArray, DecodingError> {
+        let decoded = self.decode_unpacked()?;
+        let planar = self.interleave_mode() == InterleaveMode::None;
+        DecodedArray::new(self.frame_info(), planar, decoded)
+    }
+
+    /// Decodes an image of which every component uses a mapping table, and replaces every sample by
+    /// the table entry that it indexes. The entries of all tables must have the same size, which
+    /// becomes the size of a sample in the returned data; the samples are ordered as by decode and
+    /// never packed.
+    pub fn decode_with_mapping_tables(&mut self) -> Result<Vec<u8>, DecodingError> {
+        let decoded = self.decode_unpacked()?;
+        let tables = self.scan_infos.iter()
+            .flat_map(|scan_info| scan_info.mapping_table_ids())
+            .map(|&table_id| self.mapping_table(table_id).ok_or(DecodingError::InvalidParameterMappingTableId))
+            .collect::<Result<Vec<&MappingTable>, DecodingError>>()?;
+        let entry_size = tables[0].entry_size();
+        if tables.iter().any(|table| table.entry_size() != entry_size) {
+            return Err(DecodingError::InvalidParameterMappingTableId);
+        }
+
+        let frame_info = self.frame_info();
+        let bytes_per_sample = frame_info.bytes_per_sample();
+        let pixel_count = frame_info.width() as usize * frame_info.height() as usize;
+        let planar = self.interleave_mode() == InterleaveMode::None;
+        let mut mapped = Vec::with_capacity(decoded.len() / bytes_per_sample * entry_size as usize);
+        for (index, sample) in decoded.chunks_exact(bytes_per_sample).enumerate() {
+            let table = tables[if planar { index / pixel_count } else { index % tables.len() }];
+            let table_index = match *sample {
+                [value] => value as usize,
+                [low, high] => u16::from_ne_bytes([low, high]) as usize,
+                _ => unreachable!(),
+            };
+            mapped.extend_from_slice(table.entry(table_index).ok_or(DecodingError::MappingTableIndexOutOfRange)?);
+        }
+
+        Ok(mapped)
+    }
+
+    /// Decodes the image as decode does, but with one sample per 1 or 2 bytes also when packed
+    /// output is set: for the methods that convert the decoded samples.
+    fn decode_unpacked(&mut self) -> Result<Vec<u8>, DecodingError> {
+        let packed = std::mem::replace(&mut self.packed, false);
+        let result = self.decode();
+        self.packed = packed;
+        result
+    }
+
+    /// Decodes the complete image without keeping the decoded samples, to check that the stream
+    /// can be decoded. Returns the frame info on success or the first error encountered.
+    pub fn verify(&mut self) -> Result<FrameInfo, DecodingError> {
+        self.decode()?;
+        Ok(self.frame_info().clone())
+    }
+
+    /// Decodes the image as decode does, but stores every decoded row in the same row buffer and
+    /// discards it, to measure the cost of decoding without that of writing the output image.
+    /// Packing is not applied. Returns the frame info on success or the first error encountered.
+    pub fn decode_to_sink(&mut self) -> Result<FrameInfo, DecodingError> {
+        self.read_header()?;
+        if self.coding_variant() != CodingVariant::Baseline {
+            return Err(DecodingError::EncodingNotSupported);
+        }
+
+        let mut row = vec![0; self.minimum_stride()];
+        self.decode_planes(&mut row, 0, 0)?;
+        Ok(self.frame_info().clone())
+    }
+
+    /// Decodes the image into destination, which must be at least destination_size() bytes.
+    /// Returns the number of completely decoded rows (see decoded_row_count): the height of the
+    /// image, or less when the BestEffort error policy filled rows that could not be decoded.
+    /// If an error occurs while decoding the entropy coded data, the rows that have been
+    /// decoded are stored in destination and decoded_row_count tells how many rows are valid.
+    pub fn decode_into(&mut self, destination: &mut [u8]) -> Result<usize, DecodingError> {
+        self.read_header()?;
+        let stride = self.output_stride();
+        self.decode_into_with_stride(destination, stride)
+    }
+
+    /// Decodes the image into destination, storing each decoded row at row * stride.
+    /// The bytes between the end of a row and the start of the next row are not modified.
+    /// For images encoded with interleave mode None, the rows of component n start at
+    /// n * height * stride. With packed output the stride is the distance between packed rows.
+    /// Returns the number of completely decoded rows, as decode_into.
+    pub fn decode_into_with_stride(&mut self, destination: &mut [u8], stride: usize) -> Result<usize, DecodingError> {
+        self.read_header()?;
+        if self.coding_variant() != CodingVariant::Baseline {
+            return Err(DecodingError::EncodingNotSupported);
+        }
+
+        if self.is_packed() {
+            self.decode_packed_into(destination, stride)?;
+        } else {
+            self.decode_unpacked_into(destination, stride)?;
+        }
+
+        Ok(self.decoded_row_count as usize)
+    }
+
+    fn decode_unpacked_into(&mut self, destination: &mut [u8], stride: usize) -> Result<(), DecodingError> {
+        let minimum_stride = self.minimum_stride();
+        if stride < minimum_stride {
+            return Err(DecodingError::InvalidParameterStride);
+        }
+
+        let bytes_per_plane = stride * self.frame_info().height() as usize;
+        if destination.len() < bytes_per_plane * self.plane_count() - (stride - minimum_stride) {
+            return Err(DecodingError::DestinationTooSmall);
+        }
+
+        self.decode_planes(destination, stride, bytes_per_plane)
+    }
+
+    /// Decodes the scans of the image. For images encoded with interleave mode None the rows of
+    /// component n start at n * bytes_per_plane.
+    fn decode_planes(&mut self, destination: &mut [u8], stride: usize,
+                     bytes_per_plane: usize) -> Result<(), DecodingError> {
+        let frame_info = self.frame_info().clone();
+        let plane_count = self.plane_count();
+        let minimum_stride = self.minimum_stride();
+        self.decoded_row_count = 0;
+        self.scan_infos.clear();
+        self.recovered_error = None;
+        self.component_value_ranges.clear();
+        if self.interleave_mode() == InterleaveMode::None {
+            for component in 0..plane_count {
+                if component != 0 {
+                    if let Err(error) = self.reader.read_next_start_of_scan() {
+                        // Without a next scan the remaining components cannot be decoded.
+                        self.recover(error)?;
+                        for component in component..plane_count {
+                            let plane = &mut destination[self.plane_index(component) * bytes_per_plane..];
+                            self.fill_rows(plane, stride, minimum_stride, 0);
+                        }
+                        return Ok(());
+                    }
+                }
+
+                let plane = &mut destination[self.plane_index(component) * bytes_per_plane..];
+                self.decode_scan(&frame_info, 1, plane, stride)?;
+            }
+        } else {
+            self.decode_scan(&frame_info, frame_info.component_count(), destination, stride)?;
+        }
+
+        match self.reader.read_end_of_image() {
+            Err(error) => self.recover(error),
+            result => result,
+        }
+    }
+
+    /// Decodes only the components with the given indices (in frame order, starting at 0) of an
+    /// image encoded with interleave mode None. destinations contains for every wanted component a
+    /// plane of width * height samples. The scans of the other components are skipped without
+    /// decoding them: the reader moves directly to the marker after their entropy coded data.
+    pub fn decode_components_subset(&mut self, wanted: &[u8],
+                                    destinations: &mut [&mut [u8]]) -> Result<(), DecodingError> {
+        self.read_header()?;
+        if self.coding_variant() != CodingVariant::Baseline {
+            return Err(DecodingError::EncodingNotSupported);
+        }
+
+        if self.interleave_mode() != InterleaveMode::None {
+            return Err(DecodingError::InvalidParameterInterleaveMode);
+        }
+
+        let frame_info = self.frame_info().clone();
+        let component_count = frame_info.component_count();
+        if wanted.iter().enumerate().any(|(index, &component)| {
+            component >= component_count || wanted[..index].contains(&component)
+        }) {
+            return Err(DecodingError::InvalidParameterComponentCount);
+        }
+
+        if destinations.len() != wanted.len() {
+            return Err(DecodingError::InvalidParameterDestinationSize);
+        }
+
+        let stride = self.minimum_stride();
+        if destinations.iter().any(|destination| destination.len() < stride * frame_info.height() as usize) {
+            return Err(DecodingError::DestinationTooSmall);
+        }
+
+        self.decoded_row_count = 0;
+        self.scan_infos.clear();
+        self.recovered_error = None;
+        self.component_value_ranges.clear();
+        for component in 0..component_count {
+            if component != 0 {
+                self.reader.read_next_start_of_scan()?;
+            }
+
+            match wanted.iter().position(|&wanted_component| wanted_component == component) {
+                Some(index) => self.decode_scan(&frame_info, 1, destinations[index], stride)?,
+                None => BitReader::new(&mut self.reader).end_scan()?,
+            }
+        }
+
+        self.reader.read_end_of_image()
+    }
+
+    /// Decodes the scans of an image encoded with interleave mode None in bands of band_height
+    /// rows, calling callback with the component, the first row and the samples of every band.
+    /// Only a single band is stored: the scan decoder keeps its state between the bands.
+    pub(crate) fn decode_bands(&mut self, band_height: u32,
+                               callback: &mut dyn FnMut(u8, u32, &[u8])) -> Result<(), DecodingError> {
+        self.read_header()?;
+        if self.coding_variant() != CodingVariant::Baseline {
+            return Err(DecodingError::EncodingNotSupported);
+        }
+
+        if self.interleave_mode() != InterleaveMode::None {
+            return Err(DecodingError::InvalidParameterInterleaveMode);
+        }
+
+        let frame_info = self.frame_info().clone();
+        let height = frame_info.height();
+        let band_height = band_height.clamp(1, height);
+        let stride = self.minimum_stride();
+        let mut band = vec![0; stride * band_height as usize];
+
+        self.decoded_row_count = 0;
+        self.scan_infos.clear();
+        self.recovered_error = None;
+        self.component_value_ranges.clear();
+        for component in 0..frame_info.component_count() {
+            if component != 0 {
+                self.reader.read_next_start_of_scan()?;
+            }
+
+            let scan_info = self.reader.scan_info().cloned().ok_or(DecodingError::UnknownError)?;
+            let preset_coding_parameters = self.reader.validated_preset_coding_parameters()?;
+            let mut scan_decoder = ScanDecoder::new(&frame_info, 1, InterleaveMode::None, scan_info.near_lossless(),
+                                                    &preset_coding_parameters, std::mem::take(&mut self.buffers),
+                                                    self.row_callback.take());
+            self.scan_infos.push(scan_info);
+
+            let mut bit_reader = BitReader::new(&mut self.reader);
+            let mut result = Ok(());
+            let mut first_row = 0;
+            while first_row < height {
+                let row_count = band_height.min(height - first_row);
+                let band = &mut band[..row_count as usize * stride];
+                result = scan_decoder.decode_band(&mut bit_reader, band, stride, row_count as usize);
+                if result.is_err() {
+                    break;
+                }
+
+                callback(component, first_row, band);
+                first_row += row_count;
+            }
+
+            let result = result.and_then(|()| bit_reader.end_scan());
+            self.decoded_row_count = scan_decoder.decoded_line_count();
+            self.row_callback = scan_decoder.take_row_callback();
+            self.buffers = scan_decoder.into_buffers();
+            result?;
+        }
+
+        self.reader.read_end_of_image()
+    }
+
+    /// The position in the frame of the component that is stored as the alpha channel, when it
+    /// isn't the last component.
+    fn alpha_component(&self) -> Option<usize> {
+        let alpha_component_id = self.alpha_component_id?;
+        if self.frame_info().component_count() != 4 {
+            return None;
+        }
+
+        self.reader.component_ids().iter().position(|&id| id == alpha_component_id).filter(|&position| position != 3)
+    }
+
+    /// The position of the plane of a component in the output of an image with interleave mode None.
+    fn plane_index(&self, component: usize) -> usize {
+        let component = match self.alpha_component() {
+            Some(alpha) if component == alpha => 3,
+            Some(alpha) if component > alpha => component - 1,
+            _ => component,
+        };
+        self.effective_channel_order().plane_index(component)
+    }
+
+    /// The channel order is only applied to images with 3 or 4 components.
+    fn effective_channel_order(&self) -> ChannelOrder {
+        match self.frame_info().component_count() {
+            3 | 4 => self.channel_order,
+            _ => ChannelOrder::Rgb,
+        }
+    }
+
+    /// Returns the error with the Abort error policy. With BestEffort the first error is stored
+    /// as the recovered error and decoding can continue.
+    fn recover(&mut self, error: DecodingError) -> Result<(), DecodingError> {
+        if self.error_policy == ErrorPolicy::Abort {
+            return Err(error);
+        }
+
+        self.recovered_error.get_or_insert(error);
+        Ok(())
+    }
+
+    /// Fills the rows from first_row to the height of the image with the fill value.
+    fn fill_rows(&self, destination: &mut [u8], stride: usize, row_length: usize, first_row: u32) {
+        let frame_info = self.frame_info();
+        let sample = self.fill_value.min(self.maximum_sample_value());
+        for row in first_row as usize..frame_info.height() as usize {
+            let row = &mut destination[row * stride..row * stride + row_length];
+            if frame_info.bits_per_sample() <= 8 {
+                row.fill(sample as u8);
+            } else {
+                for bytes in row.chunks_exact_mut(2) {
+                    bytes.copy_from_slice(&sample.to_ne_bytes());
+                }
+            }
+        }
+    }
+
+    /// Decodes the image with 1 byte per sample and packs every row into destination.
+    fn decode_packed_into(&mut self, destination: &mut [u8], stride: usize) -> Result<(), DecodingError> {
+        let packed_stride = self.output_stride();
+        if stride < packed_stride {
+            return Err(DecodingError::InvalidParameterStride);
+        }
+
+        let row_count = self.frame_info().height() as usize * self.plane_count();
+        if destination.len() < stride * row_count - (stride - packed_stride) {
+            return Err(DecodingError::DestinationTooSmall);
+        }
+
+        let unpacked_stride = self.minimum_stride();
+        let mut unpacked = vec![0; unpacked_stride * row_count];
+        let result = self.decode_unpacked_into(&mut unpacked, unpacked_stride);
+
+        // Also pack the rows when the BestEffort error policy recovered from an error.
+        let bits_per_sample = self.frame_info().bits_per_sample();
+        for (row, packed_row) in unpacked.chunks_exact(unpacked_stride).zip(destination.chunks_mut(stride)) {
+            pack_row(row, bits_per_sample, &mut packed_row[..packed_stride]);
+        }
+
+        result
+    }
+
+    fn is_packed(&self) -> bool {
+        self.packed && self.frame_info().bits_per_sample() <= 4
+    }
+
+    /// The number of planes of the decoded image: 1 for each component for images encoded with
+    /// interleave mode None, 1 for the other interleave modes.
+    fn plane_count(&self) -> usize {
+        if self.interleave_mode() == InterleaveMode::None {
+            self.frame_info().component_count() as usize
+        } else {
+            1
+        }
+    }
+
+    /// The number of bytes of an output row, packed or with 1 or 2 bytes per sample.
+    fn output_stride(&self) -> usize {
+        if self.is_packed() {
+            let samples_per_row = self.minimum_stride();
+            (samples_per_row * self.frame_info().bits_per_sample() as usize).div_ceil(8)
+        } else {
+            self.minimum_stride()
+        }
+    }
+
+    /// The number of bytes of a decoded row. For images encoded with interleave mode None, a row
+    /// holds the samples of a single component.
+    fn minimum_stride(&self) -> usize {
+        let frame_info = self.frame_info();
+        let components_in_row = if self.interleave_mode() == InterleaveMode::None {
+            1
+        } else {
+            frame_info.component_count() as usize
+        };
+        frame_info.width() as usize * components_in_row * frame_info.bytes_per_sample()
+    }
+
+    /// Decodes the scan of which the SOS segment was read last, with the NEAR value of that scan.
+    /// With the BestEffort error policy the rows that cannot be decoded are filled with the fill value.
+    fn decode_scan(&mut self, frame_info: &FrameInfo, component_count: u8, destination: &mut [u8],
+                   stride: usize) -> Result<(), DecodingError> {
+        let scan_info = self.reader.scan_info().cloned().ok_or(DecodingError::UnknownError)?;
+        let preset_coding_parameters = self.reader.validated_preset_coding_parameters()?;
+        let interleave_mode = scan_info.interleave_mode();
+        let near_lossless = scan_info.near_lossless();
+        let first_component = self.reader.component_ids().iter()
+            .position(|&id| scan_info.component_ids().first() == Some(&id))
+            .ok_or(DecodingError::UnknownError)?;
+        self.scan_infos.push(scan_info);
+        if self.collect_value_ranges {
+            self.component_value_ranges.resize(frame_info.component_count() as usize, (u32::MAX, 0));
+        }
+
+        let color_transformation = self.color_transformation();
+        let transform = color_transformation != ColorTransformation::None &&
+            self.interleave_mode() != InterleaveMode::None &&
+            (component_count == 3 || component_count == 4);
+        if transform && frame_info.bits_per_sample() != 8 && frame_info.bits_per_sample() != 16 {
+            return Err(DecodingError::BitDepthForTransformNotSupported);
+        }
+
+        let mut scan_decoder = ScanDecoder::new(frame_info, component_count, interleave_mode, near_lossless,
+                                                &preset_coding_parameters, std::mem::take(&mut self.buffers),
+                                                self.row_callback.take());
+
+        let channel_order = if self.interleave_mode() == InterleaveMode::None {
+            ChannelOrder::Rgb
+        } else {
+            self.effective_channel_order()
+        };
+        let row_length = self.minimum_stride();
+        let mut bit_reader = BitReader::new(&mut self.reader);
+        let result = scan_decoder.decode(&mut bit_reader, destination, stride);
+        // After an error BestEffort skips the rest of the corrupted data, up to the next marker. Abort
+        // returns directly: the corrupted data may not end (like an endless stream of 0 bytes).
+        let result = match result {
+            Err(error) if self.error_policy == ErrorPolicy::Abort => Err(error),
+            result => result.and(bit_reader.end_scan()),
+        };
+        self.decoded_row_count = scan_decoder.decoded_line_count();
+        self.row_callback = scan_decoder.take_row_callback();
+        self.buffers = scan_decoder.into_buffers();
+
+        let alpha_component = if self.interleave_mode() == InterleaveMode::None {
+            None
+        } else {
+            self.alpha_component()
+        };
+        if transform || alpha_component.is_some() || channel_order != ChannelOrder::Rgb || self.collect_value_ranges {
+            let bytes_per_sample = frame_info.bytes_per_sample();
+            for row in 0..self.decoded_row_count as usize {
+                let row = &mut destination[row * stride..row * stride + row_length];
+                // The rows hold the reconstructed samples, which for NEAR > 0 are already limited to
+                // the sample range per component: the inverse transformation follows, as in CharLS.
+                if transform {
+                    color_transformation.inverse_transform_row(row, component_count as usize, bytes_per_sample);
+                }
+
+                if self.collect_value_ranges {
+                    let ranges = &mut self.component_value_ranges[first_component..];
+                    update_value_ranges(ranges, row, component_count as usize, bytes_per_sample);
+                }
+
+                if let Some(alpha) = alpha_component {
+                    // Moves the alpha sample of every pixel to the end.
+                    for pixel in row.chunks_exact_mut(component_count as usize * bytes_per_sample) {
+                        pixel[alpha * bytes_per_sample..].rotate_left(bytes_per_sample);
+                    }
+                }
+
+                if channel_order != ChannelOrder::Rgb {
+                    channel_order.apply_to_row(row, component_count as usize, bytes_per_sample);
+                }
+            }
+        }
+
+        if let Err(error) = result {
+            self.recover(error)?;
+            self.fill_rows(destination, stride, row_length, self.decoded_row_count);
+        }
+
+        Ok(())
+    }
+}
+
+
+/// Adds the samples of a row with component_count interleaved components to the (minimum,
+/// maximum) ranges of these components.
+fn update_value_ranges(ranges: &mut [(u32, u32)], row: &[u8], component_count: usize, bytes_per_sample: usize) {
+    for (index, bytes) in row.chunks_exact(bytes_per_sample).enumerate() {
+        let value = match bytes {
+            [value] => *value as u32,
+            bytes => u16::from_ne_bytes([bytes[0], bytes[1]]) as u32,
+        };
+        let range = &mut ranges[index % component_count];
+        range.0 = range.0.min(value);
+        range.1 = range.1.max(value);
+    }
+}
+
+/// Packs the samples of a row MSB-first into packed, filling the unused low bits of the last
+/// byte with 0.
+fn pack_row(samples: &[u8], bits_per_sample: u8, packed: &mut [u8]) {
+    let bits_per_sample = bits_per_sample as usize;
+    packed.fill(0);
+    for (index, &sample) in samples.iter().enumerate() {
+        let bit_position = index * bits_per_sample;
+        let shift = 16 - bits_per_sample - bit_position % 8;
+        let value = (sample as u16) << shift;
+        packed[bit_position / 8] |= (value >> 8) as u8;
+        if shift < 8 {
+            packed[bit_position / 8 + 1] |= value as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::MAXIMUM_COMPONENT_COUNT;
+    use crate::frame_info::{Height, Width};
+    use crate::jpeg_marker_code::JpegMarkerCode;
+    use crate::simd_algorithm;
+
+    // A 16 x 16, 8 bit monochrome image, lossless encoded by CharLS.
+    const GRAY8_16X16: &[u8] = include_bytes!("../tests/fixtures/gray8_16x16.jls");
+    const START_OF_BIT_STREAM: usize = 0x19;
+
+    // A 16 x 16, 4 bit monochrome image, lossless encoded by CharLS.
+    const GRAY4_16X16: &[u8] = include_bytes!("../tests/fixtures/gray4_16x16.jls");
+
+    // A 32 x 8, 8 bit monochrome image with runs, encoded by CharLS lossless and with NEAR = 2.
+    // The runs are interrupted mid-line and at the last sample of a line; the interruptions are
+    // coded with RItype 0 (Ra != Rb) and RItype 1 (Ra == Rb).
+    const RUNS8_32X8: &[u8] = include_bytes!("../tests/fixtures/runs8_32x8.jls");
+    const RUNS8_32X8_NEAR2: &[u8] = include_bytes!("../tests/fixtures/runs8_32x8_near2.jls");
+    const RUNS8_32X8_NEAR2_DECODED: &[u8] = include_bytes!("../tests/fixtures/runs8_32x8_near2.raw");
+
+    // A 16 x 16, 8 bit monochrome image with LSE MAXVAL = 100, encoded by CharLS lossless and with
+    // NEAR = 2.
+    const MAXVAL100_16X16: &[u8] = include_bytes!("../tests/fixtures/maxval100_16x16.jls");
+    const MAXVAL100_16X16_NEAR2: &[u8] = include_bytes!("../tests/fixtures/maxval100_16x16_near2.jls");
+    const MAXVAL100_16X16_DECODED: &[u8] = include_bytes!("../tests/fixtures/maxval100_16x16.raw");
+
+    // 5 x 8, 8 bit monochrome and sample interleaved RGB images encoded by CharLS and the images as
+    // decoded by CharLS. The samples of the last column differ much from the other columns: with a
+    // wrong Rd for the last sample (instead of Rb) its contexts and therefore the decoded samples
+    // are different.
+    const EDGE_FIXTURES: [(&[u8], &[u8]); 2] = [
+        (include_bytes!("../tests/fixtures/edge8_5x8.jls"), include_bytes!("../tests/fixtures/edge8_5x8.raw")),
+        (include_bytes!("../tests/fixtures/edge_rgb8_sample_5x8.jls"),
+         include_bytes!("../tests/fixtures/edge_rgb8_sample_5x8.raw")),
+    ];
+
+    // 8 x 8 RGB(A) images encoded by CharLS with its HP color transformations and the images as
+    // decoded by CharLS. 16 bit samples are stored little endian.
+    const COLOR_TRANSFORMATION_FIXTURES: [(&[u8], &[u8], ColorTransformation); 6] = [
+        (include_bytes!("../tests/fixtures/rgb8_hp1_sample_8x8.jls"),
+         include_bytes!("../tests/fixtures/rgb8_hp1_sample_8x8.raw"), ColorTransformation::Hp1),
+        (include_bytes!("../tests/fixtures/rgb8_hp2_sample_8x8.jls"),
+         include_bytes!("../tests/fixtures/rgb8_hp2_sample_8x8.raw"), ColorTransformation::Hp2),
+        (include_bytes!("../tests/fixtures/rgb8_hp3_sample_8x8.jls"),
+         include_bytes!("../tests/fixtures/rgb8_hp3_sample_8x8.raw"), ColorTransformation::Hp3),
+        (include_bytes!("../tests/fixtures/rgb8_hp2_line_8x8.jls"),
+         include_bytes!("../tests/fixtures/rgb8_hp2_line_8x8.raw"), ColorTransformation::Hp2),
+        (include_bytes!("../tests/fixtures/rgb16_hp3_sample_8x8.jls"),
+         include_bytes!("../tests/fixtures/rgb16_hp3_sample_8x8.raw"), ColorTransformation::Hp3),
+        (include_bytes!("../tests/fixtures/rgba8_hp1_line_8x8.jls"),
+         include_bytes!("../tests/fixtures/rgba8_hp1_line_8x8.raw"), ColorTransformation::Hp1),
+    ];
+
+    // A 16 x 16, 8 bit 4 component (CMYK) image with a flat band, lossless encoded by CharLS in all
+    // interleave modes, and the decoded image (interleaved, or component by component for None).
+    const CMYK8_16X16_FIXTURES: [(&[u8], &[u8], InterleaveMode); 3] = [
+        (include_bytes!("../tests/fixtures/cmyk8_sample_16x16.jls"),
+         include_bytes!("../tests/fixtures/cmyk8_sample_16x16.raw"), InterleaveMode::Sample),
+        (include_bytes!("../tests/fixtures/cmyk8_line_16x16.jls"),
+         include_bytes!("../tests/fixtures/cmyk8_line_16x16.raw"), InterleaveMode::Line),
+        (include_bytes!("../tests/fixtures/cmyk8_planar_16x16.jls"),
+         include_bytes!("../tests/fixtures/cmyk8_planar_16x16.raw"), InterleaveMode::None),
+    ];
+
+    // A 16 x 16, 8 bit RGB image, lossless encoded by CharLS with interleave mode None.
+    const RGB8_PLANAR_16X16: &[u8] = include_bytes!("../tests/fixtures/rgb8_planar_16x16.jls");
+
+    // The same image, lossless encoded by CharLS with interleave mode Sample and Line.
+    const RGB8_SAMPLE_16X16: &[u8] = include_bytes!("../tests/fixtures/rgb8_sample_16x16.jls");
+    const RGB8_LINE_16X16: &[u8] = include_bytes!("../tests/fixtures/rgb8_line_16x16.jls");
+
+    fn expected_runs8_sample(x: u32, y: u32) -> u8 {
+        let interrupted = if (y & 1) == 0 { x == 5 || x == 31 } else { x == 5 || x == 20 };
+        if interrupted { 180 + y as u8 } else { 100 }
+    }
+
+    fn expected_gray8_sample(x: u32, y: u32) -> u8 {
+        ((x * 7 + y * 13 + ((x * y) % 11) * 9) & 0xFF) as u8
+    }
+
+    fn check_rows(decoded: &[u8], row_count: u32) {
+        for y in 0..row_count {
+            for x in 0..16 {
+                assert_eq!(decoded[(y * 16 + x) as usize], expected_gray8_sample(x, y), "x = {}, y = {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn decode_monochrome_8_bit() {
+        let mut decoder = Decoder::new(GRAY8_16X16);
+
+        let decoded = decoder.decode().unwrap();
+
+        assert_eq!(*decoder.frame_info(), FrameInfo::new(Width(16), Height(16), 8, 1));
+        assert_eq!(decoded.len(), 16 * 16);
+        assert_eq!(decoder.decoded_row_count(), 16);
+        check_rows(&decoded, 16);
+    }
+
+    #[test]
+    fn decode_monochrome_4_bit() {
+        let mut decoder = Decoder::new(GRAY4_16X16);
+
+        let decoded = decoder.decode().unwrap();
+
+        assert_eq!(decoder.frame_info().bits_per_sample(), 4);
+        assert_eq!(decoded.len(), 16 * 16);
+        for y in 0..16 {
+            for x in 0..16 {
+                let expected = ((x + y * 3 + (x * y) % 5) & 0x0F) as u8;
+                assert_eq!(decoded[(y * 16 + x) as usize], expected, "x = {}, y = {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn decode_runs_interrupted_mid_line_and_at_line_end() {
+        let mut decoder = Decoder::new(RUNS8_32X8);
+
+        let decoded = decoder.decode().unwrap();
+
+        for y in 0..8 {
+            for x in 0..32 {
+                assert_eq!(decoded[(y * 32 + x) as usize], expected_runs8_sample(x, y), "x = {}, y = {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn decode_near_lossless_runs_interrupted_mid_line_and_at_line_end() {
+        let mut decoder = Decoder::new(RUNS8_32X8_NEAR2);
+
+        let decoded = decoder.decode().unwrap();
+
+        assert_eq!(decoder.near_lossless(), 2);
+        assert_eq!(decoded, RUNS8_32X8_NEAR2_DECODED);
+        for y in 0..8 {
+            for x in 0..32 {
+                let sample = decoded[(y * 32 + x) as usize] as i32;
+                assert!((sample - expected_runs8_sample(x, y) as i32).abs() <= 2, "x = {}, y = {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn decode_color_transformed_images_identical_to_charls() {
+        for (encoded, reference, color_transformation) in COLOR_TRANSFORMATION_FIXTURES {
+            let mut decoder = Decoder::new(encoded);
+
+            let decoded = decoder.decode().unwrap();
+
+            assert_eq!(decoder.color_transformation(), color_transformation);
+            if decoder.frame_info().bits_per_sample() <= 8 {
+                assert_eq!(decoded, reference);
+            } else {
+                let reference: Vec<u8> = reference
+                    .chunks_exact(2)
+                    .flat_map(|sample| u16::from_le_bytes([sample[0], sample[1]]).to_ne_bytes())
+                    .collect();
+                assert_eq!(decoded, reference);
+            }
+        }
+    }
+
+    #[test]
+    fn decode_4_component_images_in_all_interleave_modes() {
+        for (encoded, reference, interleave_mode) in CMYK8_16X16_FIXTURES {
+            let mut decoder = Decoder::new(encoded);
+
+            let decoded = decoder.decode().unwrap();
+
+            assert_eq!(decoder.frame_info().component_count(), 4);
+            assert_eq!(decoder.interleave_mode(), interleave_mode);
+            assert_eq!(decoded.len(), 16 * 16 * 4);
+            assert_eq!(decoded, reference, "{:?}", interleave_mode);
+        }
+    }
+
+    #[test]
+    fn decode_4_component_planar_and_sample_interleaved_images_are_the_same() {
+        let sample_interleaved = Decoder::new(CMYK8_16X16_FIXTURES[0].0).decode().unwrap();
+        let planar = Decoder::new(CMYK8_16X16_FIXTURES[2].0).decode().unwrap();
+
+        for component in 0..4 {
+            for index in 0..16 * 16 {
+                assert_eq!(planar[component * 16 * 16 + index], sample_interleaved[index * 4 + component],
+                           "component = {}, index = {}", component, index);
+            }
+        }
+    }
+
+    #[test]
+    fn decode_rgb_image_with_bgr_channel_order() {
+        for encoded in [RGB8_SAMPLE_16X16, RGB8_LINE_16X16] {
+            let rgb = Decoder::new(encoded).decode().unwrap();
+            let mut decoder = Decoder::new(encoded);
+            decoder.set_channel_order(ChannelOrder::Bgr);
+
+            let bgr = decoder.decode().unwrap();
+
+            for (rgb_pixel, bgr_pixel) in rgb.chunks_exact(3).zip(bgr.chunks_exact(3)) {
+                assert_eq!(bgr_pixel, [rgb_pixel[2], rgb_pixel[1], rgb_pixel[0]]);
+            }
+        }
+    }
+
+    #[test]
+    fn decode_planar_rgb_image_with_bgr_channel_order() {
+        let rgb = Decoder::new(RGB8_PLANAR_16X16).decode().unwrap();
+        let mut decoder = Decoder::new(RGB8_PLANAR_16X16);
+        decoder.set_channel_order(ChannelOrder::Bgr);
+
+        let bgr = decoder.decode().unwrap();
+
+        assert_eq!(bgr[..256], rgb[512..]);
+        assert_eq!(bgr[256..512], rgb[256..512]);
+        assert_eq!(bgr[512..], rgb[..256]);
+    }
+
+    #[test]
+    fn decode_color_transformed_image_with_bgr_channel_order_keeps_alpha_last() {
+        let (encoded, reference, _) = COLOR_TRANSFORMATION_FIXTURES[5];
+        let mut decoder = Decoder::new(encoded);
+        decoder.set_channel_order(ChannelOrder::Bgr);
+
+        let bgra = decoder.decode().unwrap();
+
+        for (rgba_pixel, bgra_pixel) in reference.chunks_exact(4).zip(bgra.chunks_exact(4)) {
+            assert_eq!(bgra_pixel, [rgba_pixel[2], rgba_pixel[1], rgba_pixel[0], rgba_pixel[3]]);
+        }
+    }
+
+    #[test]
+    fn decode_color_transformation_with_unsupported_bit_depth_fails() {
+        let mut encoded = COLOR_TRANSFORMATION_FIXTURES[0].0.to_vec();
+        assert_eq!(encoded[15], 8);
+        encoded[15] = 12; // P = Sample precision in the SOF segment.
+        let mut decoder = Decoder::new(encoded.as_slice());
+
+        let error = decoder.decode().unwrap_err();
+
+        assert_eq!(error, DecodingError::BitDepthForTransformNotSupported);
+    }
+
+    #[test]
+    fn decode_8_bit_image_to_u32() {
+        let mut decoder = Decoder::new(GRAY8_16X16);
+        let mut destination = vec![0; 16 * 16];
+
+        decoder.decode_to_u32_into(&mut destination).unwrap();
+
+        for (index, &sample) in destination.iter().enumerate() {
+            assert_eq!(sample, expected_gray8_sample(index as u32 % 16, index as u32 / 16) as u32);
+        }
+    }
+
+    #[test]
+    fn decode_12_bit_image_to_u32() {
+        let frame_info = FrameInfo::new(Width(16), Height(8), 12, 3);
+        let samples: Vec<u16> = (0..16 * 8 * 3).map(|index| (index * 37 % 4096) as u16).collect();
+        let source: Vec<u8> = samples.iter().flat_map(|sample| sample.to_ne_bytes()).collect();
+        let mut encoder = Encoder::new(frame_info);
+        encoder.set_interleave_mode(InterleaveMode::Sample);
+        let encoded = encoder.encode(&source).unwrap();
+        let mut decoder = Decoder::new(encoded.as_slice());
+        let mut destination = vec![0; 16 * 8 * 3];
+
+        decoder.decode_to_u32_into(&mut destination).unwrap();
+
+        assert_eq!(destination, samples.iter().map(|&sample| sample as u32).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn decode_12_bit_image_with_sample_offset_to_i16() {
+        let frame_info = FrameInfo::new(Width(16), Height(8), 12, 1);
+        let samples: Vec<u16> = (0..16 * 8).map(|index| (index * 37 % 4096) as u16).collect();
+        let source: Vec<u8> = samples.iter().flat_map(|sample| sample.to_ne_bytes()).collect();
+        let encoded = Encoder::new(frame_info).encode(&source).unwrap();
+        let mut decoder = Decoder::new(encoded.as_slice());
+        decoder.set_sample_offset(2048);
+        let mut destination = vec![0_i16; 16 * 8];
+
+        decoder.decode_signed_into(&mut destination).unwrap();
+
+        assert_eq!(destination, samples.iter().map(|&sample| sample as i16 - 2048).collect::<Vec<i16>>());
+        assert_eq!(destination.iter().min(), Some(&-2048));
+    }
+
+    #[test]
+    fn decode_signed_into_with_offset_out_of_range_fails() {
+        let frame_info = FrameInfo::new(Width(4), Height(4), 16, 1);
+        let encoded = Encoder::new(frame_info).encode(&[0; 4 * 4 * 2]).unwrap();
+
+        // 65535 doesn't fit in an i16 and 0 - i32::MIN doesn't fit in an i32.
+        let mut decoder = Decoder::new(encoded.as_slice());
+        let error = decoder.decode_signed_into(&mut [0_i16; 4 * 4]).unwrap_err();
+        assert_eq!(error, DecodingError::InvalidParameterSampleOffset);
+
+        let mut decoder = Decoder::new(encoded.as_slice());
+        decoder.set_sample_offset(i32::MIN);
+        let error = decoder.decode_signed_into(&mut [0_i32; 4 * 4]).unwrap_err();
+        assert_eq!(error, DecodingError::InvalidParameterSampleOffset);
+
+        let mut decoder = Decoder::new(encoded.as_slice());
+        decoder.set_sample_offset(32768);
+        let mut destination = [0_i16; 4 * 4];
+        decoder.decode_signed_into(&mut destination).unwrap();
+        assert_eq!(destination, [-32768; 4 * 4]);
+    }
+
+    #[test]
+    fn decode_to_u32_into_ignores_packed() {
+        let mut decoder = Decoder::new(GRAY4_16X16);
+        decoder.set_packed(true);
+        let mut destination = vec![u32::MAX; 16 * 16];
+
+        decoder.decode_to_u32_into(&mut destination).unwrap();
+
+        let expected = Decoder::new(GRAY4_16X16).decode().unwrap();
+        assert_eq!(destination, expected.iter().map(|&sample| sample as u32).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn decode_signed_into_ignores_packed() {
+        let mut decoder = Decoder::new(GRAY4_16X16);
+        decoder.set_packed(true);
+        decoder.set_sample_offset(8);
+        let mut destination = vec![i16::MAX; 16 * 16];
+
+        decoder.decode_signed_into(&mut destination).unwrap();
+
+        let expected = Decoder::new(GRAY4_16X16).decode().unwrap();
+        assert_eq!(destination, expected.iter().map(|&sample| sample as i16 - 8).collect::<Vec<i16>>());
+    }
+
+    #[test]
+    fn decode_to_u32_into_with_wrong_destination_size_fails() {
+        for size in [16 * 16 - 1, 16 * 16 + 1] {
+            let mut decoder = Decoder::new(GRAY8_16X16);
+            let mut destination = vec![0; size];
+
+            let error = decoder.decode_to_u32_into(&mut destination).unwrap_err();
+
+            assert_eq!(error, DecodingError::InvalidParameterDestinationSize);
+        }
+    }
+
+    #[test]
+    fn decode_truncated_scan_returns_decoded_rows() {
+        let bit_stream_size = GRAY8_16X16.len() - 2 - START_OF_BIT_STREAM;
+        let mut truncated = GRAY8_16X16[..START_OF_BIT_STREAM + bit_stream_size / 2].to_vec();
+        truncated.extend_from_slice(&[0xFF, 0xD9]);
+        let mut decoder = Decoder::new(truncated.as_slice());
+        let mut destination = vec![0; 16 * 16];
+
+        let error = decoder.decode_into(&mut destination).unwrap_err();
+
+        assert_eq!(error, DecodingError::UnexpectedEndOfStream);
+        let row_count = decoder.decoded_row_count();
+        assert!(row_count > 0 && row_count < 16);
+        check_rows(&destination, row_count);
+    }
+
+    #[test]
+    fn decode_scan_truncated_without_end_of_image_fails() {
+        let mut decoder = Decoder::new(&GRAY8_16X16[..START_OF_BIT_STREAM + 20]);
+
+        let error = decoder.decode().unwrap_err();
+
+        assert_eq!(error, DecodingError::UnexpectedEndOfStream);
+        assert!(decoder.decoded_row_count() < 16);
+    }
+
+    #[test]
+    fn decode_corrupted_byte_with_best_effort_error_policy_fills_remaining_rows() {
+        // A 0xFF byte followed by a byte with the high bit set is a marker, which desynchronizes the decoder.
+        let bit_stream_size = GRAY8_16X16.len() - 2 - START_OF_BIT_STREAM;
+        let mut corrupted = GRAY8_16X16.to_vec();
+        corrupted[START_OF_BIT_STREAM + bit_stream_size / 2] = 0xFF;
+        assert_eq!(Decoder::new(corrupted.as_slice()).decode().unwrap_err(), DecodingError::UnexpectedEndOfStream);
+        let mut decoder = Decoder::new(corrupted.as_slice());
+        decoder.set_error_policy(ErrorPolicy::BestEffort);
+        decoder.set_fill_value(0x7F);
+
+        let decoded = decoder.decode().unwrap();
+
+        assert_eq!(decoder.recovered_error(), Some(&DecodingError::UnexpectedEndOfStream));
+        let row_count = decoder.decoded_row_count();
+        assert!(row_count > 0 && row_count < 16);
+        check_rows(&decoded, row_count);
+        assert!(decoded[row_count as usize * 16..].iter().all(|&sample| sample == 0x7F));
+    }
+
+    #[test]
+    fn decode_with_best_effort_error_policy_without_errors() {
+        let mut decoder = Decoder::new(GRAY8_16X16);
+        decoder.set_error_policy(ErrorPolicy::BestEffort);
+
+        let decoded = decoder.decode().unwrap();
+
+        assert_eq!(decoder.recovered_error(), None);
+        check_rows(&decoded, 16);
+    }
+
+    #[test]
+    fn decode_with_leading_garbage_and_scan_for_soi() {
+        let mut embedded = vec![0x44, 0x49, 0x43, 0x4D, 0xFF, 0x00];
+        embedded.extend_from_slice(GRAY8_16X16);
+        let mut decoder = Decoder::new(embedded.as_slice());
+        decoder.set_strict_mode(false);
+        decoder.set_scan_for_soi(true);
+
+        let decoded = decoder.decode().unwrap();
+
+        check_rows(&decoded, 16);
+    }
+
+    #[test]
+    fn decode_into_with_stride_leaves_padding_untouched() {
+        const STRIDE: usize = 16 + 5;
+        let mut decoder = Decoder::new(GRAY8_16X16);
+        let mut destination = vec![0xAA; STRIDE * 16];
+
+        decoder.decode_into_with_stride(&mut destination, STRIDE).unwrap();
+
+        let reference = Decoder::new(GRAY8_16X16).decode().unwrap();
+        for (row, reference_row) in destination.chunks(STRIDE).zip(reference.chunks(16)) {
+            assert_eq!(&row[..16], reference_row);
+            assert!(row[16..].iter().all(|&padding| padding == 0xAA));
+        }
+    }
+
+    #[test]
+    fn decode_into_with_stride_without_padding_after_last_row() {
+        const STRIDE: usize = 20;
+        let mut decoder = Decoder::new(GRAY8_16X16);
+        let mut destination = vec![0; STRIDE * 15 + 16];
+
+        decoder.decode_into_with_stride(&mut destination, STRIDE).unwrap();
+
+        check_rows(&destination.chunks(STRIDE).flat_map(|row| row[..16].to_vec()).collect::<Vec<u8>>(), 16);
+    }
+
+    #[test]
+    fn decode_into_with_too_small_stride_fails() {
+        let mut decoder = Decoder::new(GRAY8_16X16);
+        let mut destination = vec![0; 16 * 16];
+
+        let error = decoder.decode_into_with_stride(&mut destination, 15).unwrap_err();
+
+        assert_eq!(error, DecodingError::InvalidParameterStride);
+    }
+
+    #[test]
+    fn decode_into_with_stride_too_small_destination_fails() {
+        let mut decoder = Decoder::new(GRAY8_16X16);
+        let mut destination = vec![0; 20 * 15 + 15];
+
+        let error = decoder.decode_into_with_stride(&mut destination, 20).unwrap_err();
+
+        assert_eq!(error, DecodingError::DestinationTooSmall);
+    }
+
+    #[test]
+    fn verify_returns_frame_info() {
+        let mut decoder = Decoder::new(GRAY8_16X16);
+
+        let frame_info = decoder.verify().unwrap();
+
+        assert_eq!(frame_info.width(), 16);
+        assert_eq!(frame_info.height(), 16);
+        assert_eq!(frame_info.bits_per_sample(), 8);
+        assert_eq!(frame_info.component_count(), 1);
+    }
+
+    #[test]
+    fn verify_corrupt_bit_stream_fails() {
+        let mut corrupt = GRAY8_16X16.to_vec();
+        let end_of_bit_stream = corrupt.len() - 2;
+        corrupt[START_OF_BIT_STREAM..end_of_bit_stream].fill(0);
+        let mut decoder = Decoder::new(corrupt.as_slice());
+
+        assert!(decoder.verify().is_err());
+    }
+
+    #[test]
+    fn read_spiff_header_of_stream_without_spiff_header_then_decode() {
+        let mut decoder = Decoder::new(GRAY8_16X16);
+
+        assert_eq!(decoder.read_spiff_header().unwrap(), None);
+
+        let decoded = decoder.decode().unwrap();
+        check_rows(&decoded, 16);
+    }
+
+    #[test]
+    fn bitstream_offset_is_start_of_entropy_coded_data() {
+        let mut decoder = Decoder::new(GRAY8_16X16);
+
+        decoder.read_header().unwrap();
+
+        assert_eq!(decoder.bitstream_offset(), Some(START_OF_BIT_STREAM as u64));
+    }
+
+    #[test]
+    fn scan_info_of_last_planar_scan() {
+        let mut decoder = Decoder::new(RGB8_PLANAR_16X16);
+
+        decoder.read_header().unwrap();
+        assert_eq!(decoder.scan_info().unwrap().component_ids(), &[1]);
+        decoder.decode().unwrap();
+
+        assert_eq!(*decoder.scan_info().unwrap(), ScanInfo {
+            component_ids: vec![3],
+            mapping_table_ids: vec![0],
+            near_lossless: 0,
+            interleave_mode: InterleaveMode::None,
+            transformation: ColorTransformation::None,
+            raw_transformation: 0,
+        });
+    }
+
+    /// Returns the encoded 8-bit indices with an RGB mapping table after the SOI marker and
+    /// selects the table for the component of the scan.
+    fn encode_indices_with_rgb_mapping_table(indices: &[u8], palette: &[u8], table_id: u8) -> Vec<u8> {
+        let frame_info = FrameInfo::new(Width(8), Height(indices.len() as u32 / 8), 8, 1);
+        let encoded = Encoder::new(frame_info).encode(indices).unwrap();
+        add_rgb_mapping_table(&encoded, palette, table_id)
+    }
+
+    /// Inserts an RGB mapping table with ID 1 after the SOI marker of a single scan stream and
+    /// selects the table with table_id for the component of the scan.
+    fn add_rgb_mapping_table(encoded: &[u8], palette: &[u8], table_id: u8) -> Vec<u8> {
+        let segment_size = (2 + 3 + palette.len()) as u16;
+        let mut stream = encoded[..2].to_vec();
+        stream.extend_from_slice(&[0xFF, 0xF8]);
+        stream.extend_from_slice(&segment_size.to_be_bytes());
+        stream.extend_from_slice(&[2, 1, 3]); // ID = mapping table, TID = 1, Wt = 3
+        stream.extend_from_slice(palette);
+        stream.extend_from_slice(&encoded[2..]);
+
+        let start_of_scan = stream.windows(2).position(|marker| marker == [0xFF, 0xDA]).unwrap();
+        stream[start_of_scan + 6] = table_id; // Tm1
+        stream
+    }
+
+    #[test]
+    fn decode_indices_with_rgb_mapping_table() {
+        let palette = [0, 0, 0, 255, 0, 0, 0, 255, 0, 10, 20, 30];
+        let indices: Vec<u8> = (0..8 * 8).map(|index| (index * 7 / 5 % 4) as u8).collect();
+        let encoded = encode_indices_with_rgb_mapping_table(&indices, &palette, 1);
+        let mut decoder = Decoder::new(encoded.as_slice());
+
+        let mapped = decoder.decode_with_mapping_tables().unwrap();
+
+        let expected: Vec<u8> = indices.iter().flat_map(|&index| palette[index as usize * 3..][..3].to_vec()).collect();
+        assert_eq!(mapped, expected);
+        assert_eq!(decoder.scan_info().unwrap().mapping_table_ids(), &[1]);
+        assert_eq!(decoder.mapping_table(1).unwrap().entry_count(), 4);
+    }
+
+    #[test]
+    fn decode_with_mapping_tables_ignores_packed() {
+        let palette: Vec<u8> = (0..16 * 3).collect();
+        let encoded = add_rgb_mapping_table(GRAY4_16X16, &palette, 1);
+        let mut decoder = Decoder::new(encoded.as_slice());
+        decoder.set_packed(true);
+
+        let mapped = decoder.decode_with_mapping_tables().unwrap();
+
+        let indices = Decoder::new(GRAY4_16X16).decode().unwrap();
+        let expected: Vec<u8> = indices.iter().flat_map(|&index| palette[index as usize * 3..][..3].to_vec()).collect();
+        assert_eq!(mapped, expected);
+    }
+
+    #[test]
+    fn decode_with_mapping_tables_with_index_outside_table_fails() {
+        let indices: Vec<u8> = (0..8 * 8).map(|index| index as u8 % 3).collect();
+        let encoded = encode_indices_with_rgb_mapping_table(&indices, &[1, 2, 3, 4, 5, 6], 1);
+
+        let error = Decoder::new(encoded.as_slice()).decode_with_mapping_tables().unwrap_err();
+
+        assert_eq!(error, DecodingError::MappingTableIndexOutOfRange);
+    }
+
+    #[test]
+    fn decode_with_mapping_tables_without_selected_table_fails() {
+        let encoded = encode_indices_with_rgb_mapping_table(&[0; 8 * 8], &[1, 2, 3], 0);
+
+        let error = Decoder::new(encoded.as_slice()).decode_with_mapping_tables().unwrap_err();
+
+        assert_eq!(error, DecodingError::InvalidParameterMappingTableId);
+    }
+
+    fn assert_send<T: Send>() {}
+
+    fn assert_sync<T: Sync>() {}
+
+    #[test]
+    fn decoder_is_send_and_sync_when_reader_is() {
+        assert_send::<Decoder<&[u8]>>();
+        assert_send::<Decoder<std::fs::File>>();
+        assert_send::<Decoder<Box<dyn Read + Send>>>();
+        assert_send::<DecoderBuffers>();
+        assert_sync::<Decoder<&[u8]>>();
+        assert_sync::<DecoderBuffers>();
+    }
+
+    #[test]
+    fn decode_on_other_thread() {
+        let mut decoder = Decoder::new(GRAY8_16X16);
+        decoder.read_header().unwrap();
+
+        let decoded = std::thread::spawn(move || decoder.decode().unwrap()).join().unwrap();
+
+        check_rows(&decoded, 16);
+    }
+
+    #[test]
+    fn decode_with_buffers_of_larger_image() {
+        let frame_info = FrameInfo::new(Width(64), Height(32), 16, 3);
+        let source: Vec<u8> = (0..64 * 32 * 3).flat_map(|index: u32| ((index * 761) as u16).to_ne_bytes()).collect();
+        let mut encoder = Encoder::new(frame_info);
+        encoder.set_interleave_mode(InterleaveMode::Sample);
+        let encoded = encoder.encode(&source).unwrap();
+        let mut decoder = Decoder::new(encoded.as_slice());
+        assert_eq!(decoder.decode().unwrap(), source);
+
+        let mut decoder = Decoder::with_buffers(GRAY8_16X16, decoder.into_buffers());
+        check_rows(&decoder.decode().unwrap(), 16);
+
+        decoder.reset(RGB8_PLANAR_16X16);
+        assert_eq!(decoder.decode().unwrap(), Decoder::new(RGB8_PLANAR_16X16).decode().unwrap());
+    }
+
+    #[test]
+    fn row_callback_is_called_for_every_row() {
+        let rows = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut decoder = Decoder::new(GRAY8_16X16);
+        let callback_rows = rows.clone();
+        decoder.set_row_callback(Box::new(move |row| callback_rows.lock().unwrap().push(row)));
+
+        check_rows(&decoder.decode().unwrap(), 16);
+
+        assert_eq!(*rows.lock().unwrap(), (0..16).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn decode_with_empty_application_data_segment() {
+        // An APP0 segment with length 2 has no payload.
+        let mut encoded = GRAY8_16X16[..2].to_vec();
+        encoded.extend_from_slice(&[0xFF, 0xE0, 0x00, 0x02]);
+        encoded.extend_from_slice(&GRAY8_16X16[2..]);
+        let mut decoder = Decoder::new(encoded.as_slice());
+
+        check_rows(&decoder.decode().unwrap(), 16);
+        assert_eq!(decoder.bitstream_offset(), Some(START_OF_BIT_STREAM as u64 + 4));
+
+        let mut decoder = Decoder::new(encoded.as_slice());
+        let markers: Vec<_> = decoder.markers().take(2).map(|marker| marker.unwrap()).collect();
+        assert_eq!(markers[1], (JpegMarkerCode::ApplicationData0, 2..6));
+    }
+
+    #[test]
+    fn decode_green_component_of_planar_rgb_image() {
+        let planes = Decoder::new(RGB8_PLANAR_16X16).decode().unwrap();
+        let mut decoder = Decoder::new(RGB8_PLANAR_16X16);
+        let mut green = vec![0; 16 * 16];
+
+        decoder.decode_components_subset(&[1], &mut [&mut green]).unwrap();
+
+        assert_eq!(green, &planes[16 * 16..2 * 16 * 16]);
+        assert_eq!(decoder.scan_infos().len(), 1);
+        assert_eq!(decoder.scan_infos()[0].component_ids(), &[2]);
+    }
+
+    #[test]
+    fn decode_components_subset_with_invalid_parameters_fails() {
+        let mut plane = vec![0; 16 * 16];
+
+        let error = Decoder::new(RGB8_SAMPLE_16X16).decode_components_subset(&[1], &mut [&mut plane]).unwrap_err();
+        assert_eq!(error, DecodingError::InvalidParameterInterleaveMode);
+
+        for wanted in [&[3][..], &[1, 1][..]] {
+            let mut decoder = Decoder::new(RGB8_PLANAR_16X16);
+            let error = decoder.decode_components_subset(wanted, &mut [&mut plane]).unwrap_err();
+            assert_eq!(error, DecodingError::InvalidParameterComponentCount);
+        }
+
+        let error = Decoder::new(RGB8_PLANAR_16X16).decode_components_subset(&[0, 2], &mut [&mut plane]).unwrap_err();
+        assert_eq!(error, DecodingError::InvalidParameterDestinationSize);
+    }
+
+    #[test]
+    fn decode_planar_image_with_fewer_scans_than_components_fails() {
+        // Remove the scan of the third component: the SOF still declares 3 components.
+        let last_scan = RGB8_PLANAR_16X16.windows(2).rposition(|marker| marker == [0xFF, 0xDA]).unwrap();
+        let mut encoded = RGB8_PLANAR_16X16[..last_scan].to_vec();
+        encoded.extend_from_slice(&[0xFF, 0xD9]);
+        let mut decoder = Decoder::new(encoded.as_slice());
+
+        let error = decoder.decode().unwrap_err();
+
+        assert_eq!(error, DecodingError::InvalidParameterComponentCount);
+        assert_eq!(decoder.scan_infos().len(), 2);
+    }
+
+    #[test]
+    fn decode_near_lossless_images_within_near_of_original() {
+        // Images encoded by CharLS; the lossless images are the originals.
+        let gray8_original = include_bytes!("../tests/fixtures/gray8_16x16.raw");
+        let bias16_original = include_bytes!("../tests/fixtures/bias16_64x64.raw");
+        let cases: [(&[u8], &[u8], u8); 4] = [
+            (include_bytes!("../tests/fixtures/gray8_16x16_near1.jls"), gray8_original, 1),
+            (include_bytes!("../tests/fixtures/gray8_16x16_near2.jls"), gray8_original, 2),
+            (include_bytes!("../tests/fixtures/gray8_16x16_near7.jls"), gray8_original, 7),
+            (include_bytes!("../tests/fixtures/bias16_64x64_near7.jls"), bias16_original, 7),
+        ];
+
+        for (encoded, original, near_lossless) in cases {
+            let mut decoder = Decoder::new(encoded);
+            decoder.read_header().unwrap();
+            let original: Vec<u32> = if decoder.frame_info().bits_per_sample() <= 8 {
+                original.iter().map(|&sample| sample as u32).collect()
+            } else {
+                original.chunks_exact(2).map(|sample| u16::from_le_bytes([sample[0], sample[1]]) as u32).collect()
+            };
+            let mut decoded = vec![0; original.len()];
+
+            decoder.decode_to_u32_into(&mut decoded).unwrap();
+
+            assert_eq!(decoder.near_lossless(), near_lossless);
+            for (index, (&decoded, &original)) in decoded.iter().zip(&original).enumerate() {
+                assert!(decoded.abs_diff(original) <= near_lossless as u32, "NEAR = {}, sample {}: {} instead of {}",
+                        near_lossless, index, decoded, original);
+            }
+        }
+    }
+
+    /// Returns the source and a planar 3 component stream of it of which the second scan has
+    /// NEAR = 5 and the other scans NEAR = 0.
+    fn encode_planar_with_near_lossless_5_for_second_scan() -> (Vec<u8>, Vec<u8>) {
+        // Every scan of a planar image codes its component independently: combine the scans of
+        // two encodings with different NEAR values into one stream.
+        let frame_info = FrameInfo::new(Width(16), Height(16), 8, 3);
+        let source: Vec<u8> = (0..3 * 16 * 16).map(|index| ((index * 37) % 251) as u8).collect();
+        let encode = |near_lossless| {
+            let mut encoder = Encoder::new(frame_info.clone());
+            encoder.set_near_lossless(near_lossless);
+            encoder.encode(&source).unwrap()
+        };
+        let lossless = encode(0);
+        let near_lossless = encode(5);
+        let start_of_scan_positions = |stream: &[u8]| -> Vec<usize> {
+            stream.windows(2).enumerate().filter(|(_, marker)| marker == &[0xFF, 0xDA]).map(|(i, _)| i).collect()
+        };
+        let lossless_scans = start_of_scan_positions(&lossless);
+        let near_lossless_scans = start_of_scan_positions(&near_lossless);
+        let mut encoded = lossless[..lossless_scans[1]].to_vec();
+        encoded.extend_from_slice(&near_lossless[near_lossless_scans[1]..near_lossless_scans[2]]);
+        encoded.extend_from_slice(&lossless[lossless_scans[2]..]);
+        (source, encoded)
+    }
+
+    #[test]
+    fn decode_planar_image_with_different_near_lossless_per_scan() {
+        let (source, encoded) = encode_planar_with_near_lossless_5_for_second_scan();
+        let mut decoder = Decoder::new(encoded.as_slice());
+
+        let decoded = decoder.decode().unwrap();
+
+        let near_lossless_values: Vec<u8> =
+            decoder.scan_infos().iter().map(|scan_info| scan_info.near_lossless()).collect();
+        assert_eq!(near_lossless_values, [0, 5, 0]);
+        assert_eq!(decoded[..256], source[..256]);
+        assert_eq!(decoded[512..], source[512..]);
+        assert_ne!(decoded[256..512], source[256..512]);
+        for (decoded_sample, source_sample) in decoded[256..512].iter().zip(&source[256..512]) {
+            assert!((*decoded_sample as i32 - *source_sample as i32).abs() <= 5);
+        }
+    }
+
+    #[test]
+    fn coding_variant_of_baseline_frame() {
+        let mut decoder = Decoder::new(GRAY8_16X16);
+
+        decoder.read_header().unwrap();
+
+        assert_eq!(decoder.coding_variant(), CodingVariant::Baseline);
+    }
+
+    #[test]
+    fn coding_variant_of_extended_frame() {
+        let mut extended = GRAY8_16X16.to_vec();
+        assert_eq!(extended[3], 0xF7);
+        extended[3] = 0xF9; // SOF_55 => SOF_57
+        let mut decoder = Decoder::new(extended.as_slice());
+
+        decoder.read_header().unwrap();
+
+        assert_eq!(decoder.coding_variant(), CodingVariant::Extended);
+        assert_eq!(decoder.frame_info().width(), 16);
+        assert_eq!(decoder.decode().unwrap_err(), DecodingError::EncodingNotSupported);
+    }
+
+    #[test]
+    fn decode_into_too_small_destination_fails() {
+        let mut decoder = Decoder::new(GRAY8_16X16);
+        let mut destination = vec![0; 16 * 16 - 1];
+
+        let error = decoder.decode_into(&mut destination).unwrap_err();
+
+        assert_eq!(error, DecodingError::DestinationTooSmall);
+    }
+
+    #[test]
+    fn decode_2_bit_image_unpacked_and_packed() {
+        // A width of 5 samples leaves 6 padding bits in the last byte of every packed row.
+        let source: Vec<u8> = (0..5 * 3).map(|index| (index * 7 % 4) as u8).collect();
+        let encoded = Encoder::new(FrameInfo::new(Width(5), Height(3), 2, 1)).encode(&source).unwrap();
+
+        let unpacked = Decoder::new(encoded.as_slice()).decode().unwrap();
+        assert_eq!(unpacked, source);
+
+        let mut decoder = Decoder::new(encoded.as_slice());
+        decoder.set_packed(true);
+        decoder.read_header().unwrap();
+        assert_eq!(decoder.destination_size(), 2 * 3);
+        let packed = decoder.decode().unwrap();
+        let expected: Vec<u8> = source.chunks_exact(5).flat_map(|row| [
+            row[0] << 6 | row[1] << 4 | row[2] << 2 | row[3],
+            row[4] << 6,
+        ]).collect();
+        assert_eq!(packed, expected);
+    }
+
+    #[test]
+    fn decode_packed_with_stride_leaves_padding_bytes_untouched() {
+        let source: Vec<u8> = (0..5 * 3).map(|index| (index % 4) as u8).collect();
+        let encoded = Encoder::new(FrameInfo::new(Width(5), Height(3), 2, 1)).encode(&source).unwrap();
+        let mut decoder = Decoder::new(encoded.as_slice());
+        decoder.set_packed(true);
+
+        let mut destination = vec![0xAA; 3 * 3];
+        decoder.decode_into_with_stride(&mut destination, 3).unwrap();
+        assert_eq!(destination, [0x1B, 0x00, 0xAA, 0x6C, 0x40, 0xAA, 0xB1, 0x80, 0xAA]);
+        assert_eq!(decoder.decode_into_with_stride(&mut destination, 1).unwrap_err(),
+                   DecodingError::InvalidParameterStride);
+    }
+
+    #[test]
+    fn pack_row_of_3_bit_samples_crosses_byte_boundaries() {
+        let mut packed = [0xFF; 2];
+        pack_row(&[0b101, 0b011, 0b110, 0b001], 3, &mut packed);
+        assert_eq!(packed, [0b1010_1111, 0b0001_0000]);
+    }
+
+    #[test]
+    fn packed_option_is_ignored_for_more_than_4_bits_per_sample() {
+        let mut decoder = Decoder::new(GRAY8_16X16);
+        decoder.set_packed(true);
+        assert_eq!(decoder.decode().unwrap().len(), 16 * 16);
+    }
+
+    #[test]
+    fn decode_image_with_maximum_sample_value_smaller_than_bit_depth() {
+        let mut decoder = Decoder::new(MAXVAL100_16X16);
+        decoder.read_header().unwrap();
+        assert_eq!(decoder.frame_info().bits_per_sample(), 8);
+        assert_eq!(decoder.maximum_sample_value(), 100);
+        assert_eq!(decoder.decode().unwrap(), MAXVAL100_16X16_DECODED);
+
+        let mut decoder = Decoder::new(MAXVAL100_16X16_NEAR2);
+        let decoded = decoder.decode().unwrap();
+        assert_eq!(decoder.near_lossless(), 2);
+        assert!(decoded.iter().zip(MAXVAL100_16X16_DECODED)
+            .all(|(&sample, &original)| sample <= 100 && sample.abs_diff(original) <= 2));
+    }
+
+    #[test]
+    fn fill_value_is_limited_to_maximum_sample_value_of_lse_segment() {
+        // Truncated after the first bytes of the entropy coded data.
+        let truncated = &MAXVAL100_16X16[..MAXVAL100_16X16.len() / 4];
+        let mut decoder = Decoder::new(truncated);
+        decoder.set_error_policy(ErrorPolicy::BestEffort);
+        decoder.set_fill_value(255);
+
+        let decoded = decoder.decode().unwrap();
+        assert_eq!(*decoded.last().unwrap(), 100);
+    }
+
+    #[test]
+    fn state_transitions_from_before_start_of_image_to_after_end_of_image() {
+        let frame_info = FrameInfo::new(Width(4), Height(4), 8, 3);
+        let mut encoder = Encoder::new(frame_info.clone());
+        encoder.write_spiff_header(SpiffHeader::new(&frame_info, 10)).unwrap();
+        let encoded = encoder.encode(&[7; 4 * 4 * 3]).unwrap();
+
+        let mut decoder = Decoder::new(encoded.as_slice());
+        assert_eq!(decoder.state(), ReaderStatePublic::BeforeStartOfImage);
+        decoder.read_spiff_header().unwrap();
+        assert_eq!(decoder.state(), ReaderStatePublic::SpiffHeaderSection);
+        decoder.read_header().unwrap();
+        assert_eq!(decoder.state(), ReaderStatePublic::BitStreamSection);
+        decoder.decode().unwrap();
+        assert_eq!(decoder.state(), ReaderStatePublic::AfterEndOfImage);
+    }
+
+    #[test]
+    fn effective_color_interpretation_of_grayscale_and_rgb_with_transform() {
+        let mut decoder = Decoder::new(GRAY8_16X16);
+        decoder.read_header().unwrap();
+        assert_eq!(decoder.effective_color_interpretation(), ColorInterpretation::Grayscale);
+
+        let (encoded, _, color_transformation) = COLOR_TRANSFORMATION_FIXTURES[0];
+        let mut decoder = Decoder::new(encoded);
+        decoder.read_header().unwrap();
+        assert_eq!(decoder.effective_color_interpretation(),
+                   ColorInterpretation::RgbWithTransform(color_transformation));
+    }
+
+    #[test]
+    fn effective_color_interpretation_of_spiff_ycbcr_stream() {
+        let frame_info = FrameInfo::new(Width(4), Height(4), 8, 3);
+        let mut encoder = Encoder::new(frame_info.clone());
+        encoder.write_spiff_header(SpiffHeader::new(&frame_info, 3)).unwrap();
+        let encoded = encoder.encode(&[7; 4 * 4 * 3]).unwrap();
+
+        let mut decoder = Decoder::new(encoded.as_slice());
+        decoder.read_header().unwrap();
+        assert_eq!(decoder.effective_color_interpretation(), ColorInterpretation::YCbCr);
+    }
+
+    #[test]
+    fn decode_scan_with_0xff_as_last_byte_before_end_of_image() {
+        // The 0xFF byte is followed by the 0xFF 0xD9 of the EOI marker: the bit reader must stop
+        // before it instead of reading the marker as entropy coded data.
+        let mut encoded = GRAY8_16X16.to_vec();
+        encoded.insert(encoded.len() - 2, 0xFF);
+        let mut decoder = Decoder::new(encoded.as_slice());
+
+        let decoded = decoder.decode().unwrap();
+
+        check_rows(&decoded, 16);
+        assert_eq!(decoder.state(), ReaderStatePublic::AfterEndOfImage);
+    }
+
+    #[test]
+    fn decode_planar_image_with_maximum_component_count() {
+        let frame_info = FrameInfo::new(Width(2), Height(2), 8, MAXIMUM_COMPONENT_COUNT);
+        let source: Vec<u8> = (0..2 * 2 * MAXIMUM_COMPONENT_COUNT as usize).map(|index| index as u8).collect();
+        let encoded = Encoder::new(frame_info).encode(&source).unwrap();
+        let mut decoder = Decoder::new(encoded.as_slice());
+
+        assert_eq!(decoder.decode().unwrap(), source);
+        assert_eq!(decoder.scan_infos().len(), MAXIMUM_COMPONENT_COUNT as usize);
+    }
+
+    #[test]
+    fn decode_interleaved_scan_without_all_components_of_frame_fails() {
+        // A frame with 5 components and a line interleaved scan with the maximum of 4 components.
+        let mut encoded = vec![0xFF, 0xD8, 0xFF, 0xF7, 0, 8 + 3 * 5, 8, 0, 1, 0, 1, 5];
+        for component_id in 1..=5 {
+            encoded.extend([component_id, 0x11, 0]);
+        }
+        encoded.extend([0xFF, 0xDA, 0, 6 + 2 * 4, 4]);
+        for component_id in 1..=4 {
+            encoded.extend([component_id, 0]);
+        }
+        encoded.extend([0, InterleaveMode::Line as u8, 0, 0, 0, 0xFF, 0xD9]);
+
+        assert_eq!(Decoder::new(encoded.as_slice()).decode().unwrap_err(),
+                   DecodingError::InvalidParameterComponentCount);
+    }
+
+    #[test]
+    fn decode_verified_reproduces_lossless_stream() {
+        let mut decoder = Decoder::new(GRAY8_16X16);
+        check_rows(&decoder.decode_verified().unwrap(), 16);
+
+        // Larger than the read buffer of the stream reader.
+        let frame_info = FrameInfo::new(Width(64), Height(64), 8, 3);
+        let source: Vec<u8> = (0..64 * 64 * 3).map(|index| (index * 13 % 251) as u8).collect();
+        let mut encoder = Encoder::new(frame_info.clone());
+        encoder.set_interleave_mode(InterleaveMode::Sample);
+        encoder.write_spiff_header(SpiffHeader::new(&frame_info, 10)).unwrap();
+        let encoded = encoder.encode(&source).unwrap();
+
+        let mut decoder = Decoder::new(encoded.as_slice());
+        decoder.read_header().unwrap();
+        assert_eq!(decoder.decode_verified().unwrap(), source);
+    }
+
+    #[test]
+    fn decode_verified_of_stream_with_comment_fails() {
+        let mut encoded = GRAY8_16X16[..2].to_vec();
+        encoded.extend([0xFF, JpegMarkerCode::Comment as u8, 0, 4, b'h', b'i']);
+        encoded.extend(&GRAY8_16X16[2..]);
+
+        // The stream itself decodes, but Encoder doesn't write the COM segment.
+        assert!(Decoder::new(encoded.as_slice()).decode().is_ok());
+        assert_eq!(Decoder::new(encoded.as_slice()).decode_verified().unwrap_err(), DecodingError::VerificationFailed);
+    }
+
+    #[test]
+    fn decode_with_alpha_component_id_stores_that_component_last() {
+        // Encoder writes the component IDs 1..=4: the alpha samples are in the 2nd component (ID 2).
+        let frame_info = FrameInfo::new(Width(4), Height(2), 8, 4);
+        let pixels: Vec<[u8; 4]> = (0..8).map(|index| [index, 100 + index, 50 + index, 200 + index]).collect();
+        for interleave_mode in [InterleaveMode::None, InterleaveMode::Line, InterleaveMode::Sample] {
+            let source: Vec<u8> = if interleave_mode == InterleaveMode::None {
+                (0..4).flat_map(|component| pixels.iter().map(move |pixel| pixel[component])).collect()
+            } else {
+                pixels.concat()
+            };
+            let mut encoder = Encoder::new(frame_info.clone());
+            encoder.set_interleave_mode(interleave_mode);
+            let encoded = encoder.encode(&source).unwrap();
+
+            let expected = |order: [usize; 4]| -> Vec<u8> {
+                if interleave_mode == InterleaveMode::None {
+                    order.iter().flat_map(|&component| pixels.iter().map(move |pixel| pixel[component])).collect()
+                } else {
+                    pixels.iter().flat_map(|pixel| order.map(|component| pixel[component])).collect()
+                }
+            };
+
+            let mut decoder = Decoder::new(encoded.as_slice());
+            decoder.set_alpha_component_id(2);
+            assert_eq!(decoder.decode().unwrap(), expected([0, 2, 3, 1]), "{:?}", interleave_mode);
+
+            let mut decoder = Decoder::new(encoded.as_slice());
+            decoder.set_alpha_component_id(2);
+            decoder.set_channel_order(ChannelOrder::Bgr);
+            assert_eq!(decoder.decode().unwrap(), expected([3, 2, 0, 1]), "{:?}", interleave_mode);
+        }
+    }
+
+    #[test]
+    fn alpha_component_id_without_matching_component_is_ignored() {
+        let (encoded, reference, _) = CMYK8_16X16_FIXTURES[0];
+        let mut decoder = Decoder::new(encoded);
+        decoder.set_alpha_component_id(200);
+
+        assert_eq!(decoder.decode().unwrap(), reference);
+    }
+
+    /// Reads the bytes of an iterator.
+    struct IteratorReader<I: Iterator<Item = u8>>(I);
+
+    impl<I: Iterator<Item = u8>> Read for IteratorReader<I> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let mut count = 0;
+            for (byte, value) in buf.iter_mut().zip(&mut self.0) {
+                *byte = value;
+                count += 1;
+            }
+            Ok(count)
+        }
+    }
+
+    #[test]
+    fn decode_never_ending_stream_with_limit_fails() {
+        // The SOI marker followed by an infinite sequence of empty COM segments.
+        let comments = std::iter::repeat([0xFF, 0xFE, 0x00, 0x02]).flatten();
+        let stream = GRAY8_16X16[..2].chain(IteratorReader(comments));
+        let mut decoder = Decoder::new_with_limit(stream, 64 * 1024);
+
+        assert_eq!(decoder.decode().unwrap_err(), DecodingError::InputTooLarge);
+    }
+
+    #[test]
+    fn decode_stream_of_exactly_the_limit() {
+        let mut decoder = Decoder::new_with_limit(GRAY8_16X16, GRAY8_16X16.len() as u64);
+        check_rows(&decoder.decode().unwrap(), 16);
+
+        let mut decoder = Decoder::new_with_limit(GRAY8_16X16, GRAY8_16X16.len() as u64 - 1);
+        assert_eq!(decoder.decode().unwrap_err(), DecodingError::InputTooLarge);
+    }
+
+    #[test]
+    fn decode_images_with_last_column_contexts_that_depend_on_rd() {
+        for (encoded, reference) in EDGE_FIXTURES {
+            assert_eq!(Decoder::new(encoded).decode().unwrap(), reference);
+        }
+    }
+
+    #[test]
+    fn decode_borrowed_returns_decoded_samples() {
+        let mut decoder = Decoder::new(RGB8_SAMPLE_16X16);
+
+        let decoded = decoder.decode_borrowed().unwrap();
+
+        assert_eq!(decoded, Decoder::new(RGB8_SAMPLE_16X16).decode().unwrap().as_slice());
+    }
+
+    #[test]
+    fn decode_borrowed_after_reset_reuses_buffer() {
+        let mut decoder = Decoder::new(RGB8_SAMPLE_16X16);
+        let first_address = decoder.decode_borrowed().unwrap().as_ptr();
+
+        decoder.reset(GRAY8_16X16);
+        let second = decoder.decode_borrowed().unwrap();
+
+        assert_eq!(second.as_ptr(), first_address);
+        assert_eq!(second, Decoder::new(GRAY8_16X16).decode().unwrap().as_slice());
+    }
+
+    #[test]
+    fn decode_with_golomb_code_longer_than_escape_code_fails() {
+        // 32 zero bits are more than the 23 zero bits of the escape code of an 8 bit image.
+        let mut corrupted = GRAY8_16X16.to_vec();
+        corrupted[START_OF_BIT_STREAM..START_OF_BIT_STREAM + 4].fill(0);
+
+        assert_eq!(Decoder::new(corrupted.as_slice()).decode().unwrap_err(), DecodingError::InvalidEncodedData);
+    }
+
+    #[test]
+    fn decode_with_endless_zero_bit_stream_fails() {
+        // The unary part of the first Golomb code is longer than the escape code after 23 zero
+        // bits: the decoder must stop there instead of reading the endless stream.
+        let source = GRAY8_16X16[..START_OF_BIT_STREAM].chain(std::io::repeat(0));
+
+        assert_eq!(Decoder::new(source).decode().unwrap_err(), DecodingError::InvalidEncodedData);
+    }
+
+    #[test]
+    fn decode_with_run_longer_than_rest_of_line_fails() {
+        // A 14 x 1, 8 bit image that starts in run mode: 8 one bits code runs of 1, 1, 1, 1, 2, 2, 2 and
+        // 2 samples (up to sample 12, with a run length order of 2 after them), the zero bit ends the
+        // run and its 2 bit remainder 3 is more than the 2 samples left.
+        let mut encoded = vec![0xFF, 0xD8, 0xFF, 0xF7, 0, 11, 8, 0, 1, 0, 14, 1, 1, 0x11, 0];
+        encoded.extend([0xFF, 0xDA, 0, 8, 1, 1, 0, 0, 0, 0]);
+        encoded.extend([0xFF, 0b0011_0000, 0, 0, 0xFF, 0xD9]);
+
+        assert_eq!(Decoder::new(encoded.as_slice()).decode().unwrap_err(), DecodingError::InvalidEncodedData);
+    }
+
+    #[test]
+    fn decode_with_corrupted_bit_stream_returns_error_of_bit_stream() {
+        let end_of_bit_stream = GRAY8_16X16.len() - 2;
+        for position in START_OF_BIT_STREAM..end_of_bit_stream {
+            for value in [0x00, 0x01, 0x80, GRAY8_16X16[position] ^ 0x55] {
+                let mut corrupted = GRAY8_16X16.to_vec();
+                corrupted[position] = value;
+
+                if let Err(error) = Decoder::new(corrupted.as_slice()).decode() {
+                    assert!(matches!(error, DecodingError::InvalidEncodedData | DecodingError::UnexpectedEndOfStream |
+                                     DecodingError::EndOfImageMarkerNotFound),
+                            "byte {} set to {:#04x} returned {:?}", position, value, error);
+                }
+            }
+        }
+    }
+
+    /// Returns at most 1 byte per read call and is interrupted before every byte, like an
+    /// unbuffered socket.
+    struct OneByteReader<'a> {
+        data: &'a [u8],
+        interrupted: bool,
+    }
+
+    impl Read for OneByteReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.interrupted = !self.interrupted;
+            if self.interrupted {
+                return Err(std::io::ErrorKind::Interrupted.into());
+            }
+
+            let count = buf.len().min(1).min(self.data.len());
+            buf[..count].copy_from_slice(&self.data[..count]);
+            self.data = &self.data[count..];
+            Ok(count)
+        }
+    }
+
+    #[test]
+    fn decode_from_reader_that_returns_one_byte_per_read() {
+        let mut streams = vec![GRAY8_16X16, GRAY4_16X16, MAXVAL100_16X16, RUNS8_32X8_NEAR2, RGB8_PLANAR_16X16,
+                               RGB8_LINE_16X16, RGB8_SAMPLE_16X16];
+        streams.extend(COLOR_TRANSFORMATION_FIXTURES.iter().map(|(encoded, _, _)| *encoded));
+        streams.extend(CMYK8_16X16_FIXTURES.iter().map(|(encoded, _, _)| *encoded));
+
+        for encoded in streams {
+            let mut decoder = Decoder::new(OneByteReader { data: encoded, interrupted: false });
+
+            assert_eq!(decoder.decode().unwrap(), Decoder::new(encoded).decode().unwrap());
+        }
+    }
+
+    #[test]
+    fn decode_components_subset_from_reader_that_returns_one_byte_per_read() {
+        let mut decoder = Decoder::new(OneByteReader { data: RGB8_PLANAR_16X16, interrupted: false });
+        let mut blue = vec![0; 16 * 16];
+
+        decoder.decode_components_subset(&[2], &mut [&mut blue]).unwrap();
+
+        let decoded = Decoder::new(RGB8_PLANAR_16X16).decode().unwrap();
+        assert_eq!(blue, &decoded[2 * 16 * 16..]);
+    }
+
+    /// Inserts a COM segment with the given text after the SOI marker.
+    fn with_comment(encoded: &[u8], comment: &[u8]) -> Vec<u8> {
+        let mut stream = encoded[..2].to_vec();
+        stream.extend_from_slice(&[0xFF, 0xFE]);
+        stream.extend_from_slice(&(comment.len() as u16 + 2).to_be_bytes());
+        stream.extend_from_slice(comment);
+        stream.extend_from_slice(&encoded[2..]);
+        stream
+    }
+
+    fn decode_payload_hash(encoded: &[u8]) -> Option<u32> {
+        let mut decoder = Decoder::new(encoded);
+        decoder.set_compute_payload_hash(true);
+        decoder.decode().unwrap();
+        decoder.payload_hash()
+    }
+
+    #[test]
+    fn payload_hash_does_not_depend_on_comments() {
+        let first = decode_payload_hash(&with_comment(GRAY8_16X16, b"first"));
+        let second = decode_payload_hash(&with_comment(GRAY8_16X16, b"a longer second comment"));
+
+        assert!(first.is_some());
+        assert_eq!(first, second);
+        assert_eq!(first, decode_payload_hash(GRAY8_16X16));
+        assert_ne!(first, decode_payload_hash(GRAY4_16X16));
+    }
+
+    #[test]
+    fn payload_hash_is_crc32_of_entropy_coded_data() {
+        let end_of_bit_stream = GRAY8_16X16.len() - 2;
+        let expected = GRAY8_16X16[START_OF_BIT_STREAM..end_of_bit_stream].iter()
+            .fold(crate::crc32::CRC32_INITIAL_VALUE, |crc, &value| crate::crc32::update_crc32(crc, value));
+
+        assert_eq!(decode_payload_hash(GRAY8_16X16), Some(crate::crc32::finish_crc32(expected)));
+    }
+
+    #[test]
+    fn payload_hash_of_planar_image_covers_all_scans() {
+        let first = decode_payload_hash(RGB8_PLANAR_16X16);
+
+        let mut decoder = Decoder::new(RGB8_PLANAR_16X16);
+        decoder.set_compute_payload_hash(true);
+        let mut red = vec![0; 16 * 16];
+        decoder.decode_components_subset(&[0], &mut [&mut red]).unwrap();
+
+        assert_eq!(decoder.payload_hash(), first);
+    }
+
+    #[test]
+    fn payload_hash_is_none_before_end_of_image_and_when_not_enabled() {
+        let mut decoder = Decoder::new(GRAY8_16X16);
+        decoder.set_compute_payload_hash(true);
+        decoder.read_header().unwrap();
+
+        assert_eq!(decoder.payload_hash(), None);
+
+        let mut decoder = Decoder::new(GRAY8_16X16);
+        decoder.decode().unwrap();
+
+        assert_eq!(decoder.payload_hash(), None);
+    }
+
+    /// Returns the encoded 8-bit indices of a scan that selects the mapping table with ID 1, without
+    /// a table definition: an abbreviated image stream.
+    fn encode_indices_selecting_table_1(indices: &[u8]) -> Vec<u8> {
+        let frame_info = FrameInfo::new(Width(8), Height(indices.len() as u32 / 8), 8, 1);
+        let mut stream = Encoder::new(frame_info).encode(indices).unwrap();
+        let start_of_scan = stream.windows(2).position(|marker| marker == [0xFF, 0xDA]).unwrap();
+        stream[start_of_scan + 6] = 1; // Tm1
+        stream
+    }
+
+    #[test]
+    fn decode_abbreviated_image_with_tables_of_tables_only_stream() {
+        let tables = [0xFF, 0xD8, 0xFF, 0xF8, 0x00, 0x0B, 2, 1, 3, 10, 11, 12, 20, 21, 22, 0xFF, 0xD9];
+        let table_set = crate::table_set::read_tables(&tables).unwrap();
+        let indices: Vec<u8> = (0..64).map(|i| (i % 2) as u8).collect();
+        let encoded = encode_indices_selecting_table_1(&indices);
+
+        let mut decoder = Decoder::with_tables(encoded.as_slice(), table_set);
+        let mapped = decoder.decode_with_mapping_tables().unwrap();
+
+        let expected: Vec<u8> = indices.iter().flat_map(|&index| [10 + index * 10, 11 + index * 10, 12 + index * 10])
+            .collect();
+        assert_eq!(mapped, expected);
+    }
+
+    #[test]
+    fn decode_abbreviated_image_without_tables_fails() {
+        let encoded = encode_indices_selecting_table_1(&[0; 64]);
+
+        let error = Decoder::new(encoded.as_slice()).decode_with_mapping_tables().unwrap_err();
+
+        assert_eq!(error, DecodingError::InvalidParameterMappingTableId);
+    }
+
+    #[test]
+    fn mapping_table_of_image_stream_replaces_table_of_table_set() {
+        let tables = [0xFF, 0xD8, 0xFF, 0xF8, 0x00, 0x06, 2, 1, 1, 5, 0xFF, 0xD9];
+        let table_set = crate::table_set::read_tables(&tables).unwrap();
+        let encoded = encode_indices_with_rgb_mapping_table(&[0; 64], &[1, 2, 3], 1);
+
+        let mut decoder = Decoder::with_tables(encoded.as_slice(), table_set);
+        let mapped = decoder.decode_with_mapping_tables().unwrap();
+
+        assert_eq!(decoder.mapping_table(1).unwrap().entry_size(), 3);
+        assert_eq!(&mapped[..3], &[1, 2, 3]);
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn decode_to_ndarray_of_monochrome_8_bit_image() {
+        let DecodedArray::U8(array) = Decoder::new(GRAY8_16X16).decode_to_ndarray().unwrap() else {
+            panic!("expected u8 samples");
+        };
+
+        assert_eq!(array.shape(), &[16, 16, 1]);
+        assert!(array.is_standard_layout());
+        assert_eq!(array[[0, 0, 0]], expected_gray8_sample(0, 0));
+        assert_eq!(array[[5, 11, 0]], expected_gray8_sample(11, 5));
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn decode_to_ndarray_is_the_same_for_all_interleave_modes() {
+        let decoded = Decoder::new(RGB8_SAMPLE_16X16).decode().unwrap();
+        for encoded in [RGB8_PLANAR_16X16, RGB8_LINE_16X16, RGB8_SAMPLE_16X16] {
+            let array = Decoder::new(encoded).decode_to_ndarray().unwrap();
+
+            assert_eq!(array.shape(), (16, 16, 3));
+            let DecodedArray::U8(array) = array else { panic!("expected u8 samples") };
+            assert!(array.is_standard_layout());
+            assert_eq!(array.as_slice().unwrap(), decoded.as_slice());
+            assert_eq!(array[[3, 7, 2]], decoded[(3 * 16 + 7) * 3 + 2]);
+        }
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn decode_to_ndarray_of_16_bit_image() {
+        let frame_info = FrameInfo::new(Width(5), Height(3), 16, 2);
+        let samples: Vec<u16> = (0..5 * 3 * 2).map(|i| i * 2021).collect();
+        let source: Vec<u8> = samples.iter().flat_map(|sample| sample.to_ne_bytes()).collect();
+        let mut encoder = Encoder::new(frame_info);
+        encoder.set_interleave_mode(InterleaveMode::Sample);
+        let encoded = encoder.encode(&source).unwrap();
+
+        let DecodedArray::U16(array) = Decoder::new(encoded.as_slice()).decode_to_ndarray().unwrap() else {
+            panic!("expected u16 samples");
+        };
+
+        assert_eq!(array.shape(), &[3, 5, 2]);
+        assert_eq!(array[[0, 0, 1]], samples[1]);
+        assert_eq!(array[[2, 4, 0]], samples[(2 * 5 + 4) * 2]);
+        assert_eq!(array.as_slice().unwrap(), samples.as_slice());
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn decode_to_ndarray_ignores_packing() {
+        let mut decoder = Decoder::new(GRAY4_16X16);
+        decoder.set_packed(true);
+
+        assert_eq!(decoder.decode_to_ndarray().unwrap().shape(), (16, 16, 1));
+    }
+
+    /// Returns the entropy coded data of the scan of a stream with one scan.
+    fn entropy_coded_data(encoded: &[u8]) -> &[u8] {
+        let start_of_scan = encoded.windows(2).position(|marker| marker == [0xFF, 0xDA]).unwrap();
+        let segment_size = u16::from_be_bytes([encoded[start_of_scan + 2], encoded[start_of_scan + 3]]) as usize;
+        &encoded[start_of_scan + 2 + segment_size..encoded.len() - 2]
+    }
+
+    #[test]
+    fn decode_planar_image_with_preset_coding_parameters_before_every_scan() {
+        const GRAY8_16X16_T5_10_30: &[u8] = include_bytes!("../tests/fixtures/gray8_16x16_t5_10_30.jls");
+        const GRAY8_16X16_T5_10_30_DECODED: &[u8] = include_bytes!("../tests/fixtures/gray8_16x16_t5_10_30.raw");
+
+        // SOI, SOF with 2 components, LSE with T1 = 5, T2 = 10, T3 = 30, the scan of component 1,
+        // LSE with the default parameters of 8 bit images, the scan of component 2 and EOI.
+        let mut encoded = vec![0xFF, 0xD8, 0xFF, 0xF7, 0, 14, 8, 0, 16, 0, 16, 2, 1, 0x11, 0, 2, 0x11, 0];
+        encoded.extend([0xFF, 0xF8, 0, 13, 1, 0, 255, 0, 5, 0, 10, 0, 30, 0, 64]);
+        encoded.extend([0xFF, 0xDA, 0, 8, 1, 1, 0, 0, 0, 0]);
+        encoded.extend(entropy_coded_data(GRAY8_16X16_T5_10_30));
+        encoded.extend([0xFF, 0xF8, 0, 13, 1, 0, 255, 0, 3, 0, 7, 0, 21, 0, 64]);
+        encoded.extend([0xFF, 0xDA, 0, 8, 1, 2, 0, 0, 0, 0]);
+        encoded.extend(entropy_coded_data(GRAY8_16X16));
+        encoded.extend([0xFF, 0xD9]);
+
+        let decoded = Decoder::new(encoded.as_slice()).decode().unwrap();
+
+        assert_eq!(&decoded[..16 * 16], GRAY8_16X16_T5_10_30_DECODED);
+        assert_eq!(&decoded[16 * 16..], Decoder::new(GRAY8_16X16).decode().unwrap().as_slice());
+    }
+
+    #[test]
+    fn decode_planar_image_with_preset_coding_parameters_of_previous_scan_fails() {
+        // Without the second LSE segment the scan of component 2 is decoded with T1 = 5, T2 = 10, T3 = 30,
+        // with which its entropy coded data is invalid.
+        let mut encoded = vec![0xFF, 0xD8, 0xFF, 0xF7, 0, 14, 8, 0, 16, 0, 16, 2, 1, 0x11, 0, 2, 0x11, 0];
+        encoded.extend([0xFF, 0xF8, 0, 13, 1, 0, 255, 0, 5, 0, 10, 0, 30, 0, 64]);
+        encoded.extend([0xFF, 0xDA, 0, 8, 1, 1, 0, 0, 0, 0]);
+        encoded.extend(entropy_coded_data(include_bytes!("../tests/fixtures/gray8_16x16_t5_10_30.jls")));
+        encoded.extend([0xFF, 0xDA, 0, 8, 1, 2, 0, 0, 0, 0]);
+        encoded.extend(entropy_coded_data(GRAY8_16X16));
+        encoded.extend([0xFF, 0xD9]);
+
+        assert_eq!(Decoder::new(encoded.as_slice()).decode().unwrap_err(), DecodingError::InvalidEncodedData);
+    }
+
+    #[test]
+    fn decode_stream_without_end_of_image_in_strict_mode_fails() {
+        let encoded = &GRAY8_16X16[..GRAY8_16X16.len() - 2];
+        let mut decoder = Decoder::new(encoded);
+
+        assert_eq!(decoder.decode().unwrap_err(), DecodingError::EndOfImageMarkerNotFound);
+        assert_eq!(decoder.decoded_row_count(), 16);
+    }
+
+    #[test]
+    fn decode_stream_without_end_of_image_in_lenient_mode() {
+        for encoded in [GRAY8_16X16, RGB8_SAMPLE_16X16, RGB8_PLANAR_16X16] {
+            let mut decoder = Decoder::new(&encoded[..encoded.len() - 2]);
+            decoder.set_strict_mode(false);
+
+            assert_eq!(decoder.decode().unwrap(), Decoder::new(encoded).decode().unwrap());
+            assert_eq!(decoder.state(), ReaderStatePublic::AfterEndOfImage);
+        }
+    }
+
+    #[test]
+    fn decode_planar_stream_that_ends_after_first_scan_in_lenient_mode_fails() {
+        let second_scan = RGB8_PLANAR_16X16.windows(2).rposition(|marker| marker == [0xFF, 0xDA]).unwrap();
+        let first_scans = RGB8_PLANAR_16X16[..second_scan].to_vec();
+        let mut decoder = Decoder::new(first_scans.as_slice());
+        decoder.set_strict_mode(false);
+
+        assert!(decoder.decode().is_err());
+    }
+
+    #[test]
+    fn compression_ratio_of_compressible_image() {
+        let frame_info = FrameInfo::new(Width(64), Height(64), 8, 1);
+        let source: Vec<u8> = (0..64 * 64).map(|i| (i % 64 / 8) as u8).collect();
+        let encoded = Encoder::new(frame_info).encode(&source).unwrap();
+        let mut decoder = Decoder::new(encoded.as_slice());
+
+        assert_eq!(decoder.compression_ratio(), None);
+        decoder.read_header().unwrap();
+        assert_eq!(decoder.compression_ratio(), None);
+
+        decoder.decode().unwrap();
+
+        let ratio = decoder.compression_ratio().unwrap();
+        assert!(ratio > 1.0, "ratio = {}", ratio);
+        assert_eq!(ratio, (64 * 64) as f64 / encoded.len() as f64);
+    }
+
+    #[test]
+    fn compression_ratio_does_not_count_bytes_before_start_of_image() {
+        let mut encoded = vec![0x12, 0x34, 0x56];
+        encoded.extend_from_slice(RGB8_SAMPLE_16X16);
+        let mut decoder = Decoder::new(encoded.as_slice());
+        decoder.set_strict_mode(false);
+        decoder.set_scan_for_soi(true);
+
+        decoder.decode().unwrap();
+
+        assert_eq!(decoder.compression_ratio(), Some((16 * 16 * 3) as f64 / RGB8_SAMPLE_16X16.len() as f64));
+    }
+
+    #[test]
+    fn decode_long_solid_run_with_run_length_order_of_j_table() {
+        // A 1000x2 image of zeros is coded in run mode with only 1 bits (see ISO/IEC 14495-1,
+        // A.7.1.1): 25 bits for the 796 samples of J[0..25], 1 bit for the rest of line 1 and 2
+        // bits for line 2 (RUNindex stays 25: 512 samples and the rest). The 28 bits with
+        // bit stuffing after every 0xFF byte are 0xFF 0x7F 0xFF 0x7C, as CharLS encodes them.
+        let mut encoded = vec![0xFF, 0xD8, 0xFF, 0xF7, 0, 11, 8, 0, 2, 0x03, 0xE8, 1, 1, 0x11, 0];
+        encoded.extend([0xFF, 0xDA, 0, 8, 1, 1, 0, 0, 0, 0]);
+        encoded.extend([0xFF, 0x7F, 0xFF, 0x7C]);
+        encoded.extend([0xFF, 0xD9]);
+
+        let decoded = Decoder::new(encoded.as_slice()).decode().unwrap();
+
+        assert_eq!(decoded, vec![0; 2000]);
+        assert_eq!(Encoder::new(FrameInfo::new(Width(1000), Height(2), 8, 1)).encode(&decoded).unwrap(), encoded);
+    }
+
+    fn value_ranges_of_interleaved_samples(decoded: &[u8], component_count: usize) -> Vec<(u32, u32)> {
+        (0..component_count).map(|component| {
+            let samples = decoded.iter().skip(component).step_by(component_count).map(|&value| value as u32);
+            (samples.clone().min().unwrap(), samples.max().unwrap())
+        }).collect()
+    }
+
+    #[test]
+    fn component_value_ranges_of_monochrome_image() {
+        let mut decoder = Decoder::new(GRAY8_16X16);
+        decoder.set_collect_value_ranges(true);
+        decoder.decode().unwrap();
+
+        let samples = (0..16).flat_map(|y| (0..16).map(move |x| expected_gray8_sample(x, y) as u32));
+        let expected = (samples.clone().min().unwrap(), samples.max().unwrap());
+        assert_eq!(decoder.component_value_ranges(), vec![expected]);
+    }
+
+    #[test]
+    fn component_value_ranges_of_color_images() {
+        let rgb = Decoder::new(RGB8_SAMPLE_16X16).decode().unwrap();
+        let expected = value_ranges_of_interleaved_samples(&rgb, 3);
+        assert_ne!(expected[0], expected[1]);
+
+        for encoded in [RGB8_PLANAR_16X16, RGB8_SAMPLE_16X16, RGB8_LINE_16X16] {
+            let mut decoder = Decoder::new(encoded);
+            decoder.set_collect_value_ranges(true);
+            decoder.set_channel_order(ChannelOrder::Bgr);
+            decoder.decode().unwrap();
+
+            // The ranges are in frame order, independent of the channel order of the output.
+            assert_eq!(decoder.component_value_ranges(), expected);
+        }
+    }
+
+    #[test]
+    fn component_value_ranges_of_16_bit_image() {
+        let source: Vec<u16> = (0..64).map(|i| 300 + i * 113 % 3000).collect();
+        let bytes: Vec<u8> = source.iter().flat_map(|value| value.to_ne_bytes()).collect();
+        let encoded = Encoder::new(FrameInfo::new(Width(8), Height(8), 12, 1)).encode(&bytes).unwrap();
+
+        let mut decoder = Decoder::new(encoded.as_slice());
+        decoder.set_collect_value_ranges(true);
+        decoder.decode().unwrap();
+
+        let expected = (*source.iter().min().unwrap() as u32, *source.iter().max().unwrap() as u32);
+        assert_eq!(decoder.component_value_ranges(), vec![expected]);
+    }
+
+    #[test]
+    fn component_value_ranges_are_empty_by_default() {
+        let mut decoder = Decoder::new(GRAY8_16X16);
+        decoder.decode().unwrap();
+
+        assert!(decoder.component_value_ranges().is_empty());
+    }
+
+    #[test]
+    fn decode_captures_application_data() {
+        let mut stream = GRAY8_16X16[..2].to_vec();
+        stream.extend_from_slice(&[0xFF, 0xEB, 0x00, 0x08, b'J', b'P', 0x00, 0x01, 0xAB, 0xCD]);
+        stream.extend_from_slice(&GRAY8_16X16[2..]);
+
+        let mut decoder = Decoder::new(stream.as_slice());
+        decoder.set_capture_application_data(11);
+        let decoded = decoder.decode().unwrap();
+
+        assert_eq!(decoder.application_data(11), Some(&[b'J', b'P', 0x00, 0x01, 0xAB, 0xCD][..]));
+        assert_eq!(decoder.application_data(0), None);
+        assert_eq!(decoded, Decoder::new(GRAY8_16X16).decode().unwrap());
+    }
+
+    #[test]
+    fn decode_into_returns_decoded_row_count() {
+        let mut destination = vec![0; 16 * 16];
+
+        assert_eq!(Decoder::new(GRAY8_16X16).decode_into(&mut destination), Ok(16));
+        check_rows(&destination, 16);
+    }
+
+    #[test]
+    fn decode_into_with_corrupted_scan_keeps_decoded_rows() {
+        // A marker in the middle of the entropy coded data ends the scan early.
+        let mut corrupted = GRAY8_16X16.to_vec();
+        let position = START_OF_BIT_STREAM + (GRAY8_16X16.len() - 2 - START_OF_BIT_STREAM) / 2;
+        corrupted[position..position + 2].copy_from_slice(&[0xFF, 0xE1]);
+        let mut decoder = Decoder::new(corrupted.as_slice());
+        let mut destination = vec![0; 16 * 16];
+
+        assert!(decoder.decode_into(&mut destination).is_err());
+        let row_count = decoder.decoded_row_count();
+        assert!(row_count > 0 && row_count < 16);
+        check_rows(&destination, row_count);
+    }
+
+    #[test]
+    fn decode_into_with_best_effort_returns_decoded_row_count() {
+        let mut corrupted = GRAY8_16X16.to_vec();
+        let position = START_OF_BIT_STREAM + (GRAY8_16X16.len() - 2 - START_OF_BIT_STREAM) / 2;
+        corrupted[position..position + 2].copy_from_slice(&[0xFF, 0xE1]);
+        let mut decoder = Decoder::new(corrupted.as_slice());
+        decoder.set_error_policy(ErrorPolicy::BestEffort);
+        let mut destination = vec![0; 16 * 16];
+
+        let row_count = decoder.decode_into(&mut destination).unwrap();
+
+        assert!(row_count > 0 && row_count < 16);
+        assert_eq!(row_count, decoder.decoded_row_count() as usize);
+        check_rows(&destination, row_count as u32);
+    }
+
+    #[test]
+    fn decode_to_sink_returns_frame_info() {
+        assert_eq!(Decoder::new(GRAY8_16X16).decode_to_sink(), Ok(FrameInfo::new(Width(16), Height(16), 8, 1)));
+        for encoded in [RGB8_PLANAR_16X16, RGB8_SAMPLE_16X16, RGB8_LINE_16X16] {
+            let mut decoder = Decoder::new(encoded);
+            assert_eq!(decoder.decode_to_sink(), Ok(FrameInfo::new(Width(16), Height(16), 8, 3)));
+            assert_eq!(decoder.decoded_row_count(), 16);
+        }
+    }
+
+    #[test]
+    fn decode_to_sink_of_truncated_stream_fails() {
+        let truncated = &GRAY8_16X16[..START_OF_BIT_STREAM + 20];
+
+        assert_eq!(Decoder::new(truncated).decode_to_sink(), Err(DecodingError::UnexpectedEndOfStream));
+    }
+
+    #[test]
+    fn decode_with_matching_expected_parameters() {
+        let mut decoder = Decoder::new(RGB8_SAMPLE_16X16);
+        decoder.set_expected_parameters(8, 3);
+
+        assert_eq!(decoder.decode().unwrap(), Decoder::new(RGB8_SAMPLE_16X16).decode().unwrap());
+    }
+
+    #[test]
+    fn decode_with_mismatching_expected_parameters_fails() {
+        for (bits_per_sample, component_count) in [(12, 3), (8, 1)] {
+            let mut decoder = Decoder::new(RGB8_SAMPLE_16X16);
+            decoder.set_expected_parameters(bits_per_sample, component_count);
+
+            assert_eq!(decoder.read_header().unwrap_err(), DecodingError::ParameterMismatch);
+            assert_eq!(decoder.decode().unwrap_err(), DecodingError::ParameterMismatch);
+        }
+    }
+
+    #[test]
+    fn decode_near_lossless_with_color_transformation() {
+        // 16 x 16, 8 bit RGB images, encoded by CharLS with NEAR = 2 and the reference decoded by CharLS.
+        let fixtures: [(&[u8], &[u8], ColorTransformation); 3] = [
+            (include_bytes!("../tests/fixtures/rgb8_hp1_sample_16x16_near2.jls"),
+             include_bytes!("../tests/fixtures/rgb8_hp1_sample_16x16_near2.raw"), ColorTransformation::Hp1),
+            (include_bytes!("../tests/fixtures/rgb8_hp2_sample_16x16_near2.jls"),
+             include_bytes!("../tests/fixtures/rgb8_hp2_sample_16x16_near2.raw"), ColorTransformation::Hp2),
+            (include_bytes!("../tests/fixtures/rgb8_hp3_line_16x16_near2.jls"),
+             include_bytes!("../tests/fixtures/rgb8_hp3_line_16x16_near2.raw"), ColorTransformation::Hp3),
+        ];
+
+        for (encoded, reference, color_transformation) in fixtures {
+            let mut decoder = Decoder::new(encoded);
+
+            let decoded = decoder.decode().unwrap();
+
+            assert_eq!(decoder.near_lossless(), 2);
+            assert_eq!(decoder.color_transformation(), color_transformation);
+            assert_eq!(decoded, reference);
+        }
+    }
+
+    #[test]
+    fn decode_with_jfif_header() {
+        let mut stream = GRAY8_16X16[..2].to_vec();
+        stream.extend_from_slice(&[0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F', 0, 1, 2, 1, 0, 72, 0, 72, 0, 0]);
+        stream.extend_from_slice(&GRAY8_16X16[2..]);
+        let mut decoder = Decoder::new(stream.as_slice());
+        decoder.set_strict_mode(false);
+
+        let decoded = decoder.decode().unwrap();
+
+        check_rows(&decoded, 16);
+    }
+
+    #[test]
+    fn near_lossless_per_component_of_planar_image() {
+        let (_, encoded) = encode_planar_with_near_lossless_5_for_second_scan();
+        let mut decoder = Decoder::new(encoded.as_slice());
+
+        decoder.read_header().unwrap();
+        assert!(decoder.near_lossless_per_component().is_empty());
+        decoder.decode().unwrap();
+
+        assert_eq!(decoder.near_lossless_per_component(), [0, 5, 0]);
+    }
+
+    #[test]
+    fn near_lossless_per_component_of_interleaved_image() {
+        let mut decoder = Decoder::new(RGB8_LINE_16X16);
+        decoder.decode().unwrap();
+
+        assert_eq!(decoder.near_lossless_per_component(), [0, 0, 0]);
+    }
+
+    #[test]
+    fn decode_dicom_le_stores_16_bit_samples_little_endian() {
+        // The reference file stores the samples little endian.
+        let encoded = include_bytes!("../tests/fixtures/bias16_64x64.jls");
+        let reference = include_bytes!("../tests/fixtures/bias16_64x64.raw");
+
+        let decoded = Decoder::new(encoded.as_slice()).decode_dicom_le().unwrap();
+
+        assert_eq!(decoded, reference);
+        let first_sample = u16::from_le_bytes([decoded[0], decoded[1]]);
+        assert_eq!(Decoder::new(encoded.as_slice()).decode().unwrap()[..2], first_sample.to_ne_bytes());
+    }
+
+    #[test]
+    fn decode_dicom_le_of_8_bit_image_matches_decode() {
+        assert_eq!(Decoder::new(GRAY8_16X16).decode_dicom_le().unwrap(), Decoder::new(GRAY8_16X16).decode().unwrap());
+    }
+
+    #[test]
+    fn read_scans_of_planar_image() {
+        let (_, encoded) = encode_planar_with_near_lossless_5_for_second_scan();
+        let mut decoder = Decoder::new(encoded.as_slice());
+
+        decoder.read_scans().unwrap();
+
+        let scans = decoder.scans();
+        assert_eq!(scans.len(), 3);
+        for (index, scan_info) in scans.iter().enumerate() {
+            assert_eq!(scan_info.component_ids(), &[index as u8 + 1]);
+            assert_eq!(scan_info.interleave_mode(), InterleaveMode::None);
+        }
+        assert_eq!(scans.iter().map(ScanInfo::near_lossless).collect::<Vec<_>>(), [0, 5, 0]);
+        assert_eq!(decoder.state(), ReaderStatePublic::AfterEndOfImage);
+        assert_eq!(decoder.decoded_row_count(), 0);
+    }
+
+    #[test]
+    fn read_scans_of_interleaved_image() {
+        let mut decoder = Decoder::new(RGB8_LINE_16X16);
+
+        decoder.read_scans().unwrap();
+
+        assert_eq!(decoder.scans().len(), 1);
+        assert_eq!(decoder.scans()[0].component_ids(), &[1, 2, 3]);
+    }
+
+    #[test]
+    fn read_scans_of_truncated_stream_fails() {
+        let truncated = &RGB8_PLANAR_16X16[..RGB8_PLANAR_16X16.len() - 20];
+        let mut decoder = Decoder::new(truncated);
+
+        assert!(decoder.read_scans().is_err());
+    }
+
+    #[test]
+    fn decode_with_maximum_segment_size() {
+        let mut stream = GRAY8_16X16[..2].to_vec();
+        stream.extend_from_slice(&[0xFF, 0xFE, 0x00, 0x0C]);
+        stream.extend_from_slice(&[b'x'; 10]);
+        stream.extend_from_slice(&GRAY8_16X16[2..]);
+        let expected = Decoder::new(GRAY8_16X16).decode().unwrap();
+
+        let mut decoder = Decoder::new(stream.as_slice());
+        decoder.set_maximum_segment_size(10);
+        assert_eq!(decoder.decode().unwrap(), expected);
+
+        let mut decoder = Decoder::new(stream.as_slice());
+        decoder.set_maximum_segment_size(9);
+        assert_eq!(decoder.decode().unwrap_err(), DecodingError::InvalidMarkerSegmentSize);
+
+        let mut decoder = Decoder::new(stream.as_slice());
+        decoder.set_maximum_segment_size(9);
+        decoder.set_strict_mode(false);
+        assert_eq!(decoder.decode().unwrap(), expected);
+    }
+
+    /// Decodes encoded with the SIMD and with the scalar line computations, which must be equal.
+    fn decode_with_and_without_simd(encoded: &[u8]) -> Vec<u8> {
+        simd_algorithm::set_enabled(true);
+        let simd = Decoder::new(encoded).decode().unwrap();
+        simd_algorithm::set_enabled(false);
+        let scalar = Decoder::new(encoded).decode().unwrap();
+
+        assert_eq!(simd, scalar);
+        simd
+    }
+
+    #[test]
+    fn decode_with_simd_equals_decode_without_simd() {
+        for encoded in [GRAY8_16X16, GRAY4_16X16, RUNS8_32X8, RUNS8_32X8_NEAR2, MAXVAL100_16X16, MAXVAL100_16X16_NEAR2,
+                        RGB8_PLANAR_16X16, RGB8_SAMPLE_16X16, RGB8_LINE_16X16] {
+            decode_with_and_without_simd(encoded);
+        }
+
+        // Widths that are not a multiple of the SIMD lane count, lossless and near-lossless encoded.
+        for (width, bits_per_sample, near_lossless) in [(61, 8, 0), (61, 8, 3), (37, 16, 0), (37, 12, 5)] {
+            let frame_info = FrameInfo::new(Width(width), Height(23), bits_per_sample, 1);
+            let mut seed = 1_u32;
+            let samples: Vec<u16> = (0..width * 23)
+                .map(|index| {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    ((index % width * 5 + (seed >> 16) % 64) % (1 << bits_per_sample)) as u16
+                })
+                .collect();
+            let source: Vec<u8> = if bits_per_sample <= 8 {
+                samples.iter().map(|&sample| sample as u8).collect()
+            } else {
+                samples.iter().flat_map(|sample| sample.to_ne_bytes()).collect()
+            };
+            let mut encoder = Encoder::new(frame_info.clone());
+            encoder.set_near_lossless(near_lossless);
+            simd_algorithm::set_enabled(true);
+            let encoded = encoder.encode(&source).unwrap();
+            let mut encoder = Encoder::new(frame_info);
+            encoder.set_near_lossless(near_lossless);
+            simd_algorithm::set_enabled(false);
+            assert_eq!(encoder.encode(&source).unwrap(), encoded);
+
+            let decoded = decode_with_and_without_simd(&encoded);
+            if near_lossless == 0 {
+                assert_eq!(decoded, source);
+            }
         }
     }
 }
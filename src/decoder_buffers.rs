@@ -0,0 +1,24 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use crate::regular_mode_context::RegularModeContext;
+
+/// The memory that the decoder uses for the line buffers and the context arrays of a scan and for
+/// the output of Decoder::decode_borrowed. A decoder reuses its buffers for all its scans; pass
+/// the buffers of a previous decoder to Decoder::with_buffers to also reuse them for the next
+/// image. Buffers of any image can be reused for any other image, they grow when needed.
+#[derive(Debug, Default)]
+pub struct DecoderBuffers {
+    pub(crate) lines: Vec<i32>,
+    pub(crate) line_contexts: Vec<i32>,
+    pub(crate) regular_mode_contexts: Vec<RegularModeContext>,
+    pub(crate) quantization_lut: Vec<i8>,
+    pub(crate) output: Vec<u8>,
+}
+
+impl DecoderBuffers {
+    /// Creates empty buffers, which allocate on first use.
+    pub fn new() -> DecoderBuffers {
+        DecoderBuffers::default()
+    }
+}
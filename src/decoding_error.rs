@@ -7,5 +7,204 @@ pub enum DecodingError {
     IoError,
     JpegMarkerStartByteNotFound,
     StartOfImageMarkerNotFound,
-    UnknownError
+    UnknownError,
+
+    /// The stream ended (or a marker was found) before all expected data was read.
+    UnexpectedEndOfStream,
+
+    /// A marker was found that is not defined by ISO/IEC 10918-1 or ISO/IEC 14495-1.
+    UnknownJpegMarkerFound,
+
+    /// A known marker was found at a position where it is not allowed.
+    UnexpectedMarkerFound,
+
+    /// The frame is encoded with a JPEG coding process other than JPEG-LS.
+    EncodingNotSupported,
+
+    /// The EOI marker was not found after the last scan. A stream that ends directly after the
+    /// last scan is only accepted in lenient mode.
+    EndOfImageMarkerNotFound,
+
+    /// More bytes than the limit set with Decoder::new_with_limit would have to be read.
+    InputTooLarge,
+
+    /// Encoding the decoded image again doesn't reproduce the stream (see Decoder::decode_verified).
+    VerificationFailed,
+
+    /// The SOF segment is missing: a SOS or EOI marker was found before it.
+    StartOfFrameMarkerNotFound,
+
+    /// The SOS segment is missing: an EOI marker or entropy coded data was found after the SOF segment.
+    StartOfScanMarkerNotFound,
+
+    /// The entropy coded data of a scan is corrupt: it contains a Golomb code longer than the
+    /// escape code, a run longer than the rest of the line or an error value that makes the
+    /// context variables overflow.
+    InvalidEncodedData,
+
+    /// The length field of a marker segment is invalid.
+    InvalidMarkerSegmentSize,
+
+    /// The bits per sample value in the SOF segment is outside the range 2..=16.
+    InvalidParameterBitsPerSample,
+
+    /// The width in the SOF segment is zero.
+    InvalidParameterWidth,
+
+    /// The height in the SOF segment is zero.
+    InvalidParameterHeight,
+
+    /// The component count in the SOF segment is zero, or the component count in the SOS segment
+    /// is zero, larger than 4 or larger than the component count of the frame. A scan with
+    /// interleave mode None must contain 1 component, an interleaved scan all components of the
+    /// frame: images with more than 4 (up to 255) components are only supported with interleave
+    /// mode None.
+    InvalidParameterComponentCount,
+
+    /// The NEAR value in the SOS segment is too large for the sample range.
+    InvalidParameterNearLossless,
+
+    /// The ILV value in the SOS segment is not a valid interleave mode.
+    InvalidParameterInterleaveMode,
+
+    /// The preset coding parameters from the LSE segment are not valid.
+    InvalidParameterJpeglsPresetParameters,
+
+    /// The destination buffer is too small to hold the decoded image.
+    DestinationTooSmall,
+
+    /// The destination doesn't have exactly one element for every sample of the decoded image.
+    InvalidParameterDestinationSize,
+
+    /// The sample offset maps decoded samples to values that the destination type can't represent.
+    InvalidParameterSampleOffset,
+
+    /// A mapping table is defined with ID 0 or twice, is continued without a definition, or a scan
+    /// selects a table that isn't defined or with a different entry size than the other components.
+    InvalidParameterMappingTableId,
+
+    /// A decoded sample is not an index of the mapping table of its component.
+    MappingTableIndexOutOfRange,
+
+    /// The SPIFF header is not consistent with the SOF segment (strict mode only).
+    InvalidSpiffHeader,
+
+    /// The SPIFF header is not followed by a SPIFF end of directory entry.
+    MissingEndOfSpiffDirectory,
+
+    /// The stream uses a color transformation that is not supported.
+    ColorTransformNotSupported,
+
+    /// The color transformation is used with a bit depth other than 8 or 16 bits.
+    BitDepthForTransformNotSupported,
+
+    /// The SOS segment of a scan that doesn't have 3 components signals a color transformation.
+    InvalidParameterColorTransformation,
+
+    /// The stride is smaller than the number of bytes of a decoded row.
+    InvalidParameterStride,
+
+    /// A parameter has a valid value that is not supported by JPEG-LS, like a sampling factor other than 1.
+    ParameterValueNotSupported,
+
+    /// The bits per sample or component count of the SOF segment differ from the values set with
+    /// Decoder::set_expected_parameters.
+    ParameterMismatch,
+}
+
+impl DecodingError {
+    /// A stable numeric code for the error, for a C API. The codes are the values of jpegls_errc of
+    /// CharLS for the errors that CharLS also reports; the other errors have codes from 1000 up.
+    pub fn to_error_code(&self) -> i32 {
+        match self {
+            DecodingError::IoError => 1000,
+            DecodingError::JpegMarkerStartByteNotFound => 12,
+            DecodingError::StartOfImageMarkerNotFound => 15,
+            DecodingError::UnknownError => 14,
+            DecodingError::UnexpectedEndOfStream => 4,
+            DecodingError::UnknownJpegMarkerFound => 11,
+            DecodingError::UnexpectedMarkerFound => 16,
+            DecodingError::EncodingNotSupported => 10,
+            DecodingError::EndOfImageMarkerNotFound => 28,
+            DecodingError::InputTooLarge => 1001,
+            DecodingError::VerificationFailed => 1002,
+            DecodingError::StartOfFrameMarkerNotFound => 1003,
+            DecodingError::StartOfScanMarkerNotFound => 1004,
+            DecodingError::InvalidEncodedData => 5,
+            DecodingError::InvalidMarkerSegmentSize => 17,
+            DecodingError::InvalidParameterBitsPerSample => 203,
+            DecodingError::InvalidParameterWidth => 200,
+            DecodingError::InvalidParameterHeight => 201,
+            DecodingError::InvalidParameterComponentCount => 202,
+            DecodingError::InvalidParameterNearLossless => 205,
+            DecodingError::InvalidParameterInterleaveMode => 204,
+            DecodingError::InvalidParameterJpeglsPresetParameters => 206,
+            DecodingError::DestinationTooSmall => 3,
+            DecodingError::InvalidParameterDestinationSize => 110,
+            DecodingError::InvalidParameterSampleOffset => 1005,
+            DecodingError::InvalidParameterMappingTableId => 208,
+            DecodingError::MappingTableIndexOutOfRange => 1006,
+            DecodingError::InvalidSpiffHeader => 29,
+            DecodingError::MissingEndOfSpiffDirectory => 24,
+            DecodingError::ColorTransformNotSupported => 9,
+            DecodingError::BitDepthForTransformNotSupported => 8,
+            DecodingError::InvalidParameterColorTransformation => 207,
+            DecodingError::InvalidParameterStride => 112,
+            DecodingError::ParameterValueNotSupported => 2,
+            DecodingError::ParameterMismatch => 1007,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_codes_are_stable_and_distinct() {
+        let codes = [
+            (DecodingError::IoError, 1000),
+            (DecodingError::JpegMarkerStartByteNotFound, 12),
+            (DecodingError::StartOfImageMarkerNotFound, 15),
+            (DecodingError::UnknownError, 14),
+            (DecodingError::UnexpectedEndOfStream, 4),
+            (DecodingError::UnknownJpegMarkerFound, 11),
+            (DecodingError::UnexpectedMarkerFound, 16),
+            (DecodingError::EncodingNotSupported, 10),
+            (DecodingError::EndOfImageMarkerNotFound, 28),
+            (DecodingError::InputTooLarge, 1001),
+            (DecodingError::VerificationFailed, 1002),
+            (DecodingError::StartOfFrameMarkerNotFound, 1003),
+            (DecodingError::StartOfScanMarkerNotFound, 1004),
+            (DecodingError::InvalidEncodedData, 5),
+            (DecodingError::InvalidMarkerSegmentSize, 17),
+            (DecodingError::InvalidParameterBitsPerSample, 203),
+            (DecodingError::InvalidParameterWidth, 200),
+            (DecodingError::InvalidParameterHeight, 201),
+            (DecodingError::InvalidParameterComponentCount, 202),
+            (DecodingError::InvalidParameterNearLossless, 205),
+            (DecodingError::InvalidParameterInterleaveMode, 204),
+            (DecodingError::InvalidParameterJpeglsPresetParameters, 206),
+            (DecodingError::DestinationTooSmall, 3),
+            (DecodingError::InvalidParameterDestinationSize, 110),
+            (DecodingError::InvalidParameterSampleOffset, 1005),
+            (DecodingError::InvalidParameterMappingTableId, 208),
+            (DecodingError::MappingTableIndexOutOfRange, 1006),
+            (DecodingError::InvalidSpiffHeader, 29),
+            (DecodingError::MissingEndOfSpiffDirectory, 24),
+            (DecodingError::ColorTransformNotSupported, 9),
+            (DecodingError::BitDepthForTransformNotSupported, 8),
+            (DecodingError::InvalidParameterColorTransformation, 207),
+            (DecodingError::InvalidParameterStride, 112),
+            (DecodingError::ParameterValueNotSupported, 2),
+            (DecodingError::ParameterMismatch, 1007),
+        ];
+
+        for (error, code) in &codes {
+            assert_eq!(error.to_error_code(), *code, "{:?}", error);
+        }
+        for (index, (error, code)) in codes.iter().enumerate() {
+            assert!(codes[index + 1..].iter().all(|(_, other)| other != code), "{:?}", error);
+        }
+    }
 }
@@ -0,0 +1,709 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use crate::bit_writer::BitWriter;
+use crate::constants::{maximum_sample_value, MAXIMUM_BITS_PER_SAMPLE, MAXIMUM_COMPONENT_COUNT_IN_SCAN,
+                       MINIMUM_BITS_PER_SAMPLE};
+use crate::encoding_error::EncodingError;
+use crate::endianness::Endianness;
+use crate::frame_info::FrameInfo;
+use crate::interleave_mode::InterleaveMode;
+use crate::jpeg_stream_writer::JpegStreamWriter;
+use crate::jpegls_algorithm::log2_ceil;
+use crate::jpegls_preset_coding_parameters::{compute_maximum_near_lossless, JpeglsPcParameters};
+use crate::scan_encoder::ScanEncoder;
+use crate::spiff_header::{SpiffHeader, SPIFF_COMPRESSION_TYPE_JPEG_LS, SPIFF_SEGMENTS_SIZE};
+
+
+/// Encodes an image as a JPEG-LS stream.
+///
+/// The source samples are read with the same layout as the decoder produces them: 1 byte per
+/// sample for precisions up to 8 bits and 2 bytes (native endian, see set_input_endianness)
+/// for higher precisions.
+/// Images encoded with interleave mode None are read component by component (planar),
+/// other images are read pixel interleaved.
+///
+/// An encoder owns all its state: it is Send and Sync, so it can be moved to another thread.
+#[derive(Debug)]
+pub struct Encoder {
+    frame_info: FrameInfo,
+    near_lossless: u8,
+    interleave_mode: InterleaveMode,
+    input_endianness: Endianness,
+    input_packed: bool,
+    spiff_header: Option<SpiffHeader>,
+}
+
+
+impl Encoder {
+    /// Creates an encoder for an image described by frame_info. By default the image is
+    /// encoded lossless with interleave mode None.
+    pub fn new(frame_info: FrameInfo) -> Encoder {
+        Encoder {
+            frame_info,
+            near_lossless: 0,
+            interleave_mode: InterleaveMode::None,
+            input_endianness: Endianness::NATIVE,
+            input_packed: false,
+            spiff_header: None,
+        }
+    }
+
+    /// Sets the NEAR parameter: the maximum difference between the source and decoded samples.
+    /// 0 (the default) means lossless.
+    pub fn set_near_lossless(&mut self, near_lossless: u8) {
+        self.near_lossless = near_lossless;
+    }
+
+    /// Sets how the components of a multi-component image are ordered in the encoded scans.
+    pub fn set_interleave_mode(&mut self, interleave_mode: InterleaveMode) {
+        self.interleave_mode = interleave_mode;
+    }
+
+    /// Sets the byte order of the source samples for images with more than 8 bits per sample.
+    /// The default is the byte order of the platform.
+    pub fn set_input_endianness(&mut self, input_endianness: Endianness) {
+        self.input_endianness = input_endianness;
+    }
+
+    /// Sets whether the source samples of images with 2 to 4 bits per sample are packed MSB-first
+    /// into bytes, multiple samples per byte, as Decoder::set_packed stores them, instead of
+    /// 1 byte per sample (the default). Every row starts at a byte boundary and the unused low
+    /// bits of the last byte of a row must be 0. For images with more than 4 bits per sample the
+    /// option has no effect.
+    pub fn set_input_packed(&mut self, input_packed: bool) {
+        self.input_packed = input_packed;
+    }
+
+    /// Writes a SPIFF header, followed by the SPIFF end of directory entry, directly after the
+    /// SOI marker of the encoded stream. The dimensions, component count and bits per sample of
+    /// the header must match the frame info.
+    pub fn write_spiff_header(&mut self, spiff_header: SpiffHeader) -> Result<(), EncodingError> {
+        let frame_info = &self.frame_info;
+        if spiff_header.width() != frame_info.width() || spiff_header.height() != frame_info.height() ||
+            spiff_header.component_count() != frame_info.component_count() ||
+            spiff_header.bits_per_sample() != frame_info.bits_per_sample() ||
+            spiff_header.compression_type() != SPIFF_COMPRESSION_TYPE_JPEG_LS || spiff_header.resolution_units() > 2 {
+            return Err(EncodingError::InvalidSpiffHeader);
+        }
+
+        self.spiff_header = Some(spiff_header);
+        Ok(())
+    }
+
+    /// The size in bytes of the source image.
+    pub fn source_size(&self) -> usize {
+        let frame_info = &self.frame_info;
+        if self.is_input_packed() {
+            return self.packed_stride() * frame_info.height() as usize * self.plane_count();
+        }
+
+        frame_info.width() as usize * frame_info.height() as usize * frame_info.bytes_per_pixel()
+    }
+
+    /// Returns an upper bound of the size in bytes of the stream that encode produces for an
+    /// image described by frame_info, to preallocate the destination buffer.
+    ///
+    /// The bound is derived as follows: every sample is coded with at most LIMIT bits
+    /// (ISO/IEC 14495-1, A.5.3), LIMIT = 2 * (bpp + max(8, bpp)). An escape code uses exactly
+    /// LIMIT bits, other regular mode codes use fewer. Run mode uses at most 1 bit per sample of
+    /// the run and the bits of the run length remainder are included in the LIMIT of the run
+    /// interruption sample. As a byte after 0xFF holds only 7 bits, the entropy coded data of a
+    /// scan needs at most ceil(bits / 7) bytes plus 1 byte of padding. The headers need 2 (SOI) +
+    /// 44 (optional SPIFF header) + 10 + 3 * Nf (SOF) + 10 per scan (SOS with 1 component, the
+    /// worst case) + 2 (EOI) bytes. LIMIT doesn't depend on NEAR, so the bound holds for every
+    /// valid NEAR value. The bits per sample of frame_info must be in the range 2..=16.
+    pub fn worst_case_encoded_size(frame_info: &FrameInfo, _near_lossless: u8) -> usize {
+        let maximum_sample_value = maximum_sample_value(frame_info.bits_per_sample()) as i32;
+        let bits_per_pixel = log2_ceil(maximum_sample_value + 1).max(2);
+        let limit = 2 * (bits_per_pixel + bits_per_pixel.max(8)) as usize;
+
+        let component_count = frame_info.component_count() as usize;
+        let sample_count = frame_info.width() as usize * frame_info.height() as usize * component_count;
+        let header_size = 2 + SPIFF_SEGMENTS_SIZE + 10 + 3 * component_count + 10 * component_count + 2;
+        header_size + (sample_count * limit).div_ceil(7) + component_count
+    }
+
+    /// Encodes the image in source, which must be at least source_size() bytes, and returns the
+    /// JPEG-LS stream.
+    pub fn encode(&mut self, source: &[u8]) -> Result<Vec<u8>, EncodingError> {
+        self.validate()?;
+        let mut destination = vec![0; Encoder::worst_case_encoded_size(&self.frame_info, self.near_lossless)];
+        let bytes_written = self.encode_into(source, &mut destination)?;
+        destination.truncate(bytes_written);
+        Ok(destination)
+    }
+
+    /// Encodes the image in source, which must be at least source_size() bytes, into destination
+    /// and returns the number of bytes written. A destination of worst_case_encoded_size() bytes
+    /// is always large enough.
+    pub fn encode_into(&mut self, source: &[u8], destination: &mut [u8]) -> Result<usize, EncodingError> {
+        self.validate()?;
+        if source.len() < self.source_size() {
+            return Err(EncodingError::SourceTooSmall);
+        }
+
+        let unpacked;
+        let source = if self.is_input_packed() {
+            unpacked = self.unpack_source(source)?;
+            unpacked.as_slice()
+        } else {
+            source
+        };
+
+        let frame_info = self.frame_info.clone();
+        let mut writer = JpegStreamWriter::new(destination);
+        writer.write_start_of_image()?;
+        if let Some(spiff_header) = &self.spiff_header {
+            writer.write_spiff_header_segment(spiff_header)?;
+            writer.write_spiff_end_of_directory_entry()?;
+        }
+
+        writer.write_start_of_frame_segment(&frame_info)?;
+
+        let stride = self.minimum_stride();
+        if self.interleave_mode == InterleaveMode::None {
+            let bytes_per_plane = stride * frame_info.height() as usize;
+            for component in 0..frame_info.component_count() {
+                writer.write_start_of_scan_segment(&[component + 1], self.near_lossless, self.interleave_mode)?;
+                let plane = &source[component as usize * bytes_per_plane..];
+                self.encode_scan(&mut writer, 1, plane, stride)?;
+            }
+        } else {
+            let component_ids: Vec<u8> = (1..=frame_info.component_count()).collect();
+            writer.write_start_of_scan_segment(&component_ids, self.near_lossless, self.interleave_mode)?;
+            self.encode_scan(&mut writer, frame_info.component_count(), source, stride)?;
+        }
+
+        writer.write_end_of_image()?;
+        Ok(writer.bytes_written())
+    }
+
+    fn validate(&self) -> Result<(), EncodingError> {
+        let frame_info = &self.frame_info;
+        if frame_info.width() == 0 || frame_info.width() > u16::MAX as u32 {
+            return Err(EncodingError::InvalidParameterWidth);
+        }
+
+        if frame_info.height() == 0 || frame_info.height() > u16::MAX as u32 {
+            return Err(EncodingError::InvalidParameterHeight);
+        }
+
+        if !(MINIMUM_BITS_PER_SAMPLE..=MAXIMUM_BITS_PER_SAMPLE).contains(&frame_info.bits_per_sample()) {
+            return Err(EncodingError::InvalidParameterBitsPerSample);
+        }
+
+        if frame_info.component_count() == 0 {
+            return Err(EncodingError::InvalidParameterComponentCount);
+        }
+
+        if frame_info.component_count() == 1 && self.interleave_mode != InterleaveMode::None {
+            return Err(EncodingError::InvalidParameterInterleaveMode);
+        }
+
+        // All components of an interleaved image are stored in a single scan.
+        if self.interleave_mode != InterleaveMode::None &&
+            frame_info.component_count() > MAXIMUM_COMPONENT_COUNT_IN_SCAN {
+            return Err(EncodingError::InvalidParameterComponentCount);
+        }
+
+        if self.near_lossless as i32 > compute_maximum_near_lossless(self.maximum_sample_value()) {
+            return Err(EncodingError::InvalidParameterNearLossless);
+        }
+
+        Ok(())
+    }
+
+    fn maximum_sample_value(&self) -> i32 {
+        maximum_sample_value(self.frame_info.bits_per_sample()) as i32
+    }
+
+    /// The number of bytes of a source row. For images encoded with interleave mode None, a row
+    /// holds the samples of a single component.
+    fn minimum_stride(&self) -> usize {
+        let components_in_row = if self.interleave_mode == InterleaveMode::None {
+            1
+        } else {
+            self.frame_info.component_count() as usize
+        };
+        self.frame_info.width() as usize * components_in_row * self.frame_info.bytes_per_sample()
+    }
+
+    fn is_input_packed(&self) -> bool {
+        self.input_packed && self.frame_info.bits_per_sample() <= 4
+    }
+
+    /// The number of rows of samples in the source: one set per component for interleave mode None.
+    fn plane_count(&self) -> usize {
+        if self.interleave_mode == InterleaveMode::None {
+            self.frame_info.component_count() as usize
+        } else {
+            1
+        }
+    }
+
+    /// The number of bytes of a packed source row.
+    fn packed_stride(&self) -> usize {
+        let samples_in_row = self.minimum_stride() / self.frame_info.bytes_per_sample();
+        (samples_in_row * self.frame_info.bits_per_sample() as usize).div_ceil(8)
+    }
+
+    /// Unpacks the rows of a packed source to 1 byte per sample.
+    fn unpack_source(&self, source: &[u8]) -> Result<Vec<u8>, EncodingError> {
+        let packed_stride = self.packed_stride();
+        let stride = self.minimum_stride();
+        let row_count = self.frame_info.height() as usize * self.plane_count();
+        let mut unpacked = vec![0; stride * row_count];
+        for (packed_row, row) in source.chunks_exact(packed_stride).zip(unpacked.chunks_exact_mut(stride)) {
+            unpack_row(packed_row, self.frame_info.bits_per_sample(), row)?;
+        }
+
+        Ok(unpacked)
+    }
+
+    fn encode_scan(&self, writer: &mut JpegStreamWriter, component_count: u8, source: &[u8],
+                   stride: usize) -> Result<(), EncodingError> {
+        let preset_coding_parameters =
+            JpeglsPcParameters::compute_default(self.maximum_sample_value(), self.near_lossless as i32);
+        let mut scan_encoder = ScanEncoder::new(self.frame_info.width(), self.frame_info.height(),
+                                                self.frame_info.bits_per_sample(), component_count,
+                                                self.interleave_mode, self.near_lossless,
+                                                &preset_coding_parameters);
+        scan_encoder.set_input_endianness(self.input_endianness);
+
+        let mut bit_writer = BitWriter::new(writer);
+        scan_encoder.encode(&mut bit_writer, source, stride)?;
+        bit_writer.end_scan()
+    }
+}
+
+/// Unpacks the MSB-first packed samples of a row. The unused low bits of the last byte must be 0.
+fn unpack_row(packed: &[u8], bits_per_sample: u8, samples: &mut [u8]) -> Result<(), EncodingError> {
+    let bits_per_sample = bits_per_sample as usize;
+    let mask = (1 << bits_per_sample) - 1;
+    for (index, sample) in samples.iter_mut().enumerate() {
+        let bit_position = index * bits_per_sample;
+        let shift = 16 - bits_per_sample - bit_position % 8;
+        let high = (packed[bit_position / 8] as u16) << 8;
+        let low = if shift < 8 { packed[bit_position / 8 + 1] as u16 } else { 0 };
+        *sample = (((high | low) >> shift) & mask) as u8;
+    }
+
+    let padding_bits = packed.len() * 8 - samples.len() * bits_per_sample;
+    if packed[packed.len() - 1] & ((1 << padding_bits) - 1) as u8 != 0 {
+        return Err(EncodingError::InvalidSourcePadding);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::Decoder;
+    use crate::frame_info::{Height, Width};
+
+    // Images encoded by CharLS with default parameters and the images as decoded by CharLS.
+    // The encoder must produce the same streams.
+    const CHARLS_FIXTURES: [(&[u8], &[u8], u8, InterleaveMode); 8] = [
+        (include_bytes!("../tests/fixtures/gray8_16x16.jls"),
+         include_bytes!("../tests/fixtures/gray8_16x16.raw"), 0, InterleaveMode::None),
+        (include_bytes!("../tests/fixtures/gray4_16x16.jls"),
+         include_bytes!("../tests/fixtures/gray4_16x16.raw"), 0, InterleaveMode::None),
+        (include_bytes!("../tests/fixtures/runs8_32x8_near2.jls"),
+         include_bytes!("../tests/fixtures/runs8_32x8.raw"), 2, InterleaveMode::None),
+        (include_bytes!("../tests/fixtures/rgb8_sample_16x16.jls"),
+         include_bytes!("../tests/fixtures/rgb8_sample_16x16.raw"), 0, InterleaveMode::Sample),
+        (include_bytes!("../tests/fixtures/rgb8_line_16x16.jls"),
+         include_bytes!("../tests/fixtures/rgb8_line_16x16.raw"), 0, InterleaveMode::Line),
+        (include_bytes!("../tests/fixtures/rgb8_planar_16x16.jls"),
+         include_bytes!("../tests/fixtures/rgb8_planar_16x16.raw"), 0, InterleaveMode::None),
+        // Noise between the extremes: most errors exceed LIMIT and are escape coded.
+        (include_bytes!("../tests/fixtures/escape8_16x16.jls"),
+         include_bytes!("../tests/fixtures/escape8_16x16.raw"), 0, InterleaveMode::None),
+        (include_bytes!("../tests/fixtures/escape8_16x16_near3.jls"),
+         include_bytes!("../tests/fixtures/escape8_16x16.raw"), 3, InterleaveMode::None),
+    ];
+
+    /// Creates samples with gradients, noise and flat areas, to exercise both regular and run mode.
+    fn create_samples(frame_info: &FrameInfo) -> Vec<u8> {
+        let maximum_sample_value = maximum_sample_value(frame_info.bits_per_sample());
+        let sample_count = frame_info.width() * frame_info.height() * frame_info.component_count() as u32;
+        let mut seed: u32 = 7;
+        let mut samples = Vec::new();
+        for index in 0..sample_count {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let value = match (index / 37) % 3 {
+                0 => index * 3,
+                1 => seed >> 8,
+                _ => 100,
+            } & maximum_sample_value;
+
+            if frame_info.bits_per_sample() <= 8 {
+                samples.push(value as u8);
+            } else {
+                samples.extend_from_slice(&(value as u16).to_ne_bytes());
+            }
+        }
+
+        samples
+    }
+
+    fn encode(frame_info: &FrameInfo, near_lossless: u8, interleave_mode: InterleaveMode,
+              source: &[u8]) -> Result<Vec<u8>, EncodingError> {
+        let mut encoder = Encoder::new(frame_info.clone());
+        encoder.set_near_lossless(near_lossless);
+        encoder.set_interleave_mode(interleave_mode);
+        encoder.encode(source)
+    }
+
+    #[test]
+    fn encoder_is_send_and_sync() {
+        fn assert_send_and_sync<T: Send + Sync>() {}
+
+        assert_send_and_sync::<Encoder>();
+    }
+
+    #[test]
+    fn encode_produces_same_stream_as_charls() {
+        for (encoded, source, near_lossless, interleave_mode) in CHARLS_FIXTURES {
+            let mut decoder = Decoder::new(encoded);
+            decoder.read_header().unwrap();
+            let frame_info = decoder.frame_info().clone();
+
+            assert_eq!(encode(&frame_info, near_lossless, interleave_mode, source).unwrap(), encoded);
+        }
+    }
+
+    #[test]
+    fn encode_and_decode_lossless() {
+        let frames = [
+            (FrameInfo::new(Width(33), Height(17), 8, 1), InterleaveMode::None),
+            (FrameInfo::new(Width(19), Height(11), 2, 1), InterleaveMode::None),
+            (FrameInfo::new(Width(21), Height(13), 12, 1), InterleaveMode::None),
+            (FrameInfo::new(Width(17), Height(9), 16, 1), InterleaveMode::None),
+            (FrameInfo::new(Width(15), Height(8), 8, 3), InterleaveMode::None),
+            (FrameInfo::new(Width(15), Height(8), 8, 3), InterleaveMode::Line),
+            (FrameInfo::new(Width(15), Height(8), 10, 3), InterleaveMode::Sample),
+            (FrameInfo::new(Width(9), Height(7), 16, 4), InterleaveMode::Sample),
+        ];
+
+        for (frame_info, interleave_mode) in frames {
+            let source = create_samples(&frame_info);
+
+            let encoded = encode(&frame_info, 0, interleave_mode, &source).unwrap();
+
+            let mut decoder = Decoder::new(encoded.as_slice());
+            assert_eq!(decoder.decode().unwrap(), source, "{:?} {:?}", frame_info, interleave_mode);
+            assert_eq!(decoder.interleave_mode(), interleave_mode);
+        }
+    }
+
+    #[test]
+    fn encode_and_decode_images_of_varying_content() {
+        type Pattern = fn(u32, u32, u32) -> u32;
+        let patterns: [(&str, Pattern); 6] = [
+            ("flat", |_, _, maximum| maximum / 3),
+            ("horizontal gradient", |x, _, maximum| (x * 5) % (maximum + 1)),
+            ("vertical gradient", |_, y, maximum| maximum - (y * 3) % (maximum + 1)),
+            ("checkerboard of extremes", |x, y, maximum| if (x + y) % 2 == 0 { 0 } else { maximum }),
+            ("noise", |x, y, maximum| ((x * 7919 + y * 104_729).wrapping_mul(2_654_435_761) >> 7) & maximum),
+            ("flat with spikes", |x, y, maximum| if (x * 3 + y) % 11 == 0 { maximum } else { 1 }),
+        ];
+        let frames = [
+            (FrameInfo::new(Width(23), Height(11), 8, 1), InterleaveMode::None),
+            (FrameInfo::new(Width(1), Height(9), 8, 1), InterleaveMode::None),
+            (FrameInfo::new(Width(13), Height(7), 12, 3), InterleaveMode::Line),
+            (FrameInfo::new(Width(13), Height(7), 6, 3), InterleaveMode::Sample),
+        ];
+
+        for (name, pattern) in patterns {
+            for (frame_info, interleave_mode) in &frames {
+                let maximum = maximum_sample_value(frame_info.bits_per_sample());
+                let samples_per_row = frame_info.width() * frame_info.component_count() as u32;
+                let source: Vec<u8> = (0..samples_per_row * frame_info.height())
+                    .map(|index| pattern(index % samples_per_row, index / samples_per_row, maximum))
+                    .flat_map(|value| if frame_info.bits_per_sample() <= 8 {
+                        vec![value as u8]
+                    } else {
+                        (value as u16).to_ne_bytes().to_vec()
+                    })
+                    .collect();
+
+                let encoded = encode(frame_info, 0, *interleave_mode, &source).unwrap();
+
+                let decoded = Decoder::new(encoded.as_slice()).decode().unwrap();
+                assert_eq!(decoded, source, "{} {:?} {:?}", name, frame_info, interleave_mode);
+            }
+        }
+    }
+
+    #[test]
+    fn encode_solid_color_image_uses_run_mode() {
+        // Every line is a single run, coded in a few bits once RUNindex has grown.
+        let frame_info = FrameInfo::new(Width(256), Height(256), 8, 1);
+        let source = vec![77; 256 * 256];
+
+        let encoded = encode(&frame_info, 0, InterleaveMode::None, &source).unwrap();
+
+        assert!(encoded.len() < 128, "{}", encoded.len());
+        assert_eq!(Decoder::new(encoded.as_slice()).decode().unwrap(), source);
+    }
+
+    #[test]
+    fn encode_solid_color_image_with_run_interruptions() {
+        // Every line has a single sample with another value, which interrupts the runs.
+        let frame_info = FrameInfo::new(Width(256), Height(64), 8, 3);
+        let mut source = [10u8, 200, 30].repeat(256 * 64);
+        for y in 0..64 {
+            source[(y * 256 + y * 3 + 5) * 3 + y % 3] = 90;
+        }
+
+        for interleave_mode in [InterleaveMode::Line, InterleaveMode::Sample] {
+            let encoded = encode(&frame_info, 0, interleave_mode, &source).unwrap();
+
+            assert!(encoded.len() < 1000, "{}", encoded.len());
+            assert_eq!(Decoder::new(encoded.as_slice()).decode().unwrap(), source);
+        }
+    }
+
+    #[test]
+    fn encode_with_spiff_header_round_trips() {
+        let frame_info = FrameInfo::new(Width(15), Height(8), 8, 3);
+        let source = create_samples(&frame_info);
+        let mut spiff_header = SpiffHeader::new(&frame_info, 10);
+        spiff_header.set_resolution(1, 96, 72);
+        let mut encoder = Encoder::new(frame_info.clone());
+        encoder.set_interleave_mode(InterleaveMode::Sample);
+        encoder.write_spiff_header(spiff_header.clone()).unwrap();
+
+        let encoded = encoder.encode(&source).unwrap();
+
+        let mut decoder = Decoder::new(encoded.as_slice());
+        assert_eq!(decoder.decode().unwrap(), source);
+        assert_eq!(decoder.spiff_header(), Some(&spiff_header));
+    }
+
+    #[test]
+    fn write_spiff_header_inconsistent_with_frame_fails() {
+        let frame_info = FrameInfo::new(Width(15), Height(8), 8, 3);
+        let mut encoder = Encoder::new(frame_info);
+
+        for other_frame_info in [FrameInfo::new(Width(16), Height(8), 8, 3), FrameInfo::new(Width(15), Height(9), 8, 3),
+                                 FrameInfo::new(Width(15), Height(8), 12, 3),
+                                 FrameInfo::new(Width(15), Height(8), 8, 1)] {
+            let spiff_header = SpiffHeader::new(&other_frame_info, 10);
+            assert_eq!(encoder.write_spiff_header(spiff_header), Err(EncodingError::InvalidSpiffHeader));
+        }
+
+        let mut spiff_header = SpiffHeader::new(&FrameInfo::new(Width(15), Height(8), 8, 3), 10);
+        spiff_header.set_resolution(3, 1, 1);
+        assert_eq!(encoder.write_spiff_header(spiff_header), Err(EncodingError::InvalidSpiffHeader));
+    }
+
+    #[test]
+    fn encode_and_decode_near_lossless() {
+        let frame_info = FrameInfo::new(Width(31), Height(9), 8, 3);
+        let source = create_samples(&frame_info);
+
+        for interleave_mode in [InterleaveMode::None, InterleaveMode::Line, InterleaveMode::Sample] {
+            let encoded = encode(&frame_info, 3, interleave_mode, &source).unwrap();
+
+            let mut decoder = Decoder::new(encoded.as_slice());
+            let decoded = decoder.decode().unwrap();
+            assert_eq!(decoder.near_lossless(), 3);
+            for (decoded_sample, source_sample) in decoded.iter().zip(&source) {
+                assert!((*decoded_sample as i32 - *source_sample as i32).abs() <= 3);
+            }
+        }
+    }
+
+    #[test]
+    fn encode_little_and_big_endian_input() {
+        let frame_info = FrameInfo::new(Width(13), Height(7), 12, 3);
+        let source = create_samples(&frame_info);
+        let values: Vec<u16> = source.chunks_exact(2).map(|bytes| u16::from_ne_bytes([bytes[0], bytes[1]])).collect();
+
+        for (input_endianness, to_bytes) in [(Endianness::Little, u16::to_le_bytes as fn(u16) -> [u8; 2]),
+                                             (Endianness::Big, u16::to_be_bytes)] {
+            let input: Vec<u8> = values.iter().flat_map(|&value| to_bytes(value)).collect();
+            let mut encoder = Encoder::new(frame_info.clone());
+            encoder.set_interleave_mode(InterleaveMode::Sample);
+            encoder.set_input_endianness(input_endianness);
+
+            let encoded = encoder.encode(&input).unwrap();
+
+            let decoded = Decoder::new(encoded.as_slice()).decode().unwrap();
+            assert_eq!(decoded, source, "{:?}", input_endianness);
+        }
+    }
+
+    #[test]
+    fn encoded_size_never_exceeds_worst_case_encoded_size() {
+        let images = [
+            (FrameInfo::new(Width(1), Height(1), 8, 1), InterleaveMode::None, 0),
+            (FrameInfo::new(Width(33), Height(17), 8, 1), InterleaveMode::None, 0),
+            (FrameInfo::new(Width(16), Height(16), 2, 1), InterleaveMode::None, 0),
+            (FrameInfo::new(Width(21), Height(13), 16, 1), InterleaveMode::None, 0),
+            (FrameInfo::new(Width(15), Height(8), 8, 3), InterleaveMode::None, 2),
+            (FrameInfo::new(Width(15), Height(8), 12, 3), InterleaveMode::Line, 0),
+            (FrameInfo::new(Width(9), Height(7), 16, 4), InterleaveMode::Sample, 0),
+        ];
+
+        for (frame_info, interleave_mode, near_lossless) in images {
+            let worst_case_encoded_size = Encoder::worst_case_encoded_size(&frame_info, near_lossless);
+
+            // Noise is the worst case for the entropy coder; all ones produces many 0xFF bytes.
+            let noise = create_samples(&frame_info);
+            let maximum_value = maximum_sample_value(frame_info.bits_per_sample()) as u16;
+            let all_ones: Vec<u8> = if frame_info.bits_per_sample() <= 8 {
+                vec![maximum_value as u8; noise.len()]
+            } else {
+                (0..noise.len() / 2).flat_map(|_| maximum_value.to_ne_bytes()).collect()
+            };
+
+            for source in [noise, all_ones] {
+                let encoded = encode(&frame_info, near_lossless, interleave_mode, &source).unwrap();
+                assert!(encoded.len() <= worst_case_encoded_size, "{:?}: {} > {}", frame_info,
+                        encoded.len(), worst_case_encoded_size);
+            }
+        }
+    }
+
+    #[test]
+    fn encode_into_worst_case_sized_destination() {
+        let frame_info = FrameInfo::new(Width(15), Height(8), 8, 3);
+        let source = create_samples(&frame_info);
+        let mut encoder = Encoder::new(frame_info.clone());
+        encoder.set_interleave_mode(InterleaveMode::Line);
+        let mut destination = vec![0; Encoder::worst_case_encoded_size(&frame_info, 0)];
+
+        let bytes_written = encoder.encode_into(&source, &mut destination).unwrap();
+
+        assert_eq!(destination[..bytes_written], encoder.encode(&source).unwrap());
+        let decoded = Decoder::new(&destination[..bytes_written]).decode().unwrap();
+        assert_eq!(decoded, source);
+    }
+
+    #[test]
+    fn encode_into_exactly_sized_destination() {
+        let frame_info = FrameInfo::new(Width(33), Height(17), 8, 1);
+        let source = create_samples(&frame_info);
+        let mut encoder = Encoder::new(frame_info);
+        let encoded = encoder.encode(&source).unwrap();
+        let mut destination = vec![0; encoded.len()];
+
+        assert_eq!(encoder.encode_into(&source, &mut destination), Ok(encoded.len()));
+        assert_eq!(destination, encoded);
+    }
+
+    #[test]
+    fn encode_into_too_small_destination_returns_error() {
+        let frame_info = FrameInfo::new(Width(33), Height(17), 8, 1);
+        let source = create_samples(&frame_info);
+        let mut encoder = Encoder::new(frame_info);
+        let encoded_size = encoder.encode(&source).unwrap().len();
+
+        for size in [0, 10, encoded_size / 2, encoded_size - 1] {
+            let mut destination = vec![0; size];
+            assert_eq!(encoder.encode_into(&source, &mut destination), Err(EncodingError::DestinationTooSmall));
+        }
+    }
+
+    #[test]
+    fn encode_with_invalid_parameters_returns_error() {
+        let source = [0; 16];
+
+        assert_eq!(encode(&FrameInfo::new(Width(0), Height(1), 8, 1), 0, InterleaveMode::None, &source),
+                   Err(EncodingError::InvalidParameterWidth));
+        assert_eq!(encode(&FrameInfo::new(Width(1), Height(65536), 8, 1), 0, InterleaveMode::None, &source),
+                   Err(EncodingError::InvalidParameterHeight));
+        assert_eq!(encode(&FrameInfo::new(Width(1), Height(1), 17, 1), 0, InterleaveMode::None, &source),
+                   Err(EncodingError::InvalidParameterBitsPerSample));
+        assert_eq!(encode(&FrameInfo::new(Width(1), Height(1), 8, 0), 0, InterleaveMode::None, &source),
+                   Err(EncodingError::InvalidParameterComponentCount));
+        assert_eq!(encode(&FrameInfo::new(Width(1), Height(1), 8, 1), 0, InterleaveMode::Line, &source),
+                   Err(EncodingError::InvalidParameterInterleaveMode));
+        assert_eq!(encode(&FrameInfo::new(Width(1), Height(1), 8, 5), 0, InterleaveMode::Sample, &source),
+                   Err(EncodingError::InvalidParameterComponentCount));
+        assert_eq!(encode(&FrameInfo::new(Width(1), Height(1), 4, 1), 8, InterleaveMode::None, &source),
+                   Err(EncodingError::InvalidParameterNearLossless));
+        assert_eq!(encode(&FrameInfo::new(Width(4), Height(4), 9, 1), 0, InterleaveMode::None, &source),
+                   Err(EncodingError::SourceTooSmall));
+    }
+
+    fn encode_packed(frame_info: FrameInfo, interleave_mode: InterleaveMode,
+                     source: &[u8]) -> Result<Vec<u8>, EncodingError> {
+        let mut encoder = Encoder::new(frame_info);
+        encoder.set_interleave_mode(interleave_mode);
+        encoder.set_input_packed(true);
+        encoder.encode(source)
+    }
+
+    fn decode_packed(encoded: &[u8]) -> Vec<u8> {
+        let mut decoder = Decoder::new(encoded);
+        decoder.set_packed(true);
+        decoder.decode().unwrap()
+    }
+
+    #[test]
+    fn encode_packed_2_bit_source_round_trips() {
+        // 13 samples of 2 bits use 26 bits of the 4 bytes of a row; the low 6 bits are padding.
+        let source: Vec<u8> = (0..5u8)
+            .flat_map(|row| [0b0001_1011 ^ row, 0b1110_0100, row * 17, (row & 3) << 6])
+            .collect();
+
+        let encoded = encode_packed(FrameInfo::new(Width(13), Height(5), 2, 1), InterleaveMode::None, &source).unwrap();
+
+        assert_eq!(decode_packed(&encoded), source);
+    }
+
+    #[test]
+    fn encode_packed_source_matches_encode_of_unpacked_source() {
+        let frame_info = FrameInfo::new(Width(3), Height(2), 4, 3);
+        let unpacked: Vec<u8> = (0..18).map(|i| (i * 5 % 16) as u8).collect();
+        let packed: Vec<u8> = unpacked
+            .chunks(9)
+            .flat_map(|row| row.chunks(2).map(|pair| pair[0] << 4 | pair.get(1).unwrap_or(&0)))
+            .collect();
+        let expected = encode(&frame_info, 0, InterleaveMode::Sample, &unpacked).unwrap();
+
+        assert_eq!(encode_packed(frame_info, InterleaveMode::Sample, &packed).unwrap(), expected);
+    }
+
+    #[test]
+    fn encode_packed_planar_source_round_trips() {
+        // 2 components of 3 rows of 5 samples of 3 bits: 15 bits in 2 bytes per row.
+        let source: Vec<u8> = (0..6u8).flat_map(|row| [0b1010_0101 ^ (row << 1), (row * 36) & 0xFE]).collect();
+
+        let encoded = encode_packed(FrameInfo::new(Width(5), Height(3), 3, 2), InterleaveMode::None, &source).unwrap();
+
+        assert_eq!(decode_packed(&encoded), source);
+    }
+
+    #[test]
+    fn encode_packed_source_with_nonzero_row_padding_fails() {
+        let mut source = vec![0; 4 * 5];
+        source[7] = 0b0000_0001;
+
+        assert_eq!(encode_packed(FrameInfo::new(Width(13), Height(5), 2, 1), InterleaveMode::None, &source),
+                   Err(EncodingError::InvalidSourcePadding));
+    }
+
+    #[test]
+    fn encode_packed_source_too_small_fails() {
+        let source = vec![0; 4 * 5 - 1];
+
+        assert_eq!(encode_packed(FrameInfo::new(Width(13), Height(5), 2, 1), InterleaveMode::None, &source),
+                   Err(EncodingError::SourceTooSmall));
+    }
+
+    #[test]
+    fn input_packed_option_is_ignored_for_more_than_4_bits_per_sample() {
+        let source: Vec<u8> = (0..32).collect();
+        let frame_info = FrameInfo::new(Width(8), Height(4), 5, 1);
+        let expected = encode(&frame_info, 0, InterleaveMode::None, &source).unwrap();
+
+        assert_eq!(encode_packed(frame_info, InterleaveMode::None, &source).unwrap(), expected);
+    }
+}
@@ -0,0 +1,38 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+#[derive(Debug, PartialEq)]
+pub enum EncodingError {
+    /// An internal error: the coding context reached a state that valid samples cannot produce.
+    UnknownError,
+
+    /// The width is 0 or larger than 65535 (the maximum that can be stored in the SOF segment).
+    InvalidParameterWidth,
+
+    /// The height is 0 or larger than 65535 (the maximum that can be stored in the SOF segment).
+    InvalidParameterHeight,
+
+    /// The bits per sample is outside the range 2..=16.
+    InvalidParameterBitsPerSample,
+
+    /// The component count is 0, or larger than 4 with interleave mode Line or Sample.
+    InvalidParameterComponentCount,
+
+    /// The NEAR parameter is larger than the maximum allowed for the bits per sample.
+    InvalidParameterNearLossless,
+
+    /// An interleave mode other than None is used for an image with a single component.
+    InvalidParameterInterleaveMode,
+
+    /// The source buffer is smaller than the size of the image described by the frame info.
+    SourceTooSmall,
+
+    /// The destination buffer is too small to hold the encoded stream.
+    DestinationTooSmall,
+
+    /// The SPIFF header is not consistent with the frame info, or is not a header of a JPEG-LS image.
+    InvalidSpiffHeader,
+
+    /// The unused low bits of the last byte of a packed source row (see set_input_packed) are not 0.
+    InvalidSourcePadding,
+}
@@ -0,0 +1,54 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+/// The byte order of samples that are stored with 2 bytes per sample (9..=16 bits per sample).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Endianness {
+    Little, // The least significant byte is stored first.
+    Big,    // The most significant byte is stored first.
+}
+
+impl Endianness {
+    /// The byte order of the platform.
+    pub const NATIVE: Endianness = if cfg!(target_endian = "big") { Endianness::Big } else { Endianness::Little };
+
+    pub(crate) fn read_u16(&self, bytes: [u8; 2]) -> u16 {
+        match self {
+            Endianness::Little => u16::from_le_bytes(bytes),
+            Endianness::Big => u16::from_be_bytes(bytes),
+        }
+    }
+
+    /// Converts samples of 2 bytes from the byte order of the platform to this byte order.
+    pub(crate) fn convert_from_native(&self, samples: &mut [u8]) {
+        if *self != Endianness::NATIVE {
+            for sample in samples.chunks_exact_mut(2) {
+                sample.swap(0, 1);
+            }
+        }
+    }
+}
+
+impl Default for Endianness {
+    fn default() -> Self {
+        Endianness::NATIVE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_from_native_to_little_and_big_endian() {
+        let native: Vec<u8> = [0x1234u16, 0xABCD].iter().flat_map(|sample| sample.to_ne_bytes()).collect();
+
+        let mut little = native.clone();
+        Endianness::Little.convert_from_native(&mut little);
+        let mut big = native;
+        Endianness::Big.convert_from_native(&mut big);
+
+        assert_eq!(little, [0x34, 0x12, 0xCD, 0xAB]);
+        assert_eq!(big, [0x12, 0x34, 0xAB, 0xCD]);
+    }
+}
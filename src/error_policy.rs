@@ -0,0 +1,9 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+/// Defines how the decoder handles errors in the entropy coded data of a scan.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ErrorPolicy {
+    Abort,      // Decoding stops at the first error, which is returned.
+    BestEffort, // The rows that cannot be decoded are filled with the fill value and decoding continues.
+}
@@ -0,0 +1,173 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use std::fmt;
+
+/// The width of an image in samples per line. Width and Height are separate types, so that they
+/// can't be swapped in a call to FrameInfo::new.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Width(pub u32);
+
+/// The height of an image in lines.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Height(pub u32);
+
+impl From<u32> for Width {
+    fn from(width: u32) -> Width {
+        Width(width)
+    }
+}
+
+impl From<u32> for Height {
+    fn from(height: u32) -> Height {
+        Height(height)
+    }
+}
+
+/// Describes the dimensions and sample layout of a JPEG-LS frame, as defined by the SOF segment.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FrameInfo {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) bits_per_sample: u8,
+    pub(crate) component_count: u8,
+}
+
+impl FrameInfo {
+    /// Creates the frame info of an image, for example to describe the image to encode.
+    ///
+    /// ```
+    /// use charls_rs::{FrameInfo, Height, Width};
+    ///
+    /// let frame_info = FrameInfo::new(Width(640), Height(480), 8, 3);
+    /// assert_eq!((frame_info.width(), frame_info.height()), (640, 480));
+    /// ```
+    ///
+    /// Swapping the width and the height doesn't compile:
+    ///
+    /// ```compile_fail
+    /// use charls_rs::{FrameInfo, Height, Width};
+    ///
+    /// let frame_info = FrameInfo::new(Height(480), Width(640), 8, 3);
+    /// ```
+    pub fn new(width: Width, height: Height, bits_per_sample: u8, component_count: u8) -> FrameInfo {
+        FrameInfo {
+            width: width.0,
+            height: height.0,
+            bits_per_sample,
+            component_count,
+        }
+    }
+
+    /// Width of the image in samples per line.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the image in lines.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Number of bits per sample (2..=16).
+    pub fn bits_per_sample(&self) -> u8 {
+        self.bits_per_sample
+    }
+
+    /// Number of components (color channels) in the image.
+    pub fn component_count(&self) -> u8 {
+        self.component_count
+    }
+
+    /// Number of bytes used to store a decoded sample: 1 for up to 8 bits per sample, 2 otherwise.
+    pub fn bytes_per_sample(&self) -> usize {
+        (self.bits_per_sample as usize).div_ceil(8)
+    }
+
+    /// Number of bytes used to store the samples of all components of a decoded pixel.
+    pub fn bytes_per_pixel(&self) -> usize {
+        self.bytes_per_sample() * self.component_count as usize
+    }
+
+    /// Number of samples of all components of the image: width * height * component count. The
+    /// product can only exceed u64 when width * height exceeds 2^56, in which case the result
+    /// saturates at u64::MAX.
+    pub fn sample_count(&self) -> u64 {
+        (self.width as u64 * self.height as u64).saturating_mul(self.component_count as u64)
+    }
+}
+
+/// Formats a summary of the frame, for example "640x480, 8-bit, 3 components, 1 byte per sample".
+impl fmt::Display for FrameInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let plural = |count: usize| if count == 1 { "" } else { "s" };
+        write!(f, "{}x{}, {}-bit, {} component{}, {} byte{} per sample", self.width, self.height,
+               self.bits_per_sample, self.component_count, plural(self.component_count as usize),
+               self.bytes_per_sample(), plural(self.bytes_per_sample()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_infos_with_same_values_are_equal() {
+        let frame_info = FrameInfo::new(Width(640), Height(480), 8, 3);
+
+        assert_eq!(frame_info, FrameInfo::new(Width(640), Height(480), 8, 3));
+        assert_eq!(frame_info.clone(), frame_info);
+        assert_ne!(frame_info, FrameInfo::new(Width(640), Height(480), 8, 1));
+        assert_ne!(frame_info, FrameInfo::new(Width(480), Height(640), 8, 3));
+    }
+
+    #[test]
+    fn bytes_per_sample_and_pixel() {
+        let frame_info = FrameInfo::new(Width(640), Height(480), 8, 3);
+        assert_eq!(frame_info.bytes_per_sample(), 1);
+        assert_eq!(frame_info.bytes_per_pixel(), 3);
+
+        let frame_info = FrameInfo::new(Width(640), Height(480), 12, 1);
+        assert_eq!(frame_info.bytes_per_sample(), 2);
+        assert_eq!(frame_info.bytes_per_pixel(), 2);
+    }
+
+    #[test]
+    fn sample_count_of_small_frame() {
+        assert_eq!(FrameInfo::new(Width(640), Height(480), 8, 3).sample_count(), 640 * 480 * 3);
+    }
+
+    #[test]
+    fn sample_count_of_frame_larger_than_u32() {
+        let frame_info = FrameInfo::new(Width(65535), Height(65535), 16, 4);
+
+        assert_eq!(frame_info.sample_count(), 17_179_344_900);
+        assert!(frame_info.sample_count() > u32::MAX as u64);
+    }
+
+    #[test]
+    fn sample_count_of_frame_larger_than_u64_saturates() {
+        assert_eq!(FrameInfo::new(Width(u32::MAX), Height(u32::MAX), 8, 1).sample_count(),
+                   (u32::MAX as u64).pow(2));
+        assert_eq!(FrameInfo::new(Width(u32::MAX), Height(u32::MAX), 8, 2).sample_count(), u64::MAX);
+    }
+
+    #[test]
+    fn display_frame_info() {
+        assert_eq!(FrameInfo::new(Width(640), Height(480), 8, 3).to_string(),
+                   "640x480, 8-bit, 3 components, 1 byte per sample");
+        assert_eq!(FrameInfo::new(Width(512), Height(256), 12, 1).to_string(),
+                   "512x256, 12-bit, 1 component, 2 bytes per sample");
+    }
+
+    #[test]
+    fn width_and_height_from_u32() {
+        let width: Width = 640.into();
+        let height = Height::from(480);
+
+        let frame_info = FrameInfo::new(width, height, 8, 1);
+
+        assert_eq!(frame_info.width(), 640);
+        assert_eq!(frame_info.height(), 480);
+    }
+}
@@ -0,0 +1,180 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use crate::decoding_error::DecodingError;
+use crate::frame_info::FrameInfo;
+use crate::interleave_mode::InterleaveMode;
+use crate::jpeg_marker_code::JpegMarkerCode;
+use crate::jpeg_stream_reader::{JpegStreamReader, JPEG_MARKER_START_BYTE};
+use crate::spiff_header::SpiffHeader;
+
+/// Describes a JPEG-LS image as defined by the segments before its first scan.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HeaderInfo {
+    pub(crate) frame_info: FrameInfo,
+    pub(crate) interleave_mode: InterleaveMode,
+    pub(crate) near_lossless: u8,
+    pub(crate) spiff_header: Option<SpiffHeader>,
+}
+
+impl HeaderInfo {
+    /// The frame info of the SOF segment.
+    pub fn frame_info(&self) -> &FrameInfo {
+        &self.frame_info
+    }
+
+    /// The ILV parameter of the first scan.
+    pub fn interleave_mode(&self) -> InterleaveMode {
+        self.interleave_mode
+    }
+
+    /// The NEAR parameter of the first scan.
+    pub fn near_lossless(&self) -> u8 {
+        self.near_lossless
+    }
+
+    /// The SPIFF header, if the stream has one.
+    pub fn spiff_header(&self) -> Option<&SpiffHeader> {
+        self.spiff_header.as_ref()
+    }
+}
+
+/// Reads the segments of a JPEG-LS stream up to the first scan and returns the properties of the
+/// image, without decoding the samples.
+pub fn read_header(data: &[u8]) -> Result<HeaderInfo, DecodingError> {
+    let mut reader = JpegStreamReader::new(data);
+    reader.read_header()?;
+
+    Ok(HeaderInfo {
+        frame_info: reader.frame_info().clone(),
+        interleave_mode: reader.interleave_mode(),
+        near_lossless: reader.near_lossless(),
+        spiff_header: reader.spiff_header().cloned(),
+    })
+}
+
+/// Returns true when data starts with a JPEG-LS stream: the SOI marker, followed by APPn
+/// (including a SPIFF header), COM and LSE segments and the SOF segment of JPEG-LS (SOF_55, or
+/// SOF_57 of ISO/IEC 14495-2). Only the markers and segment sizes are checked, not the content
+/// of the segments, which makes it a cheap test to distinguish JPEG-LS from other JPEG formats.
+pub fn is_jpegls(data: &[u8]) -> bool {
+    if data.len() < 2 || data[..2] != [JPEG_MARKER_START_BYTE, JpegMarkerCode::StartOfImage as u8] {
+        return false;
+    }
+
+    let mut position = 2;
+    loop {
+        if data.get(position) != Some(&JPEG_MARKER_START_BYTE) {
+            return false;
+        }
+
+        // A marker may be preceded by fill bytes (0xFF).
+        while data.get(position) == Some(&JPEG_MARKER_START_BYTE) {
+            position += 1;
+        }
+
+        let Some(&marker_code) = data.get(position) else {
+            return false;
+        };
+        match JpegMarkerCode::try_from(marker_code) {
+            Ok(JpegMarkerCode::StartOfFrameJpegls | JpegMarkerCode::StartOfFrameJpeglsExtended) => return true,
+            Ok(JpegMarkerCode::ApplicationData0 |
+               JpegMarkerCode::ApplicationData1 |
+               JpegMarkerCode::ApplicationData2 |
+               JpegMarkerCode::ApplicationData3 |
+               JpegMarkerCode::ApplicationData4 |
+               JpegMarkerCode::ApplicationData5 |
+               JpegMarkerCode::ApplicationData6 |
+               JpegMarkerCode::ApplicationData7 |
+               JpegMarkerCode::ApplicationData8 |
+               JpegMarkerCode::ApplicationData9 |
+               JpegMarkerCode::ApplicationData10 |
+               JpegMarkerCode::ApplicationData11 |
+               JpegMarkerCode::ApplicationData12 |
+               JpegMarkerCode::ApplicationData13 |
+               JpegMarkerCode::ApplicationData14 |
+               JpegMarkerCode::ApplicationData15 |
+               JpegMarkerCode::Comment |
+               JpegMarkerCode::JpeglsPresetParameters) => {
+                let Some(size) = data.get(position + 1..position + 3) else {
+                    return false;
+                };
+                let segment_size = u16::from_be_bytes([size[0], size[1]]) as usize;
+                if segment_size < 2 {
+                    return false;
+                }
+
+                position += 1 + segment_size;
+            }
+            _ => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::Encoder;
+    use crate::frame_info::{Height, Width};
+
+    #[test]
+    fn read_header_of_encoded_stream() {
+        let frame_info = FrameInfo::new(Width(16), Height(8), 12, 3);
+        let spiff_header = SpiffHeader::new(&frame_info, 10);
+        let mut encoder = Encoder::new(frame_info.clone());
+        encoder.set_interleave_mode(InterleaveMode::Line);
+        encoder.set_near_lossless(2);
+        encoder.write_spiff_header(spiff_header.clone()).unwrap();
+        let encoded = encoder.encode(&vec![0; encoder.source_size()]).unwrap();
+
+        let header_info = read_header(&encoded).unwrap();
+
+        assert_eq!(header_info.frame_info(), &frame_info);
+        assert_eq!(header_info.interleave_mode(), InterleaveMode::Line);
+        assert_eq!(header_info.near_lossless(), 2);
+        assert_eq!(header_info.spiff_header(), Some(&spiff_header));
+    }
+
+    #[test]
+    fn read_header_of_truncated_stream_fails() {
+        let error = read_header(&[0xFF, 0xD8, 0xFF]).unwrap_err();
+
+        assert_eq!(error, DecodingError::UnexpectedEndOfStream);
+    }
+
+    #[test]
+    fn is_jpegls_of_jpegls_streams() {
+        let frame_info = FrameInfo::new(Width(4), Height(4), 8, 1);
+        let mut encoder = Encoder::new(frame_info.clone());
+        let encoded = encoder.encode(&[0; 16]).unwrap();
+        encoder.write_spiff_header(SpiffHeader::new(&frame_info, 10)).unwrap();
+        let encoded_with_spiff_header = encoder.encode(&[0; 16]).unwrap();
+
+        assert!(is_jpegls(&encoded));
+        assert!(is_jpegls(&encoded_with_spiff_header));
+        assert!(is_jpegls(&encoded[..4]));
+    }
+
+    #[test]
+    fn is_jpegls_of_baseline_jpeg_stream_is_false() {
+        // SOI, an APP0 segment with a JFIF header and the start of an SOF_0 segment.
+        let jpeg = [
+            0xFF, 0xD8,
+            0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F', 0, 1, 1, 0, 0, 1, 0, 1, 0, 0,
+            0xFF, 0xC0, 0x00, 0x0B, 8, 0, 16, 0, 16, 1, 1, 0x11, 0,
+        ];
+
+        assert!(!is_jpegls(&jpeg));
+    }
+
+    #[test]
+    fn is_jpegls_of_other_data_is_false() {
+        let random: Vec<u8> = (0..64u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+        let jpeg_2000 = [0xFF, 0x4F, 0xFF, 0x51, 0x00, 0x2F];
+
+        assert!(!is_jpegls(&random));
+        assert!(!is_jpegls(&jpeg_2000));
+        assert!(!is_jpegls(&[]));
+        assert!(!is_jpegls(&[0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, b'J']));
+    }
+}
@@ -0,0 +1,100 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use std::convert::TryFrom;
+
+use crate::frame_info::FrameInfo;
+
+/// Defines how the components of a multi-component image are ordered in the encoded scans.
+/// (see ISO/IEC 14495-1, ILV parameter of the SOS segment)
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InterleaveMode {
+    None = 0,   // Each component is encoded in its own scan (planar).
+    Line = 1,   // The components are interleaved line by line in a single scan.
+    Sample = 2, // The components are interleaved sample by sample in a single scan.
+}
+
+impl TryFrom<u8> for InterleaveMode {
+    type Error = ();
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        match v {
+            x if x == InterleaveMode::None as u8 => Ok(InterleaveMode::None),
+            x if x == InterleaveMode::Line as u8 => Ok(InterleaveMode::Line),
+            x if x == InterleaveMode::Sample as u8 => Ok(InterleaveMode::Sample),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Rearranges decoded samples from the layout of interleave mode from to the layout of
+/// interleave mode to. Images with interleave mode None are stored component by component
+/// (planar), images with interleave mode Line or Sample are stored pixel interleaved.
+///
+/// # Panics
+///
+/// Panics if src is smaller than the size of the image described by frame_info.
+pub fn reinterleave(src: &[u8], frame_info: &FrameInfo, from: InterleaveMode, to: InterleaveMode) -> Vec<u8> {
+    let bytes_per_sample = frame_info.bytes_per_sample();
+    let component_count = frame_info.component_count() as usize;
+    let pixel_count = frame_info.width() as usize * frame_info.height() as usize;
+    let src = &src[..pixel_count * component_count * bytes_per_sample];
+
+    let from_planar = from == InterleaveMode::None;
+    if from_planar == (to == InterleaveMode::None) || component_count == 1 {
+        return src.to_vec();
+    }
+
+    let mut destination = vec![0; src.len()];
+    for component in 0..component_count {
+        for pixel in 0..pixel_count {
+            let planar = (component * pixel_count + pixel) * bytes_per_sample;
+            let interleaved = (pixel * component_count + component) * bytes_per_sample;
+            let (source_index, destination_index) = if from_planar {
+                (planar, interleaved)
+            } else {
+                (interleaved, planar)
+            };
+            destination[destination_index..destination_index + bytes_per_sample]
+                .copy_from_slice(&src[source_index..source_index + bytes_per_sample]);
+        }
+    }
+
+    destination
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame_info::{Height, Width};
+
+    #[test]
+    fn reinterleave_3_component_image_round_trip() {
+        let frame_info = FrameInfo::new(Width(3), Height(2), 8, 3);
+        let interleaved: Vec<u8> = (0..18).collect();
+
+        let planar = reinterleave(&interleaved, &frame_info, InterleaveMode::Sample, InterleaveMode::None);
+
+        assert_eq!(planar, [0, 3, 6, 9, 12, 15, 1, 4, 7, 10, 13, 16, 2, 5, 8, 11, 14, 17]);
+        assert_eq!(reinterleave(&planar, &frame_info, InterleaveMode::None, InterleaveMode::Line), interleaved);
+    }
+
+    #[test]
+    fn reinterleave_16_bit_samples_keeps_sample_bytes_together() {
+        let frame_info = FrameInfo::new(Width(2), Height(1), 16, 3);
+        let interleaved = [0x10, 0x11, 0x20, 0x21, 0x30, 0x31, 0x40, 0x41, 0x50, 0x51, 0x60, 0x61];
+
+        let planar = reinterleave(&interleaved, &frame_info, InterleaveMode::Line, InterleaveMode::None);
+
+        assert_eq!(planar, [0x10, 0x11, 0x40, 0x41, 0x20, 0x21, 0x50, 0x51, 0x30, 0x31, 0x60, 0x61]);
+        assert_eq!(reinterleave(&planar, &frame_info, InterleaveMode::None, InterleaveMode::Sample), interleaved);
+    }
+
+    #[test]
+    fn reinterleave_between_line_and_sample_keeps_layout() {
+        let frame_info = FrameInfo::new(Width(2), Height(2), 8, 3);
+        let interleaved: Vec<u8> = (0..12).collect();
+
+        assert_eq!(reinterleave(&interleaved, &frame_info, InterleaveMode::Line, InterleaveMode::Sample), interleaved);
+    }
+}
@@ -3,7 +3,8 @@
 
 use std::convert::TryFrom;
 
-#[derive(Debug, Eq, PartialEq)]
+/// The JPEG markers that can appear in a JPEG-LS stream (see ISO/IEC 10918-1, B.1.1.3 and ISO/IEC 14495-1, C.1.1).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum JpegMarkerCode {
     StartOfImage = 0xD8,          // SOI: Marks the start of an image.
     EndOfImage = 0xD9,            // EOI: Marks the end of an image.
@@ -56,7 +57,120 @@ impl TryFrom<u8> for JpegMarkerCode {
             x if x == JpegMarkerCode::StartOfImage as u8 => Ok(JpegMarkerCode::StartOfImage),
             x if x == JpegMarkerCode::EndOfImage as u8 => Ok(JpegMarkerCode::EndOfImage),
             x if x == JpegMarkerCode::StartOfScan as u8 => Ok(JpegMarkerCode::StartOfScan),
+            x if x == JpegMarkerCode::DefineRestartInterval as u8 => {
+                Ok(JpegMarkerCode::DefineRestartInterval)
+            }
+            x if x == JpegMarkerCode::StartOfFrameBaselineJpeg as u8 => {
+                Ok(JpegMarkerCode::StartOfFrameBaselineJpeg)
+            }
+            x if x == JpegMarkerCode::StartOfFrameExtendedSequential as u8 => {
+                Ok(JpegMarkerCode::StartOfFrameExtendedSequential)
+            }
+            x if x == JpegMarkerCode::StartOfFrameProgressive as u8 => {
+                Ok(JpegMarkerCode::StartOfFrameProgressive)
+            }
+            x if x == JpegMarkerCode::StartOfFrameLossless as u8 => {
+                Ok(JpegMarkerCode::StartOfFrameLossless)
+            }
+            x if x == JpegMarkerCode::StartOfFrameDifferentialSequential as u8 => {
+                Ok(JpegMarkerCode::StartOfFrameDifferentialSequential)
+            }
+            x if x == JpegMarkerCode::StartOfFrameDifferentialProgressive as u8 => {
+                Ok(JpegMarkerCode::StartOfFrameDifferentialProgressive)
+            }
+            x if x == JpegMarkerCode::StartOfFrameDifferentialLossless as u8 => {
+                Ok(JpegMarkerCode::StartOfFrameDifferentialLossless)
+            }
+            x if x == JpegMarkerCode::StartOfFrameExtendedArithmetic as u8 => {
+                Ok(JpegMarkerCode::StartOfFrameExtendedArithmetic)
+            }
+            x if x == JpegMarkerCode::StartOfFrameProgressiveArithmetic as u8 => {
+                Ok(JpegMarkerCode::StartOfFrameProgressiveArithmetic)
+            }
+            x if x == JpegMarkerCode::StartOfFrameLosslessArithmetic as u8 => {
+                Ok(JpegMarkerCode::StartOfFrameLosslessArithmetic)
+            }
+            x if x == JpegMarkerCode::StartOfFrameJpegls as u8 => {
+                Ok(JpegMarkerCode::StartOfFrameJpegls)
+            }
+            x if x == JpegMarkerCode::JpeglsPresetParameters as u8 => {
+                Ok(JpegMarkerCode::JpeglsPresetParameters)
+            }
+            x if x == JpegMarkerCode::StartOfFrameJpeglsExtended as u8 => {
+                Ok(JpegMarkerCode::StartOfFrameJpeglsExtended)
+            }
+            x if x == JpegMarkerCode::ApplicationData0 as u8 => {
+                Ok(JpegMarkerCode::ApplicationData0)
+            }
+            x if x == JpegMarkerCode::ApplicationData1 as u8 => {
+                Ok(JpegMarkerCode::ApplicationData1)
+            }
+            x if x == JpegMarkerCode::ApplicationData2 as u8 => {
+                Ok(JpegMarkerCode::ApplicationData2)
+            }
+            x if x == JpegMarkerCode::ApplicationData3 as u8 => {
+                Ok(JpegMarkerCode::ApplicationData3)
+            }
+            x if x == JpegMarkerCode::ApplicationData4 as u8 => {
+                Ok(JpegMarkerCode::ApplicationData4)
+            }
+            x if x == JpegMarkerCode::ApplicationData5 as u8 => {
+                Ok(JpegMarkerCode::ApplicationData5)
+            }
+            x if x == JpegMarkerCode::ApplicationData6 as u8 => {
+                Ok(JpegMarkerCode::ApplicationData6)
+            }
+            x if x == JpegMarkerCode::ApplicationData7 as u8 => {
+                Ok(JpegMarkerCode::ApplicationData7)
+            }
+            x if x == JpegMarkerCode::ApplicationData8 as u8 => {
+                Ok(JpegMarkerCode::ApplicationData8)
+            }
+            x if x == JpegMarkerCode::ApplicationData9 as u8 => {
+                Ok(JpegMarkerCode::ApplicationData9)
+            }
+            x if x == JpegMarkerCode::ApplicationData10 as u8 => {
+                Ok(JpegMarkerCode::ApplicationData10)
+            }
+            x if x == JpegMarkerCode::ApplicationData11 as u8 => {
+                Ok(JpegMarkerCode::ApplicationData11)
+            }
+            x if x == JpegMarkerCode::ApplicationData12 as u8 => {
+                Ok(JpegMarkerCode::ApplicationData12)
+            }
+            x if x == JpegMarkerCode::ApplicationData13 as u8 => {
+                Ok(JpegMarkerCode::ApplicationData13)
+            }
+            x if x == JpegMarkerCode::ApplicationData14 as u8 => {
+                Ok(JpegMarkerCode::ApplicationData14)
+            }
+            x if x == JpegMarkerCode::ApplicationData15 as u8 => {
+                Ok(JpegMarkerCode::ApplicationData15)
+            }
+            x if x == JpegMarkerCode::Comment as u8 => Ok(JpegMarkerCode::Comment),
             _ => Err(()),
         }
     }
 }
+
+impl JpegMarkerCode {
+    /// Returns true when the marker starts a segment with a length field (APPn, COM, SOF, SOS, LSE,
+    /// ...). SOI and EOI are the only stand-alone markers of this enum (RSTm are not supported).
+    pub fn segment_has_length(&self) -> bool {
+        *self != JpegMarkerCode::StartOfImage && *self != JpegMarkerCode::EndOfImage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segment_has_length_of_stand_alone_and_segment_markers() {
+        assert!(!JpegMarkerCode::StartOfImage.segment_has_length());
+        assert!(!JpegMarkerCode::EndOfImage.segment_has_length());
+        assert!(JpegMarkerCode::ApplicationData0.segment_has_length());
+        assert!(JpegMarkerCode::StartOfScan.segment_has_length());
+        assert!(JpegMarkerCode::JpeglsPresetParameters.segment_has_length());
+    }
+}
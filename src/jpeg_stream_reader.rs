@@ -1,156 +1,1599 @@
 // Copyright (c) Team CharLS.
 // SPDX-License-Identifier: BSD-3-Clause
 
-//mod jpeg_marker_code;
+use std::io::{ErrorKind, Read};
 
-use std::io::Read;
+use crate::coding_variant::CodingVariant;
+use crate::color_transformation::ColorTransformation;
+use crate::constants::{maximum_sample_value, MAXIMUM_BITS_PER_SAMPLE, MAXIMUM_COMPONENT_COUNT_IN_SCAN,
+                       MINIMUM_BITS_PER_SAMPLE};
+use crate::crc32::{finish_crc32, update_crc32, CRC32_INITIAL_VALUE};
+use crate::decoding_error::DecodingError;
+use crate::frame_info::FrameInfo;
+use crate::interleave_mode::InterleaveMode;
+use crate::jpeg_marker_code::JpegMarkerCode;
+use crate::jpegls_preset_coding_parameters::{compute_maximum_near_lossless, JpeglsPcParameters};
+use crate::mapping_table::MappingTable;
+use crate::reader_state_public::ReaderStatePublic;
+use crate::scan_info::ScanInfo;
+use crate::spiff_header::{SpiffHeader, SPIFF_END_OF_DIRECTORY_ENTRY_TYPE, SPIFF_MAGIC_ID};
+
+pub(crate) const JPEG_MARKER_START_BYTE: u8 = 0xFF;
+
+const READ_BUFFER_SIZE: usize = 4096;
+
+#[derive(Debug, Eq, PartialEq)]
+enum ReaderState
+{
+    BeforeStartOfImage,
+    HeaderSection,
+    SpiffHeaderSection,
+    FrameSection,
+    ScanSection,
+    BitStreamSection,
+    AfterEndOfImage,
+}
+
+
+#[derive(Debug)]
+pub struct JpegStreamReader<R: Read> {
+    reader: R,
+    buffer: Vec<u8>,
+    buffer_position: usize,
+    position: u64,
+    bitstream_offset: Option<u64>,
+    frame_info: FrameInfo,
+    coding_variant: CodingVariant,
+    state: ReaderState,
+    scan_info: Option<ScanInfo>,
+    color_transformation: Option<ColorTransformation>,
+    preset_coding_parameters: JpeglsPcParameters,
+    spiff_header: Option<SpiffHeader>,
+    strict_mode: bool,
+    scan_for_soi: bool,
+    scanned_component_count: usize,
+    mapping_tables: Vec<MappingTable>,
+    table_set_ids: Vec<u8>,
+    recording: Option<Vec<u8>>,
+    component_ids: Vec<u8>,
+    bytes_read: u64,
+    byte_limit: Option<u64>,
+    payload_crc: Option<u32>,
+    start_of_image_position: u64,
+    application_data: Vec<(u8, Option<Vec<u8>>)>,
+    maximum_segment_size: Option<usize>,
+}
+
+
+impl<R: Read> JpegStreamReader<R> {
+    pub fn new(r: R) -> JpegStreamReader<R> {
+        let width = 0;
+        let height = 0;
+        let bits_per_sample = 0;
+        let component_count = 0;
+
+        JpegStreamReader {
+            reader: r,
+            buffer: Vec::new(),
+            buffer_position: 0,
+            position: 0,
+            bitstream_offset: None,
+            frame_info: FrameInfo {
+                width,
+                height,
+                bits_per_sample,
+                component_count,
+            },
+            coding_variant: CodingVariant::Baseline,
+            state: ReaderState::BeforeStartOfImage,
+            scan_info: None,
+            color_transformation: None,
+            preset_coding_parameters: JpeglsPcParameters::default(),
+            spiff_header: None,
+            strict_mode: true,
+            scan_for_soi: false,
+            scanned_component_count: 0,
+            mapping_tables: Vec::new(),
+            table_set_ids: Vec::new(),
+            recording: None,
+            component_ids: Vec::new(),
+            bytes_read: 0,
+            byte_limit: None,
+            payload_crc: None,
+            start_of_image_position: 0,
+            application_data: Vec::new(),
+            maximum_segment_size: None,
+        }
+    }
+
+    pub fn frame_info(&self) -> &FrameInfo {
+        &self.frame_info
+    }
+
+    pub fn coding_variant(&self) -> CodingVariant {
+        self.coding_variant
+    }
+
+    pub fn spiff_header(&self) -> Option<&SpiffHeader> {
+        self.spiff_header.as_ref()
+    }
+
+    /// Limits the number of bytes that are read from the underlying reader: reading beyond
+    /// byte_limit fails with InputTooLarge.
+    pub(crate) fn set_byte_limit(&mut self, byte_limit: u64) {
+        self.byte_limit = Some(byte_limit);
+    }
+
+    /// Starts or stops computing the CRC-32 of the entropy coded data of the scans that are read next.
+    pub(crate) fn set_compute_payload_hash(&mut self, compute_payload_hash: bool) {
+        self.payload_crc = compute_payload_hash.then_some(self.payload_crc.unwrap_or(CRC32_INITIAL_VALUE));
+    }
+
+    /// The CRC-32 of the entropy coded data of all scans, once the EOI marker has been read.
+    pub(crate) fn payload_hash(&self) -> Option<u32> {
+        match self.state {
+            ReaderState::AfterEndOfImage => self.payload_crc.map(finish_crc32),
+            _ => None,
+        }
+    }
+
+    /// Captures the payload of the first APPn segment with the given n (0 to 15) that is read.
+    pub(crate) fn set_capture_application_data(&mut self, n: u8) {
+        if !self.application_data.iter().any(|(captured_n, _)| *captured_n == n) {
+            self.application_data.push((n, None));
+        }
+    }
+
+    /// The captured payload of the first APPn segment, None when it hasn't been read or captured.
+    pub(crate) fn application_data(&self, n: u8) -> Option<&[u8]> {
+        self.application_data.iter().find(|(captured_n, _)| *captured_n == n)?.1.as_deref()
+    }
+
+    /// Limits the payload size of COM and APPn segments: larger segments are skipped without
+    /// capturing or parsing them in lenient mode and fail with InvalidMarkerSegmentSize in strict mode.
+    pub(crate) fn set_maximum_segment_size(&mut self, maximum_segment_size: usize) {
+        self.maximum_segment_size = Some(maximum_segment_size);
+    }
+
+    /// In strict mode (the default) inconsistencies between the optional parts of the stream
+    /// and the mandatory segments and reserved values are reported as errors. In lenient mode the
+    /// values of the mandatory segments are used and reserved values are skipped.
+    pub(crate) fn set_strict_mode(&mut self, strict_mode: bool) {
+        self.strict_mode = strict_mode;
+    }
+
+    /// When enabled in lenient mode, the bytes before the SOI marker are skipped.
+    pub(crate) fn set_scan_for_soi(&mut self, scan_for_soi: bool) {
+        self.scan_for_soi = scan_for_soi;
+    }
+
+    /// The parameters of the last SOS segment that has been read.
+    pub fn scan_info(&self) -> Option<&ScanInfo> {
+        self.scan_info.as_ref()
+    }
+
+    pub fn mapping_table(&self, table_id: u8) -> Option<&MappingTable> {
+        self.mapping_tables.iter().find(|table| table.table_id == table_id)
+    }
+
+    /// Uses the mapping tables of a tables-only stream for the stream that is read next. A table
+    /// that this stream defines again replaces the table with the same ID.
+    pub(crate) fn set_mapping_tables(&mut self, mapping_tables: Vec<MappingTable>) {
+        self.table_set_ids = mapping_tables.iter().map(|table| table.table_id).collect();
+        self.mapping_tables = mapping_tables;
+    }
+
+    /// Returns the mapping tables that have been read.
+    pub(crate) fn take_mapping_tables(&mut self) -> Vec<MappingTable> {
+        std::mem::take(&mut self.mapping_tables)
+    }
+
+    pub(crate) fn state(&self) -> ReaderStatePublic {
+        match self.state {
+            ReaderState::BeforeStartOfImage => ReaderStatePublic::BeforeStartOfImage,
+            ReaderState::HeaderSection => ReaderStatePublic::HeaderSection,
+            ReaderState::SpiffHeaderSection => ReaderStatePublic::SpiffHeaderSection,
+            ReaderState::FrameSection => ReaderStatePublic::FrameSection,
+            ReaderState::ScanSection => ReaderStatePublic::ScanSection,
+            ReaderState::BitStreamSection => ReaderStatePublic::BitStreamSection,
+            ReaderState::AfterEndOfImage => ReaderStatePublic::AfterEndOfImage,
+        }
+    }
+
+    /// MAXVAL: the maximum sample value of the LSE segment, or 2^bits_per_sample - 1 when the
+    /// stream doesn't define it.
+    pub(crate) fn maximum_sample_value(&self) -> i32 {
+        match self.preset_coding_parameters.maximum_sample_value {
+            0 => self.maximum_component_value(),
+            value => value,
+        }
+    }
+
+    pub fn near_lossless(&self) -> u8 {
+        self.scan_info.as_ref().map_or(0, |scan_info| scan_info.near_lossless)
+    }
+
+    pub fn interleave_mode(&self) -> InterleaveMode {
+        self.scan_info.as_ref().map_or(InterleaveMode::None, |scan_info| scan_info.interleave_mode)
+    }
+
+    /// The offset in bytes from the start of the stream to the next byte that will be read.
+    pub(crate) fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// The number of bytes from the SOI marker up to and including the EOI marker, once the EOI
+    /// marker has been read. Bytes before the SOI marker (see set_scan_for_soi) aren't counted.
+    pub(crate) fn compressed_size(&self) -> Option<u64> {
+        match self.state {
+            ReaderState::AfterEndOfImage => Some(self.position - self.start_of_image_position),
+            _ => None,
+        }
+    }
+
+    /// The component identifiers (Ci) of the frame, in the order of the SOF segment.
+    pub(crate) fn component_ids(&self) -> &[u8] {
+        &self.component_ids
+    }
+
+    /// Starts recording the bytes that are read, from the start of the stream. Returns false when
+    /// the bytes read so far are no longer buffered.
+    pub(crate) fn start_recording(&mut self) -> bool {
+        if self.position != self.buffer_position as u64 {
+            return false;
+        }
+
+        self.recording = Some(Vec::new());
+        true
+    }
+
+    /// Stops recording and returns all bytes read since the start of the stream.
+    pub(crate) fn take_recording(&mut self) -> Option<Vec<u8>> {
+        let mut recording = self.recording.take()?;
+        recording.extend_from_slice(&self.buffer[..self.buffer_position]);
+        Some(recording)
+    }
+
+    /// The offset in bytes from the start of the stream to the entropy coded data of the current scan.
+    pub fn bitstream_offset(&self) -> Option<u64> {
+        self.bitstream_offset
+    }
+
+    pub fn color_transformation(&self) -> ColorTransformation {
+        self.color_transformation.unwrap_or(ColorTransformation::None)
+    }
+
+    pub fn read_next_marker_code(&mut self) -> Result<JpegMarkerCode, DecodingError> {
+        let mut value = self.read_u8()?;
+        if value != JPEG_MARKER_START_BYTE {
+            return Err(DecodingError::JpegMarkerStartByteNotFound);
+        }
+
+        // Read all preceding 0xFF fill values until a non 0xFF value has been found. (see ISO/IEC 10918-1, B.1.1.2)
+        while value == JPEG_MARKER_START_BYTE {
+            value = self.read_u8()?;
+        }
+
+        JpegMarkerCode::try_from(value).map_err(|_| DecodingError::UnknownJpegMarkerFound)
+    }
+
+    /// Reads the header up to and including the first SOS segment.
+    /// After this call the reader is positioned at the start of the entropy coded data.
+    /// The SOI marker and the SOF and SOS segments are mandatory, the SPIFF header and the COM,
+    /// APPn and LSE segments are optional.
+    pub fn read_header(&mut self) -> Result<(), DecodingError> {
+        self.read_start_of_image()?;
+        self.read_until_bit_stream_section()
+    }
+
+    /// Reads a tables-only stream (the abbreviated format for table specification of ISO/IEC
+    /// 10918-1, B.5): the SOI marker, LSE, COM and APPn segments and the EOI marker, without a frame.
+    pub(crate) fn read_tables(&mut self) -> Result<(), DecodingError> {
+        self.read_start_of_image()?;
+        loop {
+            match self.read_next_marker_code()? {
+                JpegMarkerCode::EndOfImage => {
+                    self.state = ReaderState::AfterEndOfImage;
+                    return Ok(());
+                }
+
+                JpegMarkerCode::StartOfFrameJpegls |
+                JpegMarkerCode::StartOfFrameJpeglsExtended |
+                JpegMarkerCode::StartOfScan => return Err(DecodingError::UnexpectedMarkerFound),
+
+                marker_code => self.read_marker_segment(marker_code)?,
+            }
+        }
+    }
+
+    /// Reads the SOI marker and, if it directly follows, the APP8 segment with the SPIFF header.
+    /// A later call to read_header continues with the segments that follow.
+    pub(crate) fn read_spiff_header(&mut self) -> Result<(), DecodingError> {
+        self.read_start_of_image()?;
+        if self.state != ReaderState::HeaderSection || self.spiff_header.is_some() {
+            return Ok(());
+        }
+
+        let mut prefix = [0; 10];
+        for (offset, byte) in prefix.iter_mut().enumerate() {
+            match self.peek_byte(offset)? {
+                Some(value) => *byte = value,
+                None => return Ok(()),
+            }
+        }
+
+        if prefix[..2] == [JPEG_MARKER_START_BYTE, JpegMarkerCode::ApplicationData8 as u8] &&
+            prefix[4..] == SPIFF_MAGIC_ID {
+            let marker_code = self.read_next_marker_code()?;
+            self.read_marker_segment(marker_code)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_start_of_image(&mut self) -> Result<(), DecodingError> {
+        if self.state == ReaderState::BeforeStartOfImage {
+            if self.scan_for_soi && !self.strict_mode {
+                self.skip_to_start_of_image()?;
+            } else if self.read_next_marker_code()? != JpegMarkerCode::StartOfImage {
+                return Err(DecodingError::StartOfImageMarkerNotFound);
+            }
+
+            self.start_of_image_position = self.position - 2;
+            self.state = ReaderState::HeaderSection;
+        }
+
+        Ok(())
+    }
+
+    /// Skips all bytes up to and including the first SOI marker, for streams embedded in a container.
+    fn skip_to_start_of_image(&mut self) -> Result<(), DecodingError> {
+        let mut previous = 0;
+        loop {
+            let value = self.read_u8().map_err(|error| match error {
+                DecodingError::UnexpectedEndOfStream => DecodingError::StartOfImageMarkerNotFound,
+                error => error,
+            })?;
+            if previous == JPEG_MARKER_START_BYTE && value == JpegMarkerCode::StartOfImage as u8 {
+                return Ok(());
+            }
+
+            previous = value;
+        }
+    }
+
+    /// Reads the marker segments that follow a decoded scan, up to and including the next SOS segment.
+    /// An LSE segment between the scans replaces the preset coding parameters for the next scans.
+    pub(crate) fn read_next_start_of_scan(&mut self) -> Result<(), DecodingError> {
+        debug_assert_eq!(self.state, ReaderState::BitStreamSection);
+        self.state = ReaderState::ScanSection;
+        self.read_until_bit_stream_section()
+    }
+
+    /// Reads the EOI marker that must follow the last scan. The scans must have covered
+    /// all components of the frame. In lenient mode the end of the stream is accepted instead of
+    /// the EOI marker.
+    pub(crate) fn read_end_of_image(&mut self) -> Result<(), DecodingError> {
+        debug_assert_eq!(self.state, ReaderState::BitStreamSection);
+        if self.peek_byte(0)?.is_none() {
+            // Some writers omit the EOI marker after the last scan: accepted in lenient mode.
+            if self.strict_mode {
+                return Err(DecodingError::EndOfImageMarkerNotFound);
+            }
+        } else if self.read_next_marker_code()? != JpegMarkerCode::EndOfImage {
+            return Err(DecodingError::EndOfImageMarkerNotFound);
+        }
+
+        if self.scanned_component_count != self.frame_info.component_count as usize {
+            return Err(DecodingError::InvalidParameterComponentCount);
+        }
+
+        self.state = ReaderState::AfterEndOfImage;
+        Ok(())
+    }
+
+    /// Returns the preset coding parameters of the current scan, with all defaults resolved.
+    pub(crate) fn validated_preset_coding_parameters(&self) -> Result<JpeglsPcParameters, DecodingError> {
+        self.preset_coding_parameters
+            .validate(self.maximum_component_value(), self.near_lossless() as i32)
+            .ok_or(DecodingError::InvalidParameterJpeglsPresetParameters)
+    }
+
+    /// Returns the byte at the given offset from the current position without consuming it,
+    /// or None when the end of the stream is reached before that offset.
+    pub(crate) fn peek_byte(&mut self, offset: usize) -> Result<Option<u8>, DecodingError> {
+        if self.fill_buffer(offset + 1)? {
+            Ok(Some(self.buffer[self.buffer_position + offset]))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Consumes a byte previously returned by peek_byte.
+    pub(crate) fn skip_byte(&mut self) {
+        debug_assert!(self.buffer_position < self.buffer.len());
+        // Only the bit reader consumes bytes with skip_byte: they are entropy coded data.
+        if let Some(crc) = &mut self.payload_crc {
+            *crc = update_crc32(*crc, self.buffer[self.buffer_position]);
+        }
+        self.buffer_position += 1;
+        self.position += 1;
+    }
+
+    fn read_until_bit_stream_section(&mut self) -> Result<(), DecodingError> {
+        while self.state != ReaderState::BitStreamSection {
+            let marker_code = self.read_next_marker_code().map_err(|error| match error {
+                // Entropy coded data directly after the frame: the SOS segment is missing.
+                DecodingError::JpegMarkerStartByteNotFound if self.state == ReaderState::FrameSection => {
+                    DecodingError::StartOfScanMarkerNotFound
+                }
+                error => error,
+            })?;
+            if self.state == ReaderState::SpiffHeaderSection {
+                self.read_spiff_directory_entry(marker_code)?;
+            } else {
+                self.read_marker_segment(marker_code)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_marker_segment(&mut self, marker_code: JpegMarkerCode) -> Result<(), DecodingError> {
+        match marker_code {
+            JpegMarkerCode::StartOfFrameJpegls |
+            JpegMarkerCode::StartOfFrameJpeglsExtended => {
+                if self.state != ReaderState::HeaderSection {
+                    return Err(DecodingError::UnexpectedMarkerFound);
+                }
+
+                self.coding_variant = if marker_code == JpegMarkerCode::StartOfFrameJpegls {
+                    CodingVariant::Baseline
+                } else {
+                    CodingVariant::Extended
+                };
+                let segment_size = self.read_segment_size()?;
+                self.read_start_of_frame_segment(segment_size)
+            }
+
+            JpegMarkerCode::StartOfScan => {
+                if self.state == ReaderState::HeaderSection {
+                    return Err(DecodingError::StartOfFrameMarkerNotFound);
+                }
+
+                if self.state != ReaderState::FrameSection && self.state != ReaderState::ScanSection {
+                    return Err(DecodingError::UnexpectedMarkerFound);
+                }
+
+                let segment_size = self.read_segment_size()?;
+                self.read_start_of_scan_segment(segment_size)
+            }
+
+            JpegMarkerCode::JpeglsPresetParameters => {
+                let segment_size = self.read_segment_size()?;
+                self.read_preset_parameters_segment(segment_size)
+            }
+
+            JpegMarkerCode::Comment => {
+                let segment_size = self.read_segment_size()?;
+                self.is_segment_too_large(segment_size)?;
+                self.skip_bytes(segment_size)
+            }
+
+            JpegMarkerCode::ApplicationData8 => {
+                if !self.is_application_data_allowed() {
+                    return Err(DecodingError::UnexpectedMarkerFound);
+                }
+
+                let segment_size = self.read_segment_size()?;
+                if self.is_segment_too_large(segment_size)? {
+                    return self.skip_bytes(segment_size);
+                }
+
+                self.read_application_data8_segment(segment_size)
+            }
+
+            JpegMarkerCode::ApplicationData0 |
+            JpegMarkerCode::ApplicationData1 |
+            JpegMarkerCode::ApplicationData2 |
+            JpegMarkerCode::ApplicationData3 |
+            JpegMarkerCode::ApplicationData4 |
+            JpegMarkerCode::ApplicationData5 |
+            JpegMarkerCode::ApplicationData6 |
+            JpegMarkerCode::ApplicationData7 |
+            JpegMarkerCode::ApplicationData9 |
+            JpegMarkerCode::ApplicationData10 |
+            JpegMarkerCode::ApplicationData11 |
+            JpegMarkerCode::ApplicationData12 |
+            JpegMarkerCode::ApplicationData13 |
+            JpegMarkerCode::ApplicationData14 |
+            JpegMarkerCode::ApplicationData15 => {
+                if !self.is_application_data_allowed() {
+                    return Err(DecodingError::UnexpectedMarkerFound);
+                }
+
+                let segment_size = self.read_segment_size()?;
+                let n = marker_code as u8 - JpegMarkerCode::ApplicationData0 as u8;
+                if !self.is_segment_too_large(segment_size)? && self.is_application_data_captured(n) {
+                    let segment_data = self.read_bytes(segment_size)?;
+                    self.capture_application_data(n, segment_data);
+                    Ok(())
+                } else {
+                    self.skip_bytes(segment_size)
+                }
+            }
+
+            JpegMarkerCode::StartOfFrameBaselineJpeg |
+            JpegMarkerCode::StartOfFrameExtendedSequential |
+            JpegMarkerCode::StartOfFrameProgressive |
+            JpegMarkerCode::StartOfFrameLossless |
+            JpegMarkerCode::StartOfFrameDifferentialSequential |
+            JpegMarkerCode::StartOfFrameDifferentialProgressive |
+            JpegMarkerCode::StartOfFrameDifferentialLossless |
+            JpegMarkerCode::StartOfFrameExtendedArithmetic |
+            JpegMarkerCode::StartOfFrameProgressiveArithmetic |
+            JpegMarkerCode::StartOfFrameLosslessArithmetic |
+            JpegMarkerCode::DefineRestartInterval => Err(DecodingError::EncodingNotSupported),
+
+            // An EOI where the next scan is expected means the frame has components without a scan.
+            JpegMarkerCode::EndOfImage if self.state == ReaderState::ScanSection => {
+                Err(DecodingError::InvalidParameterComponentCount)
+            }
+
+            JpegMarkerCode::EndOfImage if self.state == ReaderState::HeaderSection => {
+                Err(DecodingError::StartOfFrameMarkerNotFound)
+            }
+
+            JpegMarkerCode::EndOfImage if self.state == ReaderState::FrameSection => {
+                Err(DecodingError::StartOfScanMarkerNotFound)
+            }
+
+            JpegMarkerCode::StartOfImage |
+            JpegMarkerCode::EndOfImage => Err(DecodingError::UnexpectedMarkerFound),
+        }
+    }
+
+    /// Reads a mapping table specification, or a continuation that appends entries to a table.
+    fn read_mapping_table(&mut self, continuation: bool, size: usize) -> Result<(), DecodingError> {
+        if size < 2 {
+            return Err(DecodingError::InvalidMarkerSegmentSize);
+        }
+
+        let table_id = self.read_u8()?; // TID = Table identifier
+        let entry_size = self.read_u8()?; // Wt = Width of table entries in bytes
+        if entry_size == 0 || !(size - 2).is_multiple_of(entry_size as usize) {
+            return Err(DecodingError::InvalidMarkerSegmentSize);
+        }
+
+        let data = self.read_bytes(size - 2)?;
+        if !continuation {
+            if let Some(index) = self.table_set_ids.iter().position(|&id| id == table_id) {
+                self.table_set_ids.remove(index);
+                self.mapping_tables.retain(|table| table.table_id != table_id);
+            }
+        }
+
+        let existing = self.mapping_tables.iter_mut().find(|table| table.table_id == table_id);
+        match (continuation, existing) {
+            (false, None) if table_id != 0 => {
+                self.mapping_tables.push(MappingTable { table_id, entry_size, data });
+                Ok(())
+            }
+            (true, Some(table)) if table.entry_size == entry_size => {
+                table.data.extend_from_slice(&data);
+                Ok(())
+            }
+            _ => Err(DecodingError::InvalidParameterMappingTableId),
+        }
+    }
+
+    /// APPn segments are allowed before the SOF segment, and in lenient mode also between the
+    /// SOF and the first SOS segment.
+    fn is_application_data_allowed(&self) -> bool {
+        self.state == ReaderState::HeaderSection || (!self.strict_mode && self.state == ReaderState::FrameSection)
+    }
+
+    /// Returns true when the payload of the next APPn segment with the given n must be captured.
+    fn is_application_data_captured(&self, n: u8) -> bool {
+        self.application_data.iter().any(|(captured_n, data)| *captured_n == n && data.is_none())
+    }
+
+    fn capture_application_data(&mut self, n: u8, segment_data: Vec<u8>) {
+        if let Some((_, data)) = self.application_data.iter_mut().find(|(captured_n, _)| *captured_n == n) {
+            data.get_or_insert(segment_data);
+        }
+    }
+
+    /// Returns true when a COM or APPn segment is larger than the maximum segment size and must be
+    /// skipped. In strict mode such a segment is an error.
+    fn is_segment_too_large(&self, segment_size: usize) -> Result<bool, DecodingError> {
+        if self.maximum_segment_size.is_none_or(|maximum_segment_size| segment_size <= maximum_segment_size) {
+            return Ok(false);
+        }
+
+        if self.strict_mode {
+            return Err(DecodingError::InvalidMarkerSegmentSize);
+        }
+
+        Ok(true)
+    }
+
+    fn read_start_of_frame_segment(&mut self, segment_size: usize) -> Result<(), DecodingError> {
+        // A Frame Header as defined in T.87, C.2.2 and T.81, B.2.2
+        if segment_size < 6 {
+            return Err(DecodingError::InvalidMarkerSegmentSize);
+        }
+
+        let bits_per_sample = self.read_u8()?; // P = Sample precision
+        if !(MINIMUM_BITS_PER_SAMPLE..=MAXIMUM_BITS_PER_SAMPLE).contains(&bits_per_sample) {
+            return Err(DecodingError::InvalidParameterBitsPerSample);
+        }
+
+        let height = self.read_u16()?; // Y = Number of lines
+        if height == 0 {
+            return Err(DecodingError::InvalidParameterHeight);
+        }
+
+        let width = self.read_u16()?; // X = Number of samples per line
+        if width == 0 {
+            return Err(DecodingError::InvalidParameterWidth);
+        }
+
+        let component_count = self.read_u8()?; // Nf = Number of image components in frame
+        if component_count == 0 {
+            return Err(DecodingError::InvalidParameterComponentCount);
+        }
+        if self.strict_mode {
+            if let Some(spiff_header) = &self.spiff_header {
+                if spiff_header.bits_per_sample != bits_per_sample ||
+                    spiff_header.component_count != component_count {
+                    return Err(DecodingError::InvalidSpiffHeader);
+                }
+            }
+        }
+
+        // The component specification parameters must be inside the segment, not in the next one.
+        if segment_size < 6 + component_count as usize * 3 {
+            return Err(DecodingError::InvalidMarkerSegmentSize);
+        }
+
+        self.component_ids.clear();
+        for _ in 0..component_count {
+            let component_id = self.read_u8()?; // Ci = Component identifier
+            self.component_ids.push(component_id);
+            let sampling_factor = self.read_u8()?; // Hi + Vi = Horizontal sampling factor + Vertical sampling factor
+            if sampling_factor != 0x11 {
+                // JPEG-LS doesn't support sub-sampling: all components must use Hi = Vi = 1.
+                return Err(DecodingError::ParameterValueNotSupported);
+            }
+
+            self.read_u8()?; // Tqi = Quantization table destination selector (reserved for JPEG-LS)
+        }
+
+        self.skip_bytes(segment_size.saturating_sub(6 + component_count as usize * 3))?;
+
+        self.frame_info = FrameInfo {
+            width: width as u32,
+            height: height as u32,
+            bits_per_sample,
+            component_count,
+        };
+        self.state = ReaderState::FrameSection;
+        Ok(())
+    }
+
+    fn read_application_data8_segment(&mut self, segment_size: usize) -> Result<(), DecodingError> {
+        let segment_data = self.read_bytes(segment_size)?;
+        if segment_data.len() == 5 && segment_data[..4] == *b"mrfx" {
+            // The CharLS (and HP LOCO-I) color transformation segment. mrfx = xfrm = colorXFoRM.
+            let transformation = ColorTransformation::try_from(segment_data[4])
+                .map_err(|_| DecodingError::ColorTransformNotSupported)?;
+            self.color_transformation = Some(transformation);
+        } else if self.spiff_header.is_none() {
+            self.spiff_header = SpiffHeader::try_parse(&segment_data);
+            if self.spiff_header.is_some() {
+                self.state = ReaderState::SpiffHeaderSection;
+            }
+        }
+
+        if self.is_application_data_captured(8) {
+            self.capture_application_data(8, segment_data);
+        }
+
+        Ok(())
+    }
+
+    fn read_spiff_directory_entry(&mut self, marker_code: JpegMarkerCode) -> Result<(), DecodingError> {
+        // A SPIFF directory entry as defined in ISO/IEC 10918-3, F.2.2
+        if marker_code != JpegMarkerCode::ApplicationData8 {
+            return Err(DecodingError::MissingEndOfSpiffDirectory);
+        }
+
+        let segment_size = self.read_segment_size()?;
+        if segment_size < 4 {
+            return Err(DecodingError::InvalidMarkerSegmentSize);
+        }
+
+        let segment_data = self.read_bytes(segment_size)?;
+        let entry_tag = u32::from_be_bytes([segment_data[0], segment_data[1], segment_data[2], segment_data[3]]);
+        if entry_tag == SPIFF_END_OF_DIRECTORY_ENTRY_TYPE {
+            // The end of directory entry contains an SOI marker as data (see ISO/IEC 10918-3, F.2.2.3).
+            if segment_size != 6 {
+                return Err(DecodingError::InvalidMarkerSegmentSize);
+            }
+
+            self.state = ReaderState::HeaderSection;
+        }
+
+        Ok(())
+    }
+
+    fn read_start_of_scan_segment(&mut self, segment_size: usize) -> Result<(), DecodingError> {
+        // A Scan Header as defined in T.87, C.2.3 and T.81, B.2.3
+        if segment_size < 4 {
+            return Err(DecodingError::InvalidMarkerSegmentSize);
+        }
+
+        let component_count = self.read_u8()?; // Ns = Number of components in scan
+        if component_count == 0 || component_count > MAXIMUM_COMPONENT_COUNT_IN_SCAN ||
+            component_count > self.frame_info.component_count {
+            return Err(DecodingError::InvalidParameterComponentCount);
+        }
+
+        self.scanned_component_count += component_count as usize;
+        if self.scanned_component_count > self.frame_info.component_count as usize {
+            return Err(DecodingError::InvalidParameterComponentCount);
+        }
+
+        let mut component_ids = Vec::with_capacity(component_count as usize);
+        let mut mapping_table_ids = Vec::with_capacity(component_count as usize);
+        for _ in 0..component_count {
+            component_ids.push(self.read_u8()?); // Csj = Scan component selector
+            mapping_table_ids.push(self.read_u8()?); // Tmj = Mapping table selector (0 = no table)
+        }
+
+        let near_lossless = self.read_u8()?; // NEAR parameter
+        if near_lossless as i32 > compute_maximum_near_lossless(self.maximum_sample_value()) {
+            return Err(DecodingError::InvalidParameterNearLossless);
+        }
+
+        let interleave_mode = InterleaveMode::try_from(self.read_u8()?) // ILV parameter
+            .map_err(|_| DecodingError::InvalidParameterInterleaveMode)?;
+        if self.frame_info.component_count == 1 && interleave_mode != InterleaveMode::None {
+            return Err(DecodingError::InvalidParameterInterleaveMode);
+        }
+
+        // A scan with interleave mode None contains one component, an interleaved scan all components of the frame.
+        let expected_component_count = match interleave_mode {
+            InterleaveMode::None => 1,
+            _ => self.frame_info.component_count,
+        };
+        if component_count != expected_component_count {
+            return Err(DecodingError::InvalidParameterComponentCount);
+        }
+
+        let transformation = self.read_u8()?; // transformation
+        if transformation != 0 && component_count != 3 {
+            // The HP color transformations are defined for 3 components.
+            return Err(DecodingError::InvalidParameterColorTransformation);
+        }
+
+        if self.color_transformation.is_none() && transformation != 0 {
+            // Streams without a color transformation segment may signal the transformation in the SOS.
+            self.color_transformation = Some(ColorTransformation::try_from(transformation)
+                .map_err(|_| DecodingError::ColorTransformNotSupported)?);
+        }
+
+        self.skip_bytes(segment_size.saturating_sub(4 + component_count as usize * 2))?;
+
+        self.scan_info = Some(ScanInfo {
+            component_ids,
+            mapping_table_ids,
+            near_lossless,
+            interleave_mode,
+            transformation: self.color_transformation(),
+            raw_transformation: transformation,
+        });
+        self.state = ReaderState::BitStreamSection;
+        self.bitstream_offset = Some(self.position);
+        Ok(())
+    }
+
+    fn read_preset_parameters_segment(&mut self, segment_size: usize) -> Result<(), DecodingError> {
+        // A JPEG-LS preset parameters segment as defined in T.87, C.2.4.1
+        if segment_size < 1 {
+            return Err(DecodingError::InvalidMarkerSegmentSize);
+        }
+
+        let id = self.read_u8()?;
+        match id {
+            1 => {
+                // Preset coding parameters (T.87, C.2.4.1.1)
+                if segment_size != 11 {
+                    return Err(DecodingError::InvalidMarkerSegmentSize);
+                }
+
+                self.preset_coding_parameters = JpeglsPcParameters {
+                    maximum_sample_value: self.read_u16()? as i32,
+                    threshold1: self.read_u16()? as i32,
+                    threshold2: self.read_u16()? as i32,
+                    threshold3: self.read_u16()? as i32,
+                    reset_value: self.read_u16()? as i32,
+                };
+                Ok(())
+            }
+
+            // Mapping table specification and continuation (T.87, C.2.4.1.2 and C.2.4.1.3).
+            2 | 3 => self.read_mapping_table(id == 3, segment_size - 1),
+
+            // Reserved IDs: skipped in lenient mode.
+            _ => {
+                if self.strict_mode {
+                    return Err(DecodingError::InvalidParameterJpeglsPresetParameters);
+                }
+
+                self.skip_bytes(segment_size - 1)
+            }
+        }
+    }
+
+    /// Reads the segment length field and returns the number of payload bytes that follow it,
+    /// which is 0 for an empty segment with length 2.
+    pub(crate) fn read_segment_size(&mut self) -> Result<usize, DecodingError> {
+        const SEGMENT_LENGTH: usize = 2; // The segment size also includes the length of the segment length bytes.
+        let segment_size = self.read_u16()? as usize;
+        if segment_size < SEGMENT_LENGTH {
+            return Err(DecodingError::InvalidMarkerSegmentSize);
+        }
+
+        Ok(segment_size - SEGMENT_LENGTH)
+    }
+
+    fn maximum_component_value(&self) -> i32 {
+        maximum_sample_value(self.frame_info.bits_per_sample) as i32
+    }
+
+    fn read_u8(&mut self) -> Result<u8, DecodingError> {
+        if !self.fill_buffer(1)? {
+            return Err(DecodingError::UnexpectedEndOfStream);
+        }
+
+        let value = self.buffer[self.buffer_position];
+        self.buffer_position += 1;
+        self.position += 1;
+        Ok(value)
+    }
+
+    fn read_u16(&mut self) -> Result<u16, DecodingError> {
+        let high = self.read_u8()?;
+        let low = self.read_u8()?;
+        Ok(u16::from_be_bytes([high, low]))
+    }
+
+    fn read_bytes(&mut self, count: usize) -> Result<Vec<u8>, DecodingError> {
+        let mut bytes = Vec::with_capacity(count);
+        for _ in 0..count {
+            bytes.push(self.read_u8()?);
+        }
+
+        Ok(bytes)
+    }
+
+    pub(crate) fn skip_bytes(&mut self, count: usize) -> Result<(), DecodingError> {
+        for _ in 0..count {
+            self.read_u8()?;
+        }
+
+        Ok(())
+    }
+
+    /// Ensures that at least minimum_size unread bytes are buffered.
+    /// Returns false when the end of the underlying reader is reached first. Like read_exact it
+    /// reads until enough bytes are buffered, so readers may return fewer bytes per call (down to
+    /// 1) than requested. All segment parsing and the bit reader get their bytes from here.
+    fn fill_buffer(&mut self, minimum_size: usize) -> Result<bool, DecodingError> {
+        if self.buffer.len() - self.buffer_position >= minimum_size {
+            return Ok(true);
+        }
+
+        if let Some(recording) = &mut self.recording {
+            recording.extend_from_slice(&self.buffer[..self.buffer_position]);
+        }
+
+        self.buffer.drain(..self.buffer_position);
+        self.buffer_position = 0;
+
+        while self.buffer.len() < minimum_size {
+            let available = self.buffer.len();
+            let mut read_size = READ_BUFFER_SIZE.max(minimum_size - available);
+            if let Some(byte_limit) = self.byte_limit {
+                // At the limit 1 more byte is read, to find out whether the stream ends there.
+                let remaining = usize::try_from(byte_limit.saturating_sub(self.bytes_read)).unwrap_or(usize::MAX);
+                read_size = read_size.min(remaining).max(1);
+            }
+
+            self.buffer.resize(available + read_size, 0);
+            let result = self.reader.read(&mut self.buffer[available..]);
+            match result {
+                Ok(0) => {
+                    self.buffer.truncate(available);
+                    return Ok(false);
+                }
+                Ok(bytes_read) => {
+                    self.buffer.truncate(available + bytes_read);
+                    self.bytes_read += bytes_read as u64;
+                    if self.byte_limit.is_some_and(|byte_limit| self.bytes_read > byte_limit) {
+                        return Err(DecodingError::InputTooLarge);
+                    }
+                }
+                Err(error) if error.kind() == ErrorKind::Interrupted => self.buffer.truncate(available),
+                Err(_) => {
+                    self.buffer.truncate(available);
+                    return Err(DecodingError::IoError);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use super::*;
+
+    #[test]
+    fn read_header_from_too_small_input_buffer_fails() {
+        let mut buffer = Vec::new();
+        buffer.write_all(&[1]).unwrap();
+
+        let mut reader = JpegStreamReader::new(buffer.as_slice());
+        assert!(reader.read_header().is_err());
+    }
+
+    #[test]
+    fn read_header_from_buffer_preceded_with_fill_bytes() {
+        let extra_start_byte = 0xFFu8;
+
+        let mut writer = JpegTestStreamWriter::new();
+
+        writer.write_byte(extra_start_byte);
+        writer.write_start_of_image();
+
+        writer.write_byte(extra_start_byte);
+        writer.write_start_of_frame_segment(1, 1, 2, 1);
+
+        writer.write_byte(extra_start_byte);
+        writer.write_start_of_scan_segment(0, 1, 1, 0);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+        assert!(reader.read_header().is_ok());
+    }
+
+    #[test]
+    fn read_header_from_buffer_not_starting_with_ff_throws() {
+        let mut buffer = Vec::new();
+        buffer.write_all(&[0x0F, 0xFF, 0xD8, 0xFF, 0xFF, 0xDA]).unwrap();
+
+        let mut reader = JpegStreamReader::new(buffer.as_slice());
+
+        let x = reader.read_header().unwrap_err();
+        assert_eq!(x, DecodingError::JpegMarkerStartByteNotFound);
+    }
+
+    #[test]
+    fn read_header_without_start_of_frame_fails() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_start_of_scan_segment(0, 1, 1, 0);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+        assert_eq!(reader.read_header().unwrap_err(), DecodingError::StartOfFrameMarkerNotFound);
+
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_marker(JpegMarkerCode::EndOfImage);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+        assert_eq!(reader.read_header().unwrap_err(), DecodingError::StartOfFrameMarkerNotFound);
+    }
+
+    #[test]
+    fn state_after_partial_header() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_start_of_frame_segment(1, 1, 2, 1);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+        assert_eq!(reader.state(), ReaderStatePublic::BeforeStartOfImage);
+        reader.read_spiff_header().unwrap();
+        assert_eq!(reader.state(), ReaderStatePublic::HeaderSection);
+        assert_eq!(reader.read_header().unwrap_err(), DecodingError::UnexpectedEndOfStream);
+        assert_eq!(reader.state(), ReaderStatePublic::FrameSection);
+    }
+
+    #[test]
+    fn read_header_without_start_of_scan_fails() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_start_of_frame_segment(1, 1, 2, 1);
+        writer.write_marker(JpegMarkerCode::EndOfImage);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+        assert_eq!(reader.read_header().unwrap_err(), DecodingError::StartOfScanMarkerNotFound);
+
+        // Entropy coded data directly after the SOF segment.
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_start_of_frame_segment(1, 1, 2, 1);
+        writer.write_byte(0x12);
+        writer.write_byte(0x34);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+        assert_eq!(reader.read_header().unwrap_err(), DecodingError::StartOfScanMarkerNotFound);
+    }
+
+    #[test]
+    fn read_header_with_leading_garbage_and_scan_for_soi() {
+        let mut writer = JpegTestStreamWriter::new();
+        // The garbage contains 0xFF and 0xD8 bytes, but not as an SOI marker.
+        for value in [0x00, 0x12, 0xFF, 0x34, 0xD8] {
+            writer.write_byte(value);
+        }
+        writer.write_start_of_image();
+        writer.write_start_of_frame_segment(1, 1, 2, 1);
+        writer.write_start_of_scan_segment(0, 1, 1, 0);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+        reader.set_strict_mode(false);
+        reader.set_scan_for_soi(true);
+
+        reader.read_header().unwrap();
+        assert_eq!(reader.frame_info().width(), 1);
+        assert_eq!(reader.bitstream_offset(), Some(5 + 2 + 13 + 10));
+    }
+
+    #[test]
+    fn read_header_with_leading_garbage_and_scan_for_soi_fails_in_strict_mode() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_byte(0x00);
+        writer.write_byte(0x12);
+        writer.write_start_of_image();
+        writer.write_start_of_frame_segment(1, 1, 2, 1);
+        writer.write_start_of_scan_segment(0, 1, 1, 0);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+        reader.set_scan_for_soi(true);
+
+        assert_eq!(reader.read_header().unwrap_err(), DecodingError::JpegMarkerStartByteNotFound);
+    }
+
+    #[test]
+    fn read_header_with_scan_for_soi_without_start_of_image_fails() {
+        let mut reader = JpegStreamReader::new([0x00, 0xFF, 0xD9].as_slice());
+        reader.set_strict_mode(false);
+        reader.set_scan_for_soi(true);
+
+        assert_eq!(reader.read_header().unwrap_err(), DecodingError::StartOfImageMarkerNotFound);
+    }
+
+    #[test]
+    fn read_header_with_application_data() {
+        for i in 0..16 {
+            read_header_with_application_data_for(i);
+        }
+    }
+
+    #[test]
+    fn read_header_with_jfif_application_data() {
+        // A JFIF APP0 segment: "JFIF\0", version 1.02, density in dots per inch (72 x 72), no thumbnail.
+        let jfif = [b'J', b'F', b'I', b'F', 0, 1, 2, 1, 0, 72, 0, 72, 0, 0];
+        for strict_mode in [true, false] {
+            let mut writer = JpegTestStreamWriter::new();
+            writer.write_start_of_image();
+            writer.write_segment(JpegMarkerCode::ApplicationData0, &jfif);
+            writer.write_start_of_frame_segment(3, 2, 8, 1);
+            writer.write_start_of_scan_segment(0, 1, 0, 0);
+
+            let mut reader = JpegStreamReader::new(writer.data());
+            reader.set_strict_mode(strict_mode);
+
+            reader.read_header().unwrap();
+            assert_eq!(reader.frame_info().width(), 3);
+            assert_eq!(reader.frame_info().height(), 2);
+        }
+    }
+
+    #[test]
+    fn read_header_with_application_data_after_start_of_frame_in_lenient_mode() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_start_of_frame_segment(1, 1, 2, 1);
+        writer.write_segment(JpegMarkerCode::ApplicationData0, &[0x4A, 0x46, 0x49, 0x46, 0]);
+        writer.write_start_of_scan_segment(0, 1, 1, 0);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+        reader.set_strict_mode(false);
+
+        reader.read_header().unwrap();
+        assert_eq!(reader.frame_info().width(), 1);
+    }
+
+    #[test]
+    fn read_header_with_application_data_after_start_of_frame_fails_in_strict_mode() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_start_of_frame_segment(1, 1, 2, 1);
+        writer.write_segment(JpegMarkerCode::ApplicationData0, &[0x4A, 0x46, 0x49, 0x46, 0]);
+        writer.write_start_of_scan_segment(0, 1, 1, 0);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+
+        assert_eq!(reader.read_header().unwrap_err(), DecodingError::UnexpectedMarkerFound);
+    }
+
+    #[test]
+    fn read_header_with_spiff_header_matching_frame() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_spiff_header_segment(3, 1, 1, 8);
+        writer.write_spiff_end_of_directory_entry();
+        writer.write_start_of_frame_segment(1, 1, 8, 3);
+        writer.write_start_of_scan_segment(0, 3, 0, 2);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+        reader.read_header().unwrap();
+
+        let spiff_header = reader.spiff_header().unwrap();
+        assert_eq!(spiff_header.component_count(), 3);
+        assert_eq!(spiff_header.bits_per_sample(), 8);
+        assert_eq!(spiff_header.compression_type(), 6);
+    }
+
+    #[test]
+    fn read_spiff_header_then_read_header() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_spiff_header_segment(3, 1, 1, 8);
+        writer.write_spiff_end_of_directory_entry();
+        writer.write_start_of_frame_segment(1, 1, 8, 3);
+        writer.write_start_of_scan_segment(0, 3, 0, 2);
+        let mut reader = JpegStreamReader::new(writer.data());
+
+        reader.read_spiff_header().unwrap();
+
+        assert_eq!(reader.spiff_header().unwrap().component_count(), 3);
+        assert_eq!(reader.frame_info().component_count(), 0);
+        reader.read_header().unwrap();
+        assert_eq!(reader.frame_info().component_count(), 3);
+    }
+
+    #[test]
+    fn read_spiff_header_without_spiff_header_then_read_header() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_segment(JpegMarkerCode::ApplicationData8, b"SPIFX\0\0\0");
+        writer.write_start_of_frame_segment(1, 1, 8, 1);
+        writer.write_start_of_scan_segment(0, 1, 0, 0);
+        let mut reader = JpegStreamReader::new(writer.data());
+
+        reader.read_spiff_header().unwrap();
+
+        assert!(reader.spiff_header().is_none());
+        reader.read_header().unwrap();
+        assert!(reader.spiff_header().is_none());
+        assert_eq!(reader.frame_info().component_count(), 1);
+    }
+
+    #[test]
+    fn read_header_with_spiff_header_bits_per_sample_mismatch_fails_in_strict_mode() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_spiff_header_segment(1, 1, 1, 12);
+        writer.write_spiff_end_of_directory_entry();
+        writer.write_start_of_frame_segment(1, 1, 8, 1);
+        writer.write_start_of_scan_segment(0, 1, 0, 0);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+
+        assert_eq!(reader.read_header().unwrap_err(), DecodingError::InvalidSpiffHeader);
+    }
 
-use crate::jpeg_marker_code::JpegMarkerCode;
-use crate::decoding_error::DecodingError;
+    #[test]
+    fn read_header_with_spiff_header_component_count_mismatch_fails_in_strict_mode() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_spiff_header_segment(1, 1, 1, 8);
+        writer.write_spiff_end_of_directory_entry();
+        writer.write_start_of_frame_segment(1, 1, 8, 3);
+        writer.write_start_of_scan_segment(0, 3, 0, 2);
 
-#[derive(Clone, Debug)]
-pub struct FrameInfo {
-    width: u32,
-    height: u32,
-    bits_per_sample: u8,
-    component_count: u8,
-}
+        let mut reader = JpegStreamReader::new(writer.data());
 
+        assert_eq!(reader.read_header().unwrap_err(), DecodingError::InvalidSpiffHeader);
+    }
 
-#[derive(Debug, Eq, PartialEq)]
-enum ReaderState
-{
-    BeforeStartOfImage,
-    HeaderSection,
-    SpiffHeaderSection,
-    ImageSection,
-    FrameSection,
-    ScanSection,
-    BitStreamSection,
-    AfterEndOfImage,
-}
+    #[test]
+    fn read_header_with_spiff_header_mismatch_uses_frame_in_lenient_mode() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_spiff_header_segment(1, 1, 1, 12);
+        writer.write_spiff_end_of_directory_entry();
+        writer.write_start_of_frame_segment(1, 1, 8, 3);
+        writer.write_start_of_scan_segment(0, 3, 0, 2);
 
+        let mut reader = JpegStreamReader::new(writer.data());
+        reader.set_strict_mode(false);
+        reader.read_header().unwrap();
 
-#[derive(Debug)]
-pub struct JpegStreamReader<R: Read> {
-    reader: R,
-    frame_info: FrameInfo,
-    state: ReaderState,
-}
+        assert_eq!(reader.frame_info().bits_per_sample(), 8);
+        assert_eq!(reader.frame_info().component_count(), 3);
+    }
 
+    #[test]
+    fn read_header_with_spiff_header_without_end_of_directory_fails() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_spiff_header_segment(1, 1, 1, 8);
+        writer.write_start_of_frame_segment(1, 1, 8, 1);
+        writer.write_start_of_scan_segment(0, 1, 0, 0);
 
-impl<R: Read> JpegStreamReader<R> {
-    pub fn new(r: R) -> JpegStreamReader<R> {
-        let width = 0;
-        let height = 0;
-        let bits_per_sample = 0;
-        let component_count = 0;
+        let mut reader = JpegStreamReader::new(writer.data());
 
-        JpegStreamReader {
-            reader: r,
-            frame_info: FrameInfo {
-                width,
-                height,
-                bits_per_sample,
-                component_count,
-            },
-            state: ReaderState::BeforeStartOfImage,
-        }
+        assert_eq!(reader.read_header().unwrap_err(), DecodingError::MissingEndOfSpiffDirectory);
     }
 
-    pub fn read_next_marker_code(&mut self) -> Result<JpegMarkerCode, DecodingError> {
-        let mut value = self.read_u8()?;
-        if value != 255 {
-            return Err(DecodingError::JpegMarkerStartByteNotFound);
-        }
+    #[test]
+    fn bitstream_offset_is_header_length() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_start_of_frame_segment(1, 1, 2, 1);
+        writer.write_start_of_scan_segment(0, 1, 1, 0);
+        let header_length = writer.data().len() as u64;
+        writer.write_byte(0);
 
-        // Read all preceding 0xFF fill values until a non 0xFF value has been found. (see ISO/IEC 10918-1, B.1.1.2)
-        while value == 255 {
-            value = self.read_u8()?;
+        let mut reader = JpegStreamReader::new(writer.data());
+        assert_eq!(reader.bitstream_offset(), None);
+        reader.read_header().unwrap();
+
+        assert_eq!(reader.bitstream_offset(), Some(header_length));
+    }
+
+    #[test]
+    fn read_header_with_color_transformation_segment() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_segment(JpegMarkerCode::ApplicationData8, b"mrfx\x02");
+        writer.write_start_of_frame_segment(1, 1, 8, 3);
+        writer.write_start_of_scan_segment(0, 3, 0, 2);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+        reader.read_header().unwrap();
+
+        assert_eq!(reader.color_transformation(), ColorTransformation::Hp2);
+    }
+
+    #[test]
+    fn read_header_with_unsupported_color_transformation_fails() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_segment(JpegMarkerCode::ApplicationData8, b"mrfx\x04"); // 4 = RgbAsYuvLossy
+        writer.write_start_of_frame_segment(1, 1, 8, 3);
+        writer.write_start_of_scan_segment(0, 3, 0, 2);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+
+        assert_eq!(reader.read_header().unwrap_err(), DecodingError::ColorTransformNotSupported);
+    }
+
+    #[test]
+    fn read_header_with_reserved_preset_parameters_id_fails_in_strict_mode() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_segment(JpegMarkerCode::JpeglsPresetParameters, &[0x0A, 1, 2, 3]);
+        writer.write_start_of_frame_segment(1, 1, 8, 1);
+        writer.write_start_of_scan_segment(0, 1, 0, 0);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+
+        assert_eq!(reader.read_header().unwrap_err(), DecodingError::InvalidParameterJpeglsPresetParameters);
+    }
+
+    #[test]
+    fn read_header_with_reserved_preset_parameters_id_is_skipped_in_lenient_mode() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_segment(JpegMarkerCode::JpeglsPresetParameters, &[0x0A, 1, 2, 3]);
+        writer.write_start_of_frame_segment(1, 1, 8, 1);
+        writer.write_start_of_scan_segment(0, 1, 0, 0);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+        reader.set_strict_mode(false);
+
+        assert!(reader.read_header().is_ok());
+        assert_eq!(reader.frame_info().bits_per_sample(), 8);
+    }
+
+    #[test]
+    fn read_header_with_zero_components_in_frame_fails() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_start_of_frame_segment(1, 1, 2, 0);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+
+        assert_eq!(reader.read_header().unwrap_err(), DecodingError::InvalidParameterComponentCount);
+    }
+
+    #[test]
+    fn read_header_with_fewer_component_specifications_than_in_frame_fails() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        // A SOF segment with Nf = 3 but only the parameters of 2 components.
+        writer.write_segment(JpegMarkerCode::StartOfFrameJpegls, &[8, 0, 1, 0, 1, 3, 1, 0x11, 0, 2, 0x11, 0]);
+        writer.write_start_of_scan_segment(1, 3, 0, 1);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+
+        assert_eq!(reader.read_header().unwrap_err(), DecodingError::InvalidMarkerSegmentSize);
+    }
+
+    #[test]
+    fn read_header_with_zero_components_in_scan_fails() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_start_of_frame_segment(1, 1, 8, 3);
+        writer.write_start_of_scan_segment(1, 0, 0, 0);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+
+        assert_eq!(reader.read_header().unwrap_err(), DecodingError::InvalidParameterComponentCount);
+        assert!(reader.scan_info().is_none());
+    }
+
+    #[test]
+    fn read_header_with_more_components_in_scan_than_in_frame_fails() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_start_of_frame_segment(1, 1, 2, 1);
+        writer.write_start_of_scan_segment(0, 2, 0, 0);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+
+        assert_eq!(reader.read_header().unwrap_err(), DecodingError::InvalidParameterComponentCount);
+    }
+
+    #[test]
+    fn read_header_with_scan_component_count_of_interleave_mode() {
+        for (interleave_mode, component_count) in [(0, 1), (1, 3), (2, 3)] {
+            let mut writer = JpegTestStreamWriter::new();
+            writer.write_start_of_image();
+            writer.write_start_of_frame_segment(1, 1, 8, 3);
+            writer.write_start_of_scan_segment(1, component_count, 0, interleave_mode);
+
+            let mut reader = JpegStreamReader::new(writer.data());
+
+            assert!(reader.read_header().is_ok(), "interleave mode {}", interleave_mode);
+            assert_eq!(reader.scan_info().unwrap().component_ids().len(), component_count as usize);
         }
+    }
 
-        let r = JpegMarkerCode::try_from(value);
-        if r.is_err() {
-            return Err(DecodingError::StartOfImageMarkerNotFound);
+    #[test]
+    fn read_header_with_scan_component_count_not_matching_interleave_mode_fails() {
+        for (interleave_mode, component_count) in [(0, 2), (0, 3), (1, 1), (1, 2), (2, 1), (2, 2)] {
+            let mut writer = JpegTestStreamWriter::new();
+            writer.write_start_of_image();
+            writer.write_start_of_frame_segment(1, 1, 8, 3);
+            writer.write_start_of_scan_segment(1, component_count, 0, interleave_mode);
+
+            let mut reader = JpegStreamReader::new(writer.data());
+
+            assert_eq!(reader.read_header().unwrap_err(), DecodingError::InvalidParameterComponentCount,
+                       "interleave mode {} with {} components", interleave_mode, component_count);
         }
+    }
+
+    #[test]
+    fn read_next_start_of_scan_at_end_of_image_fails() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_start_of_frame_segment(1, 1, 8, 3);
+        writer.write_start_of_scan_segment(1, 1, 0, 0);
+        writer.write_marker(JpegMarkerCode::EndOfImage);
 
-        return Ok(r.unwrap());
+        let mut reader = JpegStreamReader::new(writer.data());
+        reader.read_header().unwrap();
+
+        assert_eq!(reader.read_next_start_of_scan().unwrap_err(), DecodingError::InvalidParameterComponentCount);
     }
 
-    pub fn read_header(&mut self) -> Result<(), DecodingError> {
-        if self.state == ReaderState::BeforeStartOfImage {
-            if self.read_next_marker_code()? != JpegMarkerCode::StartOfImage {
-                return Err(DecodingError::StartOfImageMarkerNotFound);
-            }
+    #[test]
+    fn read_end_of_image_with_components_without_scan_fails() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_start_of_frame_segment(1, 1, 8, 3);
+        writer.write_start_of_scan_segment(1, 1, 0, 0);
+        writer.write_marker(JpegMarkerCode::EndOfImage);
 
-            self.state = ReaderState::HeaderSection;
-        }
+        let mut reader = JpegStreamReader::new(writer.data());
+        reader.read_header().unwrap();
 
-        Ok(())
+        assert_eq!(reader.read_end_of_image().unwrap_err(), DecodingError::InvalidParameterComponentCount);
     }
 
-    fn read_u8(&mut self) -> Result<u8, DecodingError> {
-        let mut buf = [0; 1];
-        let result = self.reader.read_exact(&mut buf);
-        if result.is_err() {
-            return Err(DecodingError::UnknownError);
-        }
+    #[test]
+    fn read_header_with_mapping_table_and_continuation() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_segment(JpegMarkerCode::JpeglsPresetParameters, &[2, 5, 2, 1, 2, 3, 4]);
+        writer.write_segment(JpegMarkerCode::JpeglsPresetParameters, &[3, 5, 2, 5, 6]);
+        writer.write_start_of_frame_segment(1, 1, 8, 1);
+        writer.write_start_of_scan_segment(1, 1, 0, 0);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+        reader.read_header().unwrap();
 
-        Ok(buf[0])
+        let table = reader.mapping_table(5).unwrap();
+        assert_eq!(table.entry_size(), 2);
+        assert_eq!(table.data(), &[1, 2, 3, 4, 5, 6]);
+        assert!(reader.mapping_table(1).is_none());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::io::Write;
-    use super::*;
+    #[test]
+    fn read_header_with_invalid_mapping_table_id_fails() {
+        // Table ID 0, a table defined twice, and a continuation of an undefined table.
+        for segments in [vec![&[2, 0, 1, 7][..]], vec![&[2, 1, 1, 7][..], &[2, 1, 1, 8][..]], vec![&[3, 1, 1, 7][..]]] {
+            let mut writer = JpegTestStreamWriter::new();
+            writer.write_start_of_image();
+            for segment in segments {
+                writer.write_segment(JpegMarkerCode::JpeglsPresetParameters, segment);
+            }
+
+            let mut reader = JpegStreamReader::new(writer.data());
+
+            assert_eq!(reader.read_header().unwrap_err(), DecodingError::InvalidParameterMappingTableId);
+        }
+    }
 
     #[test]
-    fn read_header_from_too_small_input_buffer_fails() {
-        let mut buffer = Vec::new();
-        buffer.write_all(&[1]).unwrap();
+    fn read_header_with_sampling_factor_2_returns_error() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_segment(JpegMarkerCode::StartOfFrameJpegls, &[8, 0, 1, 0, 1, 1, 1, 0x22, 0]);
 
-        let mut reader = JpegStreamReader::new(buffer.as_slice());
-        assert!(reader.read_header().is_err());
+        let mut reader = JpegStreamReader::new(writer.data());
+        assert_eq!(reader.read_header().unwrap_err(), DecodingError::ParameterValueNotSupported);
     }
 
     #[test]
-    fn read_header_from_buffer_preceded_with_fill_bytes() {
-        let extra_start_byte = 0xFFu8;
+    fn read_header_provides_scan_info() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_segment(JpegMarkerCode::ApplicationData8, b"mrfx\x01");
+        writer.write_start_of_frame_segment(1, 1, 8, 3);
+        writer.write_start_of_scan_segment(7, 3, 2, 1);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+        assert!(reader.scan_info().is_none());
+        reader.read_header().unwrap();
+
+        let scan_info = reader.scan_info().unwrap();
+        assert_eq!(scan_info.component_ids(), &[7, 8, 9]);
+        assert_eq!(scan_info.near_lossless(), 2);
+        assert_eq!(scan_info.interleave_mode(), InterleaveMode::Line);
+        assert_eq!(scan_info.transformation(), ColorTransformation::Hp1);
+    }
+
+    #[test]
+    fn read_header_with_color_transformation_in_scan_of_3_components() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_start_of_frame_segment(1, 1, 8, 3);
+        writer.write_segment(JpegMarkerCode::StartOfScan, &[3, 1, 0, 2, 0, 3, 0, 0, 2, 1]);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+
+        reader.read_header().unwrap();
+        assert_eq!(reader.color_transformation(), ColorTransformation::Hp1);
+    }
 
+    #[test]
+    fn read_header_returns_raw_transformation_of_scan() {
         let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_segment(JpegMarkerCode::ApplicationData8, b"mrfx\x02");
+        writer.write_start_of_frame_segment(1, 1, 8, 3);
+        writer.write_segment(JpegMarkerCode::StartOfScan, &[3, 1, 0, 2, 0, 3, 0, 0, 2, 0x85]);
 
-        writer.write_byte(extra_start_byte);
+        let mut reader = JpegStreamReader::new(writer.data());
+
+        reader.read_header().unwrap();
+        let scan_info = reader.scan_info().unwrap();
+        assert_eq!(scan_info.raw_transformation(), 0x85);
+        assert_eq!(scan_info.transformation(), ColorTransformation::Hp2);
+    }
+
+    #[test]
+    fn read_header_with_color_transformation_in_scan_of_1_component_fails() {
+        let mut writer = JpegTestStreamWriter::new();
         writer.write_start_of_image();
+        writer.write_start_of_frame_segment(1, 1, 8, 1);
+        writer.write_segment(JpegMarkerCode::StartOfScan, &[1, 1, 0, 0, 0, 1]);
 
-        writer.write_byte(extra_start_byte);
-        writer.write_start_of_frame_segment(1, 1, 2, 1);
+        let mut reader = JpegStreamReader::new(writer.data());
 
-        writer.write_byte(extra_start_byte);
-        writer.write_start_of_scan_segment(0, 1, 1, 0);
+        assert_eq!(reader.read_header().unwrap_err(), DecodingError::InvalidParameterColorTransformation);
+    }
+
+    #[test]
+    fn read_header_captures_application_data() {
+        let mut writer = JpegTestStreamWriter::new();
+        writer.write_start_of_image();
+        writer.write_segment(JpegMarkerCode::ApplicationData11, b"JP\x00\x01payload");
+        writer.write_segment(JpegMarkerCode::ApplicationData11, b"second");
+        writer.write_segment(JpegMarkerCode::ApplicationData8, b"mrfx\x01");
+        writer.write_segment(JpegMarkerCode::ApplicationData2, b"not captured");
+        writer.write_start_of_frame_segment(1, 1, 2, 3);
+        writer.write_start_of_scan_segment(0, 3, 0, 2);
 
         let mut reader = JpegStreamReader::new(writer.data());
-        assert!(reader.read_header().is_ok());
+        reader.set_capture_application_data(11);
+        reader.set_capture_application_data(8);
+        reader.set_capture_application_data(5);
+        reader.read_header().unwrap();
+
+        assert_eq!(reader.application_data(11), Some(&b"JP\x00\x01payload"[..]));
+        assert_eq!(reader.application_data(8), Some(&b"mrfx\x01"[..]));
+        assert_eq!(reader.application_data(5), None);
+        assert_eq!(reader.application_data(2), None);
+        assert_eq!(reader.color_transformation(), ColorTransformation::Hp1);
     }
 
     #[test]
-    fn read_header_from_buffer_not_starting_with_ff_throws() {
-        let mut buffer = Vec::new();
-        buffer.write_all(&[0x0F, 0xFF, 0xD8, 0xFF, 0xFF, 0xDA]).unwrap();
+    fn read_header_with_segments_at_maximum_segment_size() {
+        let mut writer = JpegTestStreamWriter::new();
+        write_stream_with_large_segments(&mut writer);
 
-        let mut reader = JpegStreamReader::new(buffer.as_slice());
+        let mut reader = JpegStreamReader::new(writer.data());
+        reader.set_maximum_segment_size(8);
+        reader.set_capture_application_data(11);
+        reader.read_header().unwrap();
 
-        let x = reader.read_header().unwrap_err();
-        assert_eq!(x, DecodingError::JpegMarkerStartByteNotFound);
+        assert_eq!(reader.application_data(11), Some(&b"8 bytes!"[..]));
+        assert_eq!(reader.color_transformation(), ColorTransformation::Hp1);
     }
 
     #[test]
-    fn read_header_with_application_data() {
-        for i in 0..16 {
-            read_header_with_application_data_for(i);
+    fn read_header_with_segment_above_maximum_segment_size_fails_in_strict_mode() {
+        for maximum_segment_size in [4, 6, 7] {
+            let mut writer = JpegTestStreamWriter::new();
+            write_stream_with_large_segments(&mut writer);
+
+            let mut reader = JpegStreamReader::new(writer.data());
+            reader.set_maximum_segment_size(maximum_segment_size);
+
+            assert_eq!(reader.read_header().unwrap_err(), DecodingError::InvalidMarkerSegmentSize);
         }
     }
 
+    #[test]
+    fn read_header_skips_segments_above_maximum_segment_size_in_lenient_mode() {
+        let mut writer = JpegTestStreamWriter::new();
+        write_stream_with_large_segments(&mut writer);
+
+        let mut reader = JpegStreamReader::new(writer.data());
+        reader.set_maximum_segment_size(4);
+        reader.set_strict_mode(false);
+        reader.set_capture_application_data(11);
+        reader.set_capture_application_data(8);
+        reader.read_header().unwrap();
+
+        assert_eq!(reader.application_data(11), None);
+        assert_eq!(reader.application_data(8), None);
+        assert_eq!(reader.color_transformation(), ColorTransformation::None);
+    }
+
     struct JpegTestStreamWriter {
         buffer: Vec<u8>,
     }
@@ -176,6 +1619,32 @@ mod tests {
             self.buffer.write_all(&[0xFF, 0xD8]).unwrap();
         }
 
+        fn write_spiff_header_segment(&mut self, component_count: u8, height: u32, width: u32,
+                                      bits_per_sample: u8) {
+            // Create a SPIFF header as defined in ISO/IEC 10918-3, F.2.1
+            let mut segment = Vec::new();
+
+            segment.write_all(b"SPIFF\0").unwrap();
+            write_byte(&mut segment, 2); // Major revision
+            write_byte(&mut segment, 0); // Minor revision
+            write_byte(&mut segment, 0); // Profile: not specified
+            write_byte(&mut segment, component_count);
+            write_u32(&mut segment, height);
+            write_u32(&mut segment, width);
+            write_byte(&mut segment, if component_count == 1 { 8 } else { 10 }); // Color space: grayscale or RGB
+            write_byte(&mut segment, bits_per_sample);
+            write_byte(&mut segment, 6); // Compression type: JPEG-LS
+            write_byte(&mut segment, 0); // Resolution units: aspect ratio
+            write_u32(&mut segment, 1); // Vertical resolution
+            write_u32(&mut segment, 1); // Horizontal resolution
+
+            self.write_segment(JpegMarkerCode::ApplicationData8, &segment);
+        }
+
+        fn write_spiff_end_of_directory_entry(&mut self) {
+            self.write_segment(JpegMarkerCode::ApplicationData8, &[0, 0, 0, 1, 0xFF, 0xD8]);
+        }
+
         fn write_start_of_frame_segment(&mut self, width: u16, height: u16, bits_per_sample: u8,
                                         component_count: u16) {
             // Create a Frame Header as defined in T.87, C.2.2 and T.81, B.2.2
@@ -216,10 +1685,8 @@ mod tests {
             self.write_segment(JpegMarkerCode::StartOfScan, &segment);
         }
 
-        fn write_segment(&mut self, marker_code: JpegMarkerCode, segment_data: &Vec<u8>)
+        fn write_segment(&mut self, marker_code: JpegMarkerCode, segment_data: &[u8])
         {
-            self.buffer.write_all(&[0xFF, 0xD8]).unwrap();
-
             self.write_marker(marker_code);
             write_u16(&mut self.buffer, (segment_data.len() + 2) as u16);
             self.buffer.write_all(segment_data).unwrap();
@@ -245,6 +1712,15 @@ mod tests {
         assert!(reader.read_header().is_ok());
     }
 
+    fn write_stream_with_large_segments(writer: &mut JpegTestStreamWriter) {
+        writer.write_start_of_image();
+        writer.write_segment(JpegMarkerCode::Comment, b"comment");
+        writer.write_segment(JpegMarkerCode::ApplicationData11, b"8 bytes!");
+        writer.write_segment(JpegMarkerCode::ApplicationData8, b"mrfx\x01");
+        writer.write_start_of_frame_segment(1, 1, 8, 3);
+        writer.write_start_of_scan_segment(0, 3, 0, 2);
+    }
+
     fn write_byte(buffer: &mut Vec<u8>, value: u8) {
         buffer.write_all(&[value]).unwrap();
     }
@@ -252,4 +1728,8 @@ mod tests {
     fn write_u16(buffer: &mut Vec<u8>, value: u16) {
         buffer.write_all(&value.to_be_bytes()).unwrap();
     }
+
+    fn write_u32(buffer: &mut Vec<u8>, value: u32) {
+        buffer.write_all(&value.to_be_bytes()).unwrap();
+    }
 }
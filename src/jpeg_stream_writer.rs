@@ -0,0 +1,115 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use crate::encoding_error::EncodingError;
+use crate::frame_info::FrameInfo;
+use crate::interleave_mode::InterleaveMode;
+use crate::jpeg_marker_code::JpegMarkerCode;
+use crate::jpeg_stream_reader::JPEG_MARKER_START_BYTE;
+use crate::spiff_header::{SpiffHeader, SPIFF_END_OF_DIRECTORY_ENTRY_TYPE};
+
+/// Writes the markers and segments of a JPEG-LS stream (see ISO/IEC 14495-1, annex C)
+/// into a destination buffer. Writing beyond the end of the buffer fails with DestinationTooSmall.
+pub(crate) struct JpegStreamWriter<'a> {
+    destination: &'a mut [u8],
+    position: usize,
+}
+
+impl<'a> JpegStreamWriter<'a> {
+    pub(crate) fn new(destination: &'a mut [u8]) -> JpegStreamWriter<'a> {
+        JpegStreamWriter {
+            destination,
+            position: 0,
+        }
+    }
+
+    /// The number of bytes that have been written.
+    pub(crate) fn bytes_written(&self) -> usize {
+        self.position
+    }
+
+    pub(crate) fn write_start_of_image(&mut self) -> Result<(), EncodingError> {
+        self.write_marker(JpegMarkerCode::StartOfImage)
+    }
+
+    pub(crate) fn write_end_of_image(&mut self) -> Result<(), EncodingError> {
+        self.write_marker(JpegMarkerCode::EndOfImage)
+    }
+
+    /// Writes the SPIFF header in an APP8 segment (see ISO/IEC 10918-3, F.2.1).
+    pub(crate) fn write_spiff_header_segment(&mut self, spiff_header: &SpiffHeader) -> Result<(), EncodingError> {
+        self.write_segment(JpegMarkerCode::ApplicationData8, &spiff_header.to_segment_data())
+    }
+
+    /// Writes the SPIFF end of directory entry, which contains an SOI marker as data
+    /// (see ISO/IEC 10918-3, F.2.2.3).
+    pub(crate) fn write_spiff_end_of_directory_entry(&mut self) -> Result<(), EncodingError> {
+        let mut segment = SPIFF_END_OF_DIRECTORY_ENTRY_TYPE.to_be_bytes().to_vec();
+        segment.extend_from_slice(&[JPEG_MARKER_START_BYTE, JpegMarkerCode::StartOfImage as u8]);
+        self.write_segment(JpegMarkerCode::ApplicationData8, &segment)
+    }
+
+    /// Writes a Frame Header as defined in T.87, C.2.2 and T.81, B.2.2.
+    /// The components are identified by the ids 1..=component_count.
+    pub(crate) fn write_start_of_frame_segment(&mut self, frame_info: &FrameInfo) -> Result<(), EncodingError> {
+        let mut segment = Vec::new();
+        segment.push(frame_info.bits_per_sample()); // P = Sample precision
+        push_u16(&mut segment, frame_info.height() as u16); // Y = Number of lines
+        push_u16(&mut segment, frame_info.width() as u16); // X = Number of samples per line
+
+        segment.push(frame_info.component_count()); // Nf = Number of image components in frame
+        for component_id in 1..=frame_info.component_count() {
+            segment.push(component_id); // Ci = Component identifier
+            segment.push(0x11); // Hi + Vi = Horizontal sampling factor + Vertical sampling factor
+            segment.push(0); // Tqi = Quantization table destination selector (reserved for JPEG-LS)
+        }
+
+        self.write_segment(JpegMarkerCode::StartOfFrameJpegls, &segment)
+    }
+
+    /// Writes a Scan Header as defined in T.87, C.2.3 and T.81, B.2.3.
+    pub(crate) fn write_start_of_scan_segment(&mut self, component_ids: &[u8], near_lossless: u8,
+                                              interleave_mode: InterleaveMode) -> Result<(), EncodingError> {
+        let mut segment = Vec::new();
+        segment.push(component_ids.len() as u8); // Ns = Number of components in scan
+        for &component_id in component_ids {
+            segment.push(component_id); // Csj = Scan component selector
+            segment.push(0); // Tmj = Mapping table selector (0 = no table)
+        }
+
+        segment.push(near_lossless); // NEAR = Near lossless
+        segment.push(interleave_mode as u8); // ILV = Interleave mode
+        segment.push(0); // Al + Ah = Point transform (not used by JPEG-LS)
+
+        self.write_segment(JpegMarkerCode::StartOfScan, &segment)
+    }
+
+    pub(crate) fn write_byte(&mut self, value: u8) -> Result<(), EncodingError> {
+        self.write_bytes(&[value])
+    }
+
+    fn write_bytes(&mut self, values: &[u8]) -> Result<(), EncodingError> {
+        let end = self.position + values.len();
+        if end > self.destination.len() {
+            return Err(EncodingError::DestinationTooSmall);
+        }
+
+        self.destination[self.position..end].copy_from_slice(values);
+        self.position = end;
+        Ok(())
+    }
+
+    fn write_marker(&mut self, marker_code: JpegMarkerCode) -> Result<(), EncodingError> {
+        self.write_bytes(&[JPEG_MARKER_START_BYTE, marker_code as u8])
+    }
+
+    fn write_segment(&mut self, marker_code: JpegMarkerCode, data: &[u8]) -> Result<(), EncodingError> {
+        self.write_marker(marker_code)?;
+        self.write_bytes(&(data.len() as u16 + 2).to_be_bytes())?;
+        self.write_bytes(data)
+    }
+}
+
+fn push_u16(segment: &mut Vec<u8>, value: u16) {
+    segment.extend_from_slice(&value.to_be_bytes());
+}
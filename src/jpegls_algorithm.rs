@@ -0,0 +1,204 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+//! The functions of the JPEG-LS coding process (see ISO/IEC 14495-1, annex A) that are used by
+//! both the scan decoder and the scan encoder.
+
+use crate::jpegls_preset_coding_parameters::JpeglsPcParameters;
+use crate::regular_mode_context::initialization_value_for_a;
+
+/// The run length order table J, as defined by ISO/IEC 14495-1, A.7.1.2
+pub(crate) const J: [i32; 32] = [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+
+pub(crate) const REGULAR_MODE_CONTEXT_COUNT: usize = 365;
+
+/// Computes the context id Q from the quantized gradients (see ISO/IEC 14495-1, A.3.4).
+pub(crate) fn compute_context_id(q1: i32, q2: i32, q3: i32) -> i32 {
+    (q1 * 9 + q2) * 9 + q3
+}
+
+/// Fills quantization_lut with the lookup table that maps a local gradient in
+/// -maximum_sample_value..=maximum_sample_value to its quantized value.
+pub(crate) fn fill_quantization_lut(quantization_lut: &mut Vec<i8>, preset_coding_parameters: &JpeglsPcParameters,
+                                    near_lossless: i32) {
+    let range = preset_coding_parameters.maximum_sample_value;
+    let threshold1 = preset_coding_parameters.threshold1;
+    let threshold2 = preset_coding_parameters.threshold2;
+    let threshold3 = preset_coding_parameters.threshold3;
+    quantization_lut.clear();
+    quantization_lut.extend((-range..=range)
+        .map(|di| quantize_gradient(di, threshold1, threshold2, threshold3, near_lossless)));
+}
+
+/// Quantizes a local gradient with the thresholds T1, T2 and T3 of the resolved preset coding
+/// parameters (see ISO/IEC 14495-1, A.3.3, code segment A.4). A gradient equal to -T1, -T2, -T3,
+/// T1, T2 or T3 belongs to the region farther from 0; -NEAR and NEAR are quantized to 0.
+pub(crate) fn quantize_gradient(di: i32, threshold1: i32, threshold2: i32, threshold3: i32,
+                                near_lossless: i32) -> i8 {
+    if di <= -threshold3 {
+        return -4;
+    }
+    if di <= -threshold2 {
+        return -3;
+    }
+    if di <= -threshold1 {
+        return -2;
+    }
+    if di < -near_lossless {
+        return -1;
+    }
+    if di <= near_lossless {
+        return 0;
+    }
+    if di < threshold1 {
+        return 1;
+    }
+    if di < threshold2 {
+        return 2;
+    }
+    if di < threshold3 {
+        return 3;
+    }
+
+    4
+}
+
+/// The median edge detecting predictor (see ISO/IEC 14495-1, A.4.1, code segment A.5).
+pub(crate) fn get_predicted_value(ra: i32, rb: i32, rc: i32) -> i32 {
+    if rc >= ra.max(rb) {
+        return ra.min(rb);
+    }
+
+    if rc <= ra.min(rb) {
+        return ra.max(rb);
+    }
+
+    ra + rb - rc
+}
+
+pub(crate) fn apply_sign(i: i32, sign: i32) -> i32 {
+    (sign ^ i) - sign
+}
+
+/// Returns 1 for values >= 0 and -1 for negative values.
+pub(crate) fn sign(n: i32) -> i32 {
+    if n >= 0 { 1 } else { -1 }
+}
+
+/// Maps the signed error value to a non-negative value for Golomb coding
+/// (see ISO/IEC 14495-1, A.5.2, code segment A.11).
+pub(crate) fn map_error_value(error_value: i32) -> i32 {
+    (error_value >> (i32::BITS - 2)) ^ (2 * error_value)
+}
+
+/// Maps the non-negative Golomb decoded value back to the signed error value
+/// (see ISO/IEC 14495-1, A.5.2, inverse of code segment A.11).
+pub(crate) fn unmap_error_value(mapped_error: i32) -> i32 {
+    let sign = ((mapped_error as u32) << (i32::BITS - 1)) as i32 >> (i32::BITS - 1);
+    sign ^ (mapped_error >> 1)
+}
+
+/// Computes RANGE, the number of values of a quantized prediction error (see ISO/IEC 14495-1, A.2.1).
+pub(crate) fn compute_range(maximum_sample_value: i32, near_lossless: i32) -> i32 {
+    (maximum_sample_value + 2 * near_lossless) / (2 * near_lossless + 1) + 1
+}
+
+/// Returns the initial value of the context variables A\[Q\], max(2, (RANGE + 32) / 64), of a scan
+/// with the given MAXVAL and NEAR (see ISO/IEC 14495-1, A.2.1, code segment A.1).
+pub fn initial_a_value(maximum_sample_value: u32, near_lossless: u8) -> u32 {
+    let range = compute_range(maximum_sample_value as i32, near_lossless as i32);
+    initialization_value_for_a(range) as u32
+}
+
+pub(crate) fn log2_ceil(n: i32) -> i32 {
+    let mut x = 0;
+    while n > (1 << x) {
+        x += 1;
+    }
+
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRADIENTS: [i32; 19] = [-30, -21, -20, -10, -7, -6, -3, -2, -1, 0, 1, 2, 3, 6, 7, 10, 20, 21, 30];
+
+    #[test]
+    fn quantize_gradient_with_default_thresholds() {
+        // The default thresholds of 8 bit lossless images.
+        let quantized: Vec<i8> = GRADIENTS.iter().map(|&di| quantize_gradient(di, 3, 7, 21, 0)).collect();
+
+        assert_eq!(quantized, [-4, -4, -3, -3, -3, -2, -2, -1, -1, 0, 1, 1, 2, 2, 3, 3, 3, 4, 4]);
+    }
+
+    #[test]
+    fn quantize_gradient_with_thresholds_of_lse_segment() {
+        let quantized: Vec<i8> = GRADIENTS.iter().map(|&di| quantize_gradient(di, 2, 10, 30, 0)).collect();
+
+        assert_eq!(quantized, [-4, -3, -3, -3, -2, -2, -2, -2, -1, 0, 1, 2, 2, 2, 2, 3, 3, 3, 4]);
+    }
+
+    #[test]
+    fn quantize_gradient_near_lossless_maps_near_range_to_0() {
+        let quantized: Vec<i8> = GRADIENTS.iter().map(|&di| quantize_gradient(di, 5, 10, 30, 2)).collect();
+
+        assert_eq!(quantized, [-4, -3, -3, -3, -2, -2, -1, 0, 0, 0, 0, 0, 1, 2, 2, 3, 3, 3, 4]);
+    }
+
+    #[test]
+    fn fill_quantization_lut_uses_thresholds_of_preset_coding_parameters() {
+        let preset_coding_parameters = JpeglsPcParameters {
+            maximum_sample_value: 255,
+            threshold1: 2,
+            threshold2: 10,
+            threshold3: 30,
+            reset_value: 64,
+        };
+        let mut quantization_lut = Vec::new();
+        fill_quantization_lut(&mut quantization_lut, &preset_coding_parameters, 0);
+
+        assert_eq!(quantization_lut.len(), 2 * 255 + 1);
+        for di in GRADIENTS {
+            assert_eq!(quantization_lut[(di + 255) as usize], quantize_gradient(di, 2, 10, 30, 0));
+        }
+    }
+
+    #[test]
+    fn map_error_value_interleaves_negative_and_positive_values() {
+        // MErrval = 2 * Errval for Errval >= 0, -2 * Errval - 1 otherwise (ISO/IEC 14495-1, A.5.2).
+        let mapped: Vec<i32> = [0, -1, 1, -2, 2, -3, 3].into_iter().map(map_error_value).collect();
+        assert_eq!(mapped, [0, 1, 2, 3, 4, 5, 6]);
+
+        for error_value in -300..=300 {
+            let expected = if error_value >= 0 { 2 * error_value } else { -2 * error_value - 1 };
+            assert_eq!(map_error_value(error_value), expected);
+        }
+    }
+
+    #[test]
+    fn unmap_error_value_inverts_map_error_value() {
+        for error_value in -300..=300 {
+            assert_eq!(unmap_error_value(map_error_value(error_value)), error_value);
+        }
+    }
+
+    #[test]
+    fn initial_a_value_of_lossless_scans() {
+        // RANGE = MAXVAL + 1.
+        assert_eq!(initial_a_value(3, 0), 2); // (4 + 32) / 64 = 0
+        assert_eq!(initial_a_value(255, 0), 4); // (256 + 32) / 64
+        assert_eq!(initial_a_value(4095, 0), 64); // (4096 + 32) / 64
+        assert_eq!(initial_a_value(65535, 0), 1024); // (65536 + 32) / 64
+    }
+
+    #[test]
+    fn initial_a_value_of_near_lossless_scans() {
+        // RANGE = (MAXVAL + 2 * NEAR) / (2 * NEAR + 1) + 1.
+        assert_eq!(initial_a_value(255, 3), 2); // RANGE = 38
+        assert_eq!(initial_a_value(4095, 1), 21); // RANGE = 1366
+        assert_eq!(initial_a_value(65535, 10), 49); // RANGE = 3122
+        assert_eq!(initial_a_value(65535, 255), 2); // RANGE = 130
+    }
+}
@@ -0,0 +1,161 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+const DEFAULT_THRESHOLD1: i32 = 3; // BASIC_T1
+const DEFAULT_THRESHOLD2: i32 = 7; // BASIC_T2
+const DEFAULT_THRESHOLD3: i32 = 21; // BASIC_T3
+const DEFAULT_RESET_VALUE: i32 = 64; // Default RESET value as defined in ISO/IEC 14495-1, table C.2
+
+/// The JPEG-LS preset coding parameters (see ISO/IEC 14495-1, C.2.4.1.1).
+/// A value of 0 means that the default value should be used.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct JpeglsPcParameters {
+    pub(crate) maximum_sample_value: i32,
+    pub(crate) threshold1: i32,
+    pub(crate) threshold2: i32,
+    pub(crate) threshold3: i32,
+    pub(crate) reset_value: i32,
+}
+
+impl JpeglsPcParameters {
+    /// Computes the default parameters as defined by ISO/IEC 14495-1, C.2.4.1.1.1
+    pub(crate) fn compute_default(maximum_sample_value: i32, near_lossless: i32) -> JpeglsPcParameters {
+        debug_assert!(maximum_sample_value <= u16::MAX as i32);
+        debug_assert!(near_lossless >= 0 && near_lossless <= compute_maximum_near_lossless(maximum_sample_value));
+
+        if maximum_sample_value >= 128 {
+            let factor = (maximum_sample_value.min(4095) + 128) / 256;
+            let threshold1 = clamp(factor * (DEFAULT_THRESHOLD1 - 2) + 2 + 3 * near_lossless,
+                                   near_lossless + 1, maximum_sample_value);
+            let threshold2 = clamp(factor * (DEFAULT_THRESHOLD2 - 3) + 3 + 5 * near_lossless,
+                                   threshold1, maximum_sample_value);
+            let threshold3 = clamp(factor * (DEFAULT_THRESHOLD3 - 4) + 4 + 7 * near_lossless,
+                                   threshold2, maximum_sample_value);
+
+            return JpeglsPcParameters {
+                maximum_sample_value,
+                threshold1,
+                threshold2,
+                threshold3,
+                reset_value: DEFAULT_RESET_VALUE,
+            };
+        }
+
+        let factor = 256 / (maximum_sample_value + 1);
+        let threshold1 = clamp((DEFAULT_THRESHOLD1 / factor + 3 * near_lossless).max(2),
+                               near_lossless + 1, maximum_sample_value);
+        let threshold2 = clamp((DEFAULT_THRESHOLD2 / factor + 5 * near_lossless).max(3),
+                               threshold1, maximum_sample_value);
+        let threshold3 = clamp((DEFAULT_THRESHOLD3 / factor + 7 * near_lossless).max(4),
+                               threshold2, maximum_sample_value);
+
+        JpeglsPcParameters {
+            maximum_sample_value,
+            threshold1,
+            threshold2,
+            threshold3,
+            reset_value: DEFAULT_RESET_VALUE,
+        }
+    }
+
+    /// Validates the parameters against the limits of ISO/IEC 14495-1, C.2.4.1.1 and
+    /// returns the parameters with all default (0) values replaced by the computed defaults.
+    pub(crate) fn validate(&self, maximum_component_value: i32, near_lossless: i32) -> Option<JpeglsPcParameters> {
+        if self.maximum_sample_value != 0
+            && (self.maximum_sample_value < 1 || self.maximum_sample_value > maximum_component_value) {
+            return None;
+        }
+
+        let maximum_sample_value = if self.maximum_sample_value != 0 {
+            self.maximum_sample_value
+        } else {
+            maximum_component_value
+        };
+
+        if self.threshold1 != 0
+            && (self.threshold1 < near_lossless + 1 || self.threshold1 > maximum_sample_value) {
+            return None;
+        }
+
+        let defaults = JpeglsPcParameters::compute_default(maximum_sample_value, near_lossless);
+        let threshold1 = if self.threshold1 != 0 { self.threshold1 } else { defaults.threshold1 };
+        if self.threshold2 != 0 && (self.threshold2 < threshold1 || self.threshold2 > maximum_sample_value) {
+            return None;
+        }
+
+        let threshold2 = if self.threshold2 != 0 { self.threshold2 } else { defaults.threshold2 };
+        if self.threshold3 != 0 && (self.threshold3 < threshold2 || self.threshold3 > maximum_sample_value) {
+            return None;
+        }
+
+        if self.reset_value != 0
+            && (self.reset_value < 3 || self.reset_value > maximum_sample_value.max(255)) {
+            return None;
+        }
+
+        Some(JpeglsPcParameters {
+            maximum_sample_value,
+            threshold1,
+            threshold2,
+            threshold3: if self.threshold3 != 0 { self.threshold3 } else { defaults.threshold3 },
+            reset_value: if self.reset_value != 0 { self.reset_value } else { defaults.reset_value },
+        })
+    }
+}
+
+/// The maximum NEAR value for a given MAXVAL, as defined by ISO/IEC 14495-1, C.2.3
+pub(crate) fn compute_maximum_near_lossless(maximum_sample_value: i32) -> i32 {
+    (maximum_sample_value / 2).min(255)
+}
+
+fn clamp(i: i32, j: i32, maximum_sample_value: i32) -> i32 {
+    if i > maximum_sample_value || i < j {
+        j
+    } else {
+        i
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compute_default_for_8_bit() {
+        let parameters = JpeglsPcParameters::compute_default(255, 0);
+
+        assert_eq!(parameters, JpeglsPcParameters {
+            maximum_sample_value: 255,
+            threshold1: 3,
+            threshold2: 7,
+            threshold3: 21,
+            reset_value: 64,
+        });
+    }
+
+    #[test]
+    fn compute_default_for_4_bit() {
+        let parameters = JpeglsPcParameters::compute_default(15, 0);
+
+        assert_eq!(parameters, JpeglsPcParameters {
+            maximum_sample_value: 15,
+            threshold1: 2,
+            threshold2: 3,
+            threshold3: 4,
+            reset_value: 64,
+        });
+    }
+
+    #[test]
+    fn compute_default_for_2_bit_near_lossless() {
+        let parameters = JpeglsPcParameters::compute_default(3, 1);
+
+        assert_eq!(parameters, JpeglsPcParameters {
+            maximum_sample_value: 3,
+            threshold1: 3,
+            threshold2: 3,
+            threshold3: 3,
+            reset_value: 64,
+        });
+    }
+}
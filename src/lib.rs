@@ -1,7 +1,75 @@
 // Copyright (c) Team CharLS.
 // SPDX-License-Identifier: BSD-3-Clause
 
+#[cfg(feature = "async")]
+mod async_decoder;
+mod bit_reader;
+mod bit_writer;
+mod channel_order;
+mod coding_variant;
+mod color_interpretation;
+mod color_transformation;
+mod constants;
+mod crc32;
+#[cfg(feature = "ndarray")]
+mod decoded_array;
 mod decoder;
-mod jpeg_stream_reader;
-mod jpeg_marker_code;
+mod decoder_buffers;
 mod decoding_error;
+mod encoder;
+mod encoding_error;
+mod endianness;
+mod error_policy;
+mod frame_info;
+mod header_info;
+mod interleave_mode;
+mod jpeg_marker_code;
+mod jpeg_stream_reader;
+mod jpeg_stream_writer;
+mod jpegls_algorithm;
+mod jpegls_preset_coding_parameters;
+mod mapping_table;
+mod marker_iter;
+mod reader_state_public;
+mod regular_mode_context;
+mod run_mode_context;
+mod scan_decoder;
+mod scan_encoder;
+mod scan_info;
+mod scan_state;
+#[cfg(any(feature = "simd", test))]
+mod simd_algorithm;
+mod spiff_header;
+mod table_set;
+mod tile_decoder;
+
+#[cfg(feature = "async")]
+pub use async_decoder::{AsyncDecoder, AsyncRead};
+pub use channel_order::ChannelOrder;
+pub use coding_variant::CodingVariant;
+pub use color_interpretation::ColorInterpretation;
+pub use color_transformation::ColorTransformation;
+pub use constants::{
+    maximum_sample_value, MAXIMUM_BITS_PER_SAMPLE, MAXIMUM_COMPONENT_COUNT, MINIMUM_BITS_PER_SAMPLE,
+};
+#[cfg(feature = "ndarray")]
+pub use decoded_array::DecodedArray;
+pub use decoder::Decoder;
+pub use decoder_buffers::DecoderBuffers;
+pub use decoding_error::DecodingError;
+pub use encoder::Encoder;
+pub use encoding_error::EncodingError;
+pub use endianness::Endianness;
+pub use error_policy::ErrorPolicy;
+pub use frame_info::{FrameInfo, Height, Width};
+pub use header_info::{is_jpegls, read_header, HeaderInfo};
+pub use interleave_mode::{reinterleave, InterleaveMode};
+pub use jpeg_marker_code::JpegMarkerCode;
+pub use jpegls_algorithm::initial_a_value;
+pub use mapping_table::MappingTable;
+pub use marker_iter::MarkerIter;
+pub use reader_state_public::ReaderStatePublic;
+pub use scan_info::ScanInfo;
+pub use spiff_header::SpiffHeader;
+pub use table_set::{read_tables, TableSet};
+pub use tile_decoder::TileDecoder;
@@ -0,0 +1,53 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+/// A mapping table (palette) defined by a JPEG-LS preset parameters segment (see ISO/IEC 14495-1,
+/// C.2.4.1.2): the decoded samples of a component that uses the table are indices of its entries.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MappingTable {
+    pub(crate) table_id: u8,
+    pub(crate) entry_size: u8,
+    pub(crate) data: Vec<u8>,
+}
+
+impl MappingTable {
+    /// The identifier (TID) of the table, which the SOS segment uses to select it.
+    pub fn table_id(&self) -> u8 {
+        self.table_id
+    }
+
+    /// The size in bytes of every entry (Wt).
+    pub fn entry_size(&self) -> u8 {
+        self.entry_size
+    }
+
+    /// The number of entries of the table.
+    pub fn entry_count(&self) -> usize {
+        self.data.len() / self.entry_size as usize
+    }
+
+    /// The entries of the table, stored one after the other.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the entry at index, or None when the index is outside the table.
+    pub fn entry(&self, index: usize) -> Option<&[u8]> {
+        let entry_size = self.entry_size as usize;
+        self.data.get(index * entry_size..(index + 1) * entry_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_of_rgb_table() {
+        let table = MappingTable { table_id: 1, entry_size: 3, data: vec![1, 2, 3, 4, 5, 6] };
+
+        assert_eq!(table.entry_count(), 2);
+        assert_eq!(table.entry(1), Some(&[4, 5, 6][..]));
+        assert_eq!(table.entry(2), None);
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use std::io::Read;
+use std::ops::Range;
+
+use crate::decoding_error::DecodingError;
+use crate::jpeg_marker_code::JpegMarkerCode;
+use crate::jpeg_stream_reader::{JpegStreamReader, JPEG_MARKER_START_BYTE};
+
+/// Iterates over the markers of a stream without interpreting the segments, see Decoder::markers.
+/// Every item is a marker with the byte range of the marker and its segment. The entropy coded
+/// data that follows an SOS segment is skipped and not part of any range. The iteration ends
+/// after the EOI marker or the first error.
+#[derive(Debug)]
+pub struct MarkerIter<'a, R: Read> {
+    reader: &'a mut JpegStreamReader<R>,
+    in_entropy_coded_data: bool,
+    done: bool,
+}
+
+impl<'a, R: Read> MarkerIter<'a, R> {
+    pub(crate) fn new(reader: &'a mut JpegStreamReader<R>) -> MarkerIter<'a, R> {
+        MarkerIter {
+            reader,
+            in_entropy_coded_data: false,
+            done: false,
+        }
+    }
+
+    fn read_marker(&mut self) -> Result<(JpegMarkerCode, Range<u64>), DecodingError> {
+        if self.in_entropy_coded_data {
+            self.skip_entropy_coded_data()?;
+        }
+
+        let start = self.reader.position();
+        let marker_code = self.reader.read_next_marker_code()?;
+        if marker_code.segment_has_length() {
+            let segment_size = self.reader.read_segment_size()?;
+            self.reader.skip_bytes(segment_size)?;
+        }
+
+        self.in_entropy_coded_data = marker_code == JpegMarkerCode::StartOfScan;
+        self.done = marker_code == JpegMarkerCode::EndOfImage;
+        Ok((marker_code, start..self.reader.position()))
+    }
+
+    /// Skips bytes up to the next marker: a 0xFF byte followed by a byte with the high bit set.
+    /// In entropy coded data a 0xFF byte is followed by a byte with a stuffed 0 bit (see ISO/IEC 14495-1, A.1).
+    fn skip_entropy_coded_data(&mut self) -> Result<(), DecodingError> {
+        loop {
+            match (self.reader.peek_byte(0)?, self.reader.peek_byte(1)?) {
+                (Some(JPEG_MARKER_START_BYTE), Some(next)) if next & 0x80 != 0 => return Ok(()),
+                (Some(_), _) => self.reader.skip_byte(),
+                (None, _) => return Err(DecodingError::UnexpectedEndOfStream),
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for MarkerIter<'_, R> {
+    type Item = Result<(JpegMarkerCode, Range<u64>), DecodingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = self.read_marker();
+        if result.is_err() {
+            self.done = true;
+        }
+
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::Decoder;
+    use crate::encoder::Encoder;
+    use crate::frame_info::{FrameInfo, Height, Width};
+    use crate::spiff_header::SpiffHeader;
+
+    fn encode_with_spiff_header() -> Vec<u8> {
+        let frame_info = FrameInfo::new(Width(8), Height(8), 8, 1);
+        let mut encoder = Encoder::new(frame_info.clone());
+        encoder.write_spiff_header(SpiffHeader::new(&frame_info, 8)).unwrap();
+        let source: Vec<u8> = (0..64).map(|index| (index * 29 % 256) as u8).collect();
+        encoder.encode(&source).unwrap()
+    }
+
+    #[test]
+    fn markers_of_stream_with_spiff_header() {
+        let encoded = encode_with_spiff_header();
+        let mut decoder = Decoder::new(encoded.as_slice());
+
+        let markers: Vec<(JpegMarkerCode, Range<u64>)> = decoder.markers().map(|marker| marker.unwrap()).collect();
+
+        let end = encoded.len() as u64;
+        assert_eq!(markers, [
+            (JpegMarkerCode::StartOfImage, 0..2),
+            (JpegMarkerCode::ApplicationData8, 2..36),
+            (JpegMarkerCode::ApplicationData8, 36..46),
+            (JpegMarkerCode::StartOfFrameJpegls, 46..59),
+            (JpegMarkerCode::StartOfScan, 59..69),
+            (JpegMarkerCode::EndOfImage, end - 2..end),
+        ]);
+    }
+
+    #[test]
+    fn markers_of_truncated_stream_ends_with_error() {
+        let encoded = encode_with_spiff_header();
+        let truncated = &encoded[..encoded.len() - 2];
+        let mut decoder = Decoder::new(truncated);
+
+        let markers: Vec<_> = decoder.markers().collect();
+
+        assert_eq!(markers.len(), 6);
+        assert_eq!(markers[5], Err(DecodingError::UnexpectedEndOfStream));
+    }
+}
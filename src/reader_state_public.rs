@@ -0,0 +1,15 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+/// The position of the decoder in the structure of a JPEG-LS stream (see ISO/IEC 14495-1, annex C),
+/// for example to report where parsing stopped after an error.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReaderStatePublic {
+    BeforeStartOfImage, // Nothing has been read yet.
+    HeaderSection,      // The SOI marker has been read, the SOF segment is expected.
+    SpiffHeaderSection, // The SPIFF header has been read, its directory entries are expected.
+    FrameSection,       // The SOF segment has been read, the first SOS segment is expected.
+    ScanSection,        // The entropy coded data of a scan has been read, the next SOS segment is expected.
+    BitStreamSection,   // A SOS segment has been read, the entropy coded data of its scan follows.
+    AfterEndOfImage,    // The EOI marker has been read.
+}
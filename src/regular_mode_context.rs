@@ -0,0 +1,234 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use crate::decoding_error::DecodingError;
+
+const MAX_K_VALUE: i32 = 16; // This is an implementation limit (theoretical limit is 32)
+
+const MAX_C: i32 = 127; // Maximum allowed value of C[0..364]. ISO 14495-1, section 3.3
+const MIN_C: i32 = -128; // Minimum allowed value of C[0..364]. ISO 14495-1, section 3.3
+
+/// The context variables A, B, C and N used in the regular mode (see ISO/IEC 14495-1, A.2.2).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct RegularModeContext {
+    a: i32,
+    b: i32,
+    c: i32,
+    n: i32,
+}
+
+impl RegularModeContext {
+    pub(crate) fn new(range: i32) -> RegularModeContext {
+        RegularModeContext {
+            a: initialization_value_for_a(range),
+            b: 0,
+            c: 0,
+            n: 1,
+        }
+    }
+
+    /// The bias correction value C[Q].
+    pub(crate) fn c(&self) -> i32 {
+        self.c
+    }
+
+    /// Returns the value to XOR the error with when k = 0 (see ISO/IEC 14495-1, A.5.2, code segment A.13).
+    pub(crate) fn get_error_correction(&self, k: i32) -> i32 {
+        if k != 0 {
+            return 0;
+        }
+
+        bit_wise_sign(2 * self.b + self.n - 1)
+    }
+
+    /// Updates the context variables after a sample has been coded
+    /// (see ISO/IEC 14495-1, A.6.1 and A.6.2, code segments A.12 and A.13).
+    pub(crate) fn update_variables_and_bias(&mut self, error_value: i32, near_lossless: i32,
+                                            reset_threshold: i32) -> Result<(), DecodingError> {
+        self.a += error_value.abs();
+        self.b += error_value * (2 * near_lossless + 1);
+
+        const LIMIT: i32 = 65536 * 256;
+        if self.a >= LIMIT || self.b.abs() >= LIMIT {
+            return Err(DecodingError::InvalidEncodedData);
+        }
+
+        if self.n == reset_threshold {
+            self.a >>= 1;
+            // The arithmetic shift rounds towards minus infinity, which is identical to
+            // B[Q] = -((1 - B[Q]) >> 1) for negative values of B[Q].
+            self.b >>= 1;
+            self.n >>= 1;
+        }
+
+        self.n += 1;
+
+        // Keeps B[Q] in the range -N[Q] + 1..=0 and C[Q] in the range MIN_C..=MAX_C.
+        if self.b <= -self.n {
+            self.b += self.n;
+            if self.c > MIN_C {
+                self.c -= 1;
+            }
+            if self.b <= -self.n {
+                self.b = -self.n + 1;
+            }
+        } else if self.b > 0 {
+            self.b -= self.n;
+            if self.c < MAX_C {
+                self.c += 1;
+            }
+            if self.b > 0 {
+                self.b = 0;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Computes the Golomb coding parameter k (see ISO/IEC 14495-1, A.5.1, code segment A.10).
+    pub(crate) fn get_golomb_coding_parameter(&self) -> Result<i32, DecodingError> {
+        // N[Q] is at least 2 after it has been halved, as RESET is at least 3.
+        debug_assert!(self.n > 0);
+        let mut k = 0;
+        while (self.n << k) < self.a && k < MAX_K_VALUE {
+            k += 1;
+        }
+
+        if k == MAX_K_VALUE {
+            return Err(DecodingError::InvalidEncodedData);
+        }
+
+        Ok(k)
+    }
+}
+
+/// The initial value for A[Q], as defined by ISO/IEC 14495-1, A.2.1, code segment A.1.
+pub(crate) fn initialization_value_for_a(range: i32) -> i32 {
+    ((range + 32) / 64).max(2)
+}
+
+/// Returns -1 for negative values and 0 otherwise.
+pub(crate) fn bit_wise_sign(i: i32) -> i32 {
+    i >> (i32::BITS - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESET_THRESHOLD: i32 = 64;
+
+    fn update_many_times(context: &mut RegularModeContext, error_values: impl Iterator<Item = i32>) {
+        for error_value in error_values {
+            context.update_variables_and_bias(error_value, 0, RESET_THRESHOLD).unwrap();
+
+            assert!((MIN_C..=MAX_C).contains(&context.c), "C = {}", context.c);
+            assert!(context.b > -context.n && context.b <= 0, "B = {}, N = {}", context.b, context.n);
+            assert!(context.n >= 1 && context.n <= RESET_THRESHOLD);
+        }
+    }
+
+    #[test]
+    fn bias_of_positive_errors_is_clamped_to_max_c() {
+        let mut context = RegularModeContext::new(256);
+
+        update_many_times(&mut context, std::iter::repeat_n(100, 1000));
+
+        assert_eq!(context.c(), MAX_C);
+    }
+
+    #[test]
+    fn bias_of_negative_errors_is_clamped_to_min_c() {
+        let mut context = RegularModeContext::new(256);
+
+        update_many_times(&mut context, std::iter::repeat_n(-100, 1000));
+
+        assert_eq!(context.c(), MIN_C);
+    }
+
+    #[test]
+    fn bias_update_matches_code_segment_a13() {
+        let mut context = RegularModeContext::new(256);
+        let (mut a, mut b, mut c, mut n) = (context.a, 0, 0, 1);
+
+        // A pseudo random mix of small and large errors of both signs.
+        let mut seed = 12345u32;
+        for _ in 0..10000 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let error_value = ((seed >> 16) % 61) as i32 - 25;
+            context.update_variables_and_bias(error_value, 0, RESET_THRESHOLD).unwrap();
+
+            // ISO/IEC 14495-1, A.6.1 and A.6.2, code segments A.12 and A.13.
+            b += error_value;
+            a += error_value.abs();
+            if n == RESET_THRESHOLD {
+                a >>= 1;
+                b = if b >= 0 { b >> 1 } else { -((1 - b) >> 1) };
+                n >>= 1;
+            }
+            n += 1;
+            if b <= -n {
+                b += n;
+                if c > MIN_C {
+                    c -= 1;
+                }
+                if b <= -n {
+                    b = -n + 1;
+                }
+            } else if b > 0 {
+                b -= n;
+                if c < MAX_C {
+                    c += 1;
+                }
+                if b > 0 {
+                    b = 0;
+                }
+            }
+
+            assert_eq!((context.a, context.b, context.c, context.n), (a, b, c, n));
+        }
+    }
+
+    /// The values of A, B, C and N.
+    type ContextState = (i32, i32, i32, i32);
+
+    fn check_states(near_lossless: i32, reset_threshold: i32, steps: &[(i32, ContextState)]) {
+        let mut context = RegularModeContext::new(256);
+        for &(error_value, expected) in steps {
+            context.update_variables_and_bias(error_value, near_lossless, reset_threshold).unwrap();
+
+            assert_eq!((context.a, context.b, context.c, context.n), expected, "after error value {}", error_value);
+        }
+    }
+
+    #[test]
+    fn bias_update_of_hand_computed_sequence() {
+        // (error value, (A, B, C, N) after the update), starting at A = 4, B = 0, C = 0, N = 1.
+        check_states(0, RESET_THRESHOLD, &[
+            (3, (7, 0, 1, 2)),     // B > 0: B -= N, C += 1 and B is clamped to 0.
+            (-5, (12, -2, 0, 3)),  // B <= -N: B += N and C -= 1.
+            (-1, (13, -3, 0, 4)),  // -N < B <= 0: no change of C.
+            (-2, (15, 0, -1, 5)),  // B = -N is already a correction.
+            (0, (15, 0, -1, 6)),
+            (1, (16, -6, 0, 7)),   // B = 1 > 0: B -= N without clamping.
+        ]);
+    }
+
+    #[test]
+    fn bias_update_of_near_lossless_error_is_scaled() {
+        // B is updated with Errval * (2 * NEAR + 1).
+        check_states(1, RESET_THRESHOLD, &[
+            (1, (5, 0, 1, 2)),
+            (-2, (7, -2, 0, 3)),  // B = -6 + N is still <= -N: clamped to -N + 1.
+        ]);
+    }
+
+    #[test]
+    fn bias_update_halves_negative_b_towards_minus_infinity_at_reset() {
+        check_states(0, 3, &[
+            (-1, (5, -1, 0, 2)),
+            (-1, (6, -2, 0, 3)),
+            (-1, (3, 0, -1, 2)),  // N = RESET: A = 7 >> 1, B = -((1 - -3) >> 1) = -2, N = 1, then N = 2 and B <= -N.
+        ]);
+    }
+}
@@ -0,0 +1,119 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use crate::regular_mode_context::initialization_value_for_a;
+
+/// The context variables used for the run interruption samples (see ISO/IEC 14495-1, A.7.2).
+/// Context 365 is used with RItype 0 and context 366 with RItype 1.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct RunModeContext {
+    run_interruption_type: i32,
+    a: i32,
+    n: i32,
+    nn: i32,
+}
+
+impl RunModeContext {
+    pub(crate) fn new(run_interruption_type: i32, range: i32) -> RunModeContext {
+        RunModeContext {
+            run_interruption_type,
+            a: initialization_value_for_a(range),
+            n: 1,
+            nn: 0,
+        }
+    }
+
+    pub(crate) fn run_interruption_type(&self) -> i32 {
+        self.run_interruption_type
+    }
+
+    /// Computes the Golomb coding parameter k (see ISO/IEC 14495-1, A.7.2.1, code segment A.21).
+    pub(crate) fn get_golomb_code(&self) -> i32 {
+        let temp = self.a + (self.n >> 1) * self.run_interruption_type;
+        let mut n_test = self.n;
+        let mut k = 0;
+        while n_test < temp {
+            n_test <<= 1;
+            k += 1;
+        }
+
+        k
+    }
+
+    /// Updates the context variables after a run interruption sample has been coded
+    /// (see ISO/IEC 14495-1, A.7.2.2, code segment A.23).
+    pub(crate) fn update_variables(&mut self, error_value: i32, e_mapped_error_value: i32, reset_threshold: i32) {
+        if error_value < 0 {
+            self.nn += 1;
+        }
+
+        self.a += (e_mapped_error_value + 1 - self.run_interruption_type) >> 1;
+
+        if self.n == reset_threshold {
+            self.a >>= 1;
+            self.n >>= 1;
+            self.nn >>= 1;
+        }
+
+        self.n += 1;
+    }
+
+    /// Computes the map bit that is subtracted from 2 * |Errval| - RItype to get EMErrval
+    /// (see ISO/IEC 14495-1, A.7.2.1, code segment A.22).
+    pub(crate) fn compute_map(&self, error_value: i32, k: i32) -> bool {
+        if k == 0 && error_value > 0 && 2 * self.nn < self.n {
+            return true;
+        }
+
+        error_value < 0 && (2 * self.nn >= self.n || k != 0)
+    }
+
+    /// Reconstructs the error value from the mapped value EMErrval + RItype
+    /// (see ISO/IEC 14495-1, A.7.2.2, inverse of code segment A.22).
+    pub(crate) fn compute_error_value(&self, temp: i32, k: i32) -> i32 {
+        let map = (temp & 1) != 0;
+        let error_value_abs = (temp + map as i32) / 2;
+
+        if (k != 0 || (2 * self.nn >= self.n)) == map {
+            return -error_value_abs;
+        }
+
+        error_value_abs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compute_error_value_inverts_error_mapping() {
+        // EMErrval = 2 * |Errval| - RItype - map (see ISO/IEC 14495-1, A.7.2.2, code segment A.22)
+        for run_interruption_type in 0..2 {
+            let context = RunModeContext::new(run_interruption_type, 256);
+            let k = context.get_golomb_code();
+            for error_value in -10..=10i32 {
+                if error_value == 0 && run_interruption_type == 0 {
+                    continue; // Errval 0 cannot occur with RItype 0.
+                }
+
+                let map = context.compute_map(error_value, k);
+                let e_mapped_error_value = 2 * error_value.abs() - run_interruption_type - map as i32;
+
+                assert_eq!(context.compute_error_value(e_mapped_error_value + run_interruption_type, k), error_value);
+            }
+        }
+    }
+
+    #[test]
+    fn get_golomb_code_includes_half_n_for_run_interruption_type_1() {
+        let mut context = RunModeContext::new(1, 256);
+        assert_eq!(context.get_golomb_code(), 2);
+
+        context.update_variables(0, 10, 64);
+
+        assert_eq!(context.a, 9);
+        assert_eq!(context.n, 2);
+        assert_eq!(context.get_golomb_code(), 3);
+    }
+}
@@ -0,0 +1,448 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use std::fmt;
+use std::io::Read;
+
+use crate::bit_reader::BitReader;
+use crate::constants::MAXIMUM_COMPONENT_COUNT_IN_SCAN;
+use crate::decoder_buffers::DecoderBuffers;
+use crate::decoding_error::DecodingError;
+use crate::frame_info::FrameInfo;
+use crate::interleave_mode::InterleaveMode;
+use crate::jpegls_algorithm::{apply_sign, get_predicted_value, sign, unmap_error_value, REGULAR_MODE_CONTEXT_COUNT};
+use crate::jpegls_preset_coding_parameters::JpeglsPcParameters;
+use crate::regular_mode_context::bit_wise_sign;
+use crate::scan_state::ScanState;
+
+/// A function that is called after every decoded line of a scan with the index of the line.
+pub(crate) struct RowCallback(pub(crate) Box<dyn FnMut(u32) + Send + Sync>);
+
+impl fmt::Debug for RowCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RowCallback")
+    }
+}
+
+/// Decodes the entropy coded data of a single scan (see ISO/IEC 14495-1, A.1 and annex A).
+pub(crate) struct ScanDecoder {
+    width: usize,
+    height: usize,
+    component_count: usize,
+    interleave_mode: InterleaveMode,
+    bytes_per_sample: usize,
+    state: ScanState,
+    line_contexts: Vec<i32>,
+    lines: Vec<i32>,
+    row_callback: Option<RowCallback>,
+    decoded_line_count: u32,
+}
+
+impl ScanDecoder {
+    /// Creates a decoder for a scan of a frame. component_count is the number of components in the
+    /// scan. The decoder uses the memory of buffers and calls row_callback after every line.
+    pub(crate) fn new(frame_info: &FrameInfo, component_count: u8, interleave_mode: InterleaveMode,
+                      near_lossless: u8, preset_coding_parameters: &JpeglsPcParameters,
+                      buffers: DecoderBuffers, row_callback: Option<RowCallback>) -> ScanDecoder {
+        // The reader rejects scans without components, which would leave the decode loops empty.
+        debug_assert!(component_count > 0);
+        let width = frame_info.width();
+        let state = ScanState::with_buffers(preset_coding_parameters, near_lossless, component_count as usize,
+                                            buffers.regular_mode_contexts, buffers.quantization_lut);
+        let mut line_contexts = buffers.line_contexts;
+        line_contexts.resize(width as usize, 0);
+
+        ScanDecoder {
+            width: width as usize,
+            height: frame_info.height() as usize,
+            component_count: component_count as usize,
+            interleave_mode,
+            bytes_per_sample: frame_info.bytes_per_sample(),
+            state,
+            line_contexts,
+            lines: buffers.lines,
+            row_callback,
+            decoded_line_count: 0,
+        }
+    }
+
+    /// Returns the row callback that was passed to new, for the next scan.
+    pub(crate) fn take_row_callback(&mut self) -> Option<RowCallback> {
+        self.row_callback.take()
+    }
+
+    /// Returns the memory used by the decoder, for reuse by the next scan.
+    pub(crate) fn into_buffers(self) -> DecoderBuffers {
+        let (regular_mode_contexts, quantization_lut) = self.state.into_buffers();
+        DecoderBuffers {
+            lines: self.lines,
+            line_contexts: self.line_contexts,
+            regular_mode_contexts,
+            quantization_lut,
+            // The output buffer of decode_borrowed is held by the Decoder while it decodes.
+            output: Vec::new(),
+        }
+    }
+
+    /// The number of lines of the scan that have been completely decoded.
+    pub(crate) fn decoded_line_count(&self) -> u32 {
+        self.decoded_line_count
+    }
+
+    /// Decodes the scan into destination. Line n is stored at offset n * stride, the samples of
+    /// the components in a line are stored interleaved. With stride 0 every line overwrites the
+    /// previous one at offset 0.
+    pub(crate) fn decode<R: Read>(&mut self, bit_reader: &mut BitReader<R>, destination: &mut [u8],
+                                  stride: usize) -> Result<(), DecodingError> {
+        self.decode_band(bit_reader, destination, stride, self.height)
+    }
+
+    /// Decodes the next line_count lines of the scan into destination, the first of them at
+    /// offset 0. The previous line and the context variables are kept between calls, so a scan
+    /// can be decoded in bands without storing all its lines.
+    pub(crate) fn decode_band<R: Read>(&mut self, bit_reader: &mut BitReader<R>, destination: &mut [u8],
+                                       stride: usize, line_count: usize) -> Result<(), DecodingError> {
+        debug_assert!(stride >= self.row_length() || stride == 0);
+        debug_assert!(line_count > 0 && self.decoded_line_count as usize + line_count <= self.height);
+        debug_assert!(destination.len() >= (line_count - 1) * stride + self.row_length());
+        match self.interleave_mode {
+            InterleaveMode::None | InterleaveMode::Line => {
+                self.decode_lines(bit_reader, destination, stride, line_count)
+            }
+            InterleaveMode::Sample => self.decode_sample_interleaved_lines(bit_reader, destination, stride, line_count),
+        }
+    }
+
+    /// The number of bytes of a decoded line.
+    fn row_length(&self) -> usize {
+        self.width * self.component_count * self.bytes_per_sample
+    }
+
+    /// Returns the lines buffer with 2 * line_count lines of line_size samples: the previous lines
+    /// and the current lines. They are zero initialized before the first line of the scan.
+    fn take_lines(&mut self, line_count: usize, line_size: usize) -> Vec<i32> {
+        let mut lines = std::mem::take(&mut self.lines);
+        if self.decoded_line_count == 0 {
+            lines.clear();
+            lines.resize(2 * line_count * line_size, 0);
+        }
+        lines
+    }
+
+    fn decode_lines<R: Read>(&mut self, bit_reader: &mut BitReader<R>, destination: &mut [u8],
+                             stride: usize, line_count: usize) -> Result<(), DecodingError> {
+        let line_size = self.width + 2;
+        let mut lines = self.take_lines(self.component_count, line_size);
+        let result = self.decode_lines_with(bit_reader, destination, stride, line_count, &mut lines, line_size);
+        self.lines = lines;
+        result
+    }
+
+    fn decode_lines_with<R: Read>(&mut self, bit_reader: &mut BitReader<R>, destination: &mut [u8], stride: usize,
+                                  line_count: usize, lines: &mut [i32], line_size: usize) -> Result<(), DecodingError> {
+        let (mut previous_lines, mut current_lines) = lines.split_at_mut(self.component_count * line_size);
+        // The halves are swapped after every line: after an odd number of lines the second half
+        // holds the previous lines.
+        if self.decoded_line_count % 2 == 1 {
+            std::mem::swap(&mut previous_lines, &mut current_lines);
+        }
+        let row_length = self.row_length();
+
+        for line in 0..line_count {
+            for component in 0..self.component_count {
+                let previous_line = &mut previous_lines[component * line_size..(component + 1) * line_size];
+                let current_line = &mut current_lines[component * line_size..(component + 1) * line_size];
+
+                // Initialize the edge pixels used for prediction (see ISO/IEC 14495-1, A.2.1): Rd of
+                // the last sample and Ra of the first sample are equal to Rb.
+                previous_line[self.width + 1] = previous_line[self.width];
+                current_line[0] = previous_line[1];
+
+                self.decode_line(bit_reader, component, previous_line, current_line)?;
+            }
+
+            let row = &mut destination[line * stride..line * stride + row_length];
+            for (component, current_line) in current_lines.chunks_exact(line_size).enumerate() {
+                for x in 0..self.width {
+                    self.store_sample(row, x * self.component_count + component, current_line[x + 1]);
+                }
+            }
+
+            std::mem::swap(&mut previous_lines, &mut current_lines);
+            self.end_line();
+        }
+
+        Ok(())
+    }
+
+    fn end_line(&mut self) {
+        if let Some(RowCallback(callback)) = &mut self.row_callback {
+            callback(self.decoded_line_count);
+        }
+
+        self.decoded_line_count += 1;
+    }
+
+    fn decode_line<R: Read>(&mut self, bit_reader: &mut BitReader<R>, component: usize,
+                            previous_line: &[i32], current_line: &mut [i32]) -> Result<(), DecodingError> {
+        let mut index = 0;
+        let mut rb = previous_line[0];
+        let mut rd = previous_line[1];
+        self.state.compute_line_contexts(previous_line, &mut self.line_contexts);
+
+        while index < self.width {
+            let ra = current_line[index];
+            let rc = rb;
+            rb = rd;
+            rd = previous_line[index + 2];
+
+            let qs = self.state.complete_context_id(self.line_contexts[index], ra, rc);
+            if qs != 0 {
+                current_line[index + 1] = self.decode_regular(bit_reader, qs, get_predicted_value(ra, rb, rc))?;
+                index += 1;
+            } else {
+                index += self.decode_run_mode(bit_reader, component, index, previous_line, current_line)?;
+                rb = previous_line[index];
+                rd = previous_line[index + 1];
+            }
+        }
+
+        Ok(())
+    }
+
+    fn decode_run_mode<R: Read>(&mut self, bit_reader: &mut BitReader<R>, component: usize, start_index: usize,
+                                previous_line: &[i32], current_line: &mut [i32]) -> Result<usize, DecodingError> {
+        let ra = current_line[start_index];
+        let run_length = self.decode_run_pixels(bit_reader, component, self.width - start_index)?;
+        for sample in &mut current_line[start_index + 1..start_index + 1 + run_length] {
+            *sample = ra;
+        }
+
+        let end_index = start_index + run_length;
+        if end_index == self.width {
+            return Ok(run_length);
+        }
+
+        // Run interruption
+        let rb = previous_line[end_index + 1];
+        current_line[end_index + 1] = self.decode_run_interruption_pixel(bit_reader, component, ra, rb)?;
+        self.state.decrement_run_index(component);
+        Ok(run_length + 1)
+    }
+
+    /// Decodes the length of a run (see ISO/IEC 14495-1, A.7.1, code segment A.15).
+    fn decode_run_pixels<R: Read>(&mut self, bit_reader: &mut BitReader<R>, component: usize,
+                                  pixel_count: usize) -> Result<usize, DecodingError> {
+        debug_assert!(pixel_count > 0, "run mode is never entered after the last sample of a line");
+        let mut index = 0;
+        while bit_reader.read_bit()? {
+            let count = (1 << self.state.run_length_order(component)).min(pixel_count - index);
+            index += count;
+
+            if count == (1 << self.state.run_length_order(component)) {
+                self.state.increment_run_index(component);
+            }
+
+            if index == pixel_count {
+                break;
+            }
+        }
+
+        if index != pixel_count {
+            // Incomplete run.
+            let j = self.state.run_length_order(component);
+            if j > 0 {
+                index += bit_reader.read_value(j)? as usize;
+            }
+        }
+
+        // The remainder of a corrupt stream can code a run past the end of the line.
+        if index > pixel_count {
+            return Err(DecodingError::InvalidEncodedData);
+        }
+
+        Ok(index)
+    }
+
+    fn decode_run_interruption_pixel<R: Read>(&mut self, bit_reader: &mut BitReader<R>, component: usize,
+                                              ra: i32, rb: i32) -> Result<i32, DecodingError> {
+        if (ra - rb).abs() <= self.state.near_lossless {
+            let error_value = self.decode_run_interruption_error(bit_reader, component, 1)?;
+            return Ok(self.state.compute_reconstructed_sample(ra, error_value));
+        }
+
+        let error_value = self.decode_run_interruption_error(bit_reader, component, 0)?;
+        Ok(self.state.compute_reconstructed_sample(rb, error_value * sign(rb - ra)))
+    }
+
+    /// Decodes the prediction error of a run interruption sample (see ISO/IEC 14495-1, A.7.2).
+    fn decode_run_interruption_error<R: Read>(&mut self, bit_reader: &mut BitReader<R>, component: usize,
+                                              context_index: usize) -> Result<i32, DecodingError> {
+        let k = self.state.run_mode_contexts[context_index].get_golomb_code();
+        let limit = self.state.limit - self.state.run_length_order(component) - 1;
+        let e_mapped_error_value = self.decode_value(bit_reader, k, limit)?;
+
+        let context = &mut self.state.run_mode_contexts[context_index];
+        let error_value = context.compute_error_value(e_mapped_error_value + context.run_interruption_type(), k);
+        context.update_variables(error_value, e_mapped_error_value, self.state.reset_threshold);
+        Ok(error_value)
+    }
+
+    fn decode_sample_interleaved_lines<R: Read>(&mut self, bit_reader: &mut BitReader<R>, destination: &mut [u8],
+                                                stride: usize, line_count: usize) -> Result<(), DecodingError> {
+        let line_size = (self.width + 2) * self.component_count;
+        let mut lines = self.take_lines(1, line_size);
+        let result = self.decode_sample_interleaved_lines_with(bit_reader, destination, stride, line_count, &mut lines);
+        self.lines = lines;
+        result
+    }
+
+    fn decode_sample_interleaved_lines_with<R: Read>(&mut self, bit_reader: &mut BitReader<R>,
+                                                     destination: &mut [u8], stride: usize, line_count: usize,
+                                                     lines: &mut [i32]) -> Result<(), DecodingError> {
+        let component_count = self.component_count;
+        let (mut previous_line, mut current_line) = lines.split_at_mut(lines.len() / 2);
+        if self.decoded_line_count % 2 == 1 {
+            std::mem::swap(&mut previous_line, &mut current_line);
+        }
+        let row_length = self.row_length();
+
+        for line in 0..line_count {
+            // Initialize the edge pixels used for prediction (see ISO/IEC 14495-1, A.2.1).
+            previous_line.copy_within(self.width * component_count..(self.width + 1) * component_count,
+                                      (self.width + 1) * component_count);
+            current_line[..component_count].copy_from_slice(&previous_line[component_count..2 * component_count]);
+
+            self.decode_sample_interleaved_line(bit_reader, previous_line, current_line)?;
+
+            let row = &mut destination[line * stride..line * stride + row_length];
+            for (index, sample) in current_line[component_count..(self.width + 1) * component_count]
+                .iter()
+                .enumerate() {
+                self.store_sample(row, index, *sample);
+            }
+
+            std::mem::swap(&mut previous_line, &mut current_line);
+            self.end_line();
+        }
+
+        Ok(())
+    }
+
+    fn decode_sample_interleaved_line<R: Read>(&mut self, bit_reader: &mut BitReader<R>, previous_line: &[i32],
+                                               current_line: &mut [i32]) -> Result<(), DecodingError> {
+        let component_count = self.component_count;
+        let mut qs = [0; MAXIMUM_COMPONENT_COUNT_IN_SCAN as usize];
+        let qs = &mut qs[..component_count];
+        let mut index = 0;
+
+        while index < self.width {
+            let position = (index + 1) * component_count;
+            for (component, q) in qs.iter_mut().enumerate() {
+                let ra = current_line[position - component_count + component];
+                let rc = previous_line[position - component_count + component];
+                let rb = previous_line[position + component];
+                let rd = previous_line[position + component_count + component];
+                *q = self.state.compute_context_id(ra, rb, rc, rd);
+            }
+
+            if qs.iter().all(|&q| q == 0) {
+                index += self.decode_sample_interleaved_run_mode(bit_reader, index, previous_line, current_line)?;
+            } else {
+                for (component, &q) in qs.iter().enumerate() {
+                    let ra = current_line[position - component_count + component];
+                    let rc = previous_line[position - component_count + component];
+                    let rb = previous_line[position + component];
+                    current_line[position + component] =
+                        self.decode_regular(bit_reader, q, get_predicted_value(ra, rb, rc))?;
+                }
+                index += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn decode_sample_interleaved_run_mode<R: Read>(&mut self, bit_reader: &mut BitReader<R>, start_index: usize,
+                                                   previous_line: &[i32],
+                                                   current_line: &mut [i32]) -> Result<usize, DecodingError> {
+        let component_count = self.component_count;
+        let run_length = self.decode_run_pixels(bit_reader, 0, self.width - start_index)?;
+        for index in start_index..start_index + run_length {
+            let position = (index + 1) * component_count;
+            current_line.copy_within(start_index * component_count..(start_index + 1) * component_count, position);
+        }
+
+        let end_index = start_index + run_length;
+        if end_index == self.width {
+            return Ok(run_length);
+        }
+
+        // Run interruption: all components use the run interruption context with RItype 0.
+        let position = (end_index + 1) * component_count;
+        for component in 0..component_count {
+            let ra = current_line[start_index * component_count + component];
+            let rb = previous_line[position + component];
+            let error_value = self.decode_run_interruption_error(bit_reader, 0, 0)?;
+            current_line[position + component] =
+                self.state.compute_reconstructed_sample(rb, error_value * sign(rb - ra));
+        }
+
+        self.state.decrement_run_index(0);
+        Ok(run_length + 1)
+    }
+
+    /// Decodes a sample in regular mode (see ISO/IEC 14495-1, A.4 to A.6).
+    fn decode_regular<R: Read>(&mut self, bit_reader: &mut BitReader<R>, qs: i32,
+                               predicted: i32) -> Result<i32, DecodingError> {
+        let sign = bit_wise_sign(qs);
+        let context_index = apply_sign(qs, sign) as usize;
+        // Context 0 is only coded in regular mode for a component of a sample interleaved pixel.
+        debug_assert!(context_index < REGULAR_MODE_CONTEXT_COUNT);
+        let k = self.state.regular_mode_contexts[context_index].get_golomb_coding_parameter()?;
+        let c = self.state.regular_mode_contexts[context_index].c();
+        let predicted_value = self.state.correct_prediction(predicted + apply_sign(c, sign));
+
+        let mut error_value = unmap_error_value(self.decode_value(bit_reader, k, self.state.limit)?);
+        if error_value.abs() > 65535 {
+            return Err(DecodingError::InvalidEncodedData);
+        }
+
+        let context = &mut self.state.regular_mode_contexts[context_index];
+        if k == 0 {
+            error_value ^= context.get_error_correction(self.state.near_lossless);
+        }
+
+        context.update_variables_and_bias(error_value, self.state.near_lossless, self.state.reset_threshold)?;
+        Ok(self.state.compute_reconstructed_sample(predicted_value, apply_sign(error_value, sign)))
+    }
+
+    /// Decodes a Golomb coded value, limited to limit bits (see ISO/IEC 14495-1, A.5.3). Values with
+    /// a unary part of LIMIT - qbpp - 1 or more bits are escape coded: after the unary part the
+    /// value - 1 follows in qbpp bits.
+    fn decode_value<R: Read>(&self, bit_reader: &mut BitReader<R>, k: i32, limit: i32) -> Result<i32, DecodingError> {
+        // The escape code must leave room for at least one unary coded high bit.
+        debug_assert!(limit - (self.state.quantized_bits_per_pixel + 1) > 0);
+        // The escape code has the longest unary coded part.
+        let escape_high_bits = limit - (self.state.quantized_bits_per_pixel + 1);
+        let high_bits = bit_reader.read_high_bits(escape_high_bits + 1)?;
+        if high_bits == escape_high_bits {
+            return Ok(bit_reader.read_value(self.state.quantized_bits_per_pixel)? + 1);
+        }
+
+        if k == 0 {
+            return Ok(high_bits);
+        }
+
+        Ok((high_bits << k) + bit_reader.read_value(k)?)
+    }
+
+    fn store_sample(&self, row: &mut [u8], index: usize, value: i32) {
+        debug_assert!((0..=self.state.maximum_sample_value).contains(&value));
+        if self.bytes_per_sample == 1 {
+            row[index] = value as u8;
+        } else {
+            row[index * 2..index * 2 + 2].copy_from_slice(&(value as u16).to_ne_bytes());
+        }
+    }
+}
@@ -0,0 +1,398 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use crate::bit_writer::BitWriter;
+use crate::encoding_error::EncodingError;
+use crate::endianness::Endianness;
+use crate::interleave_mode::InterleaveMode;
+use crate::jpegls_algorithm::{apply_sign, get_predicted_value, map_error_value, sign};
+use crate::jpegls_preset_coding_parameters::JpeglsPcParameters;
+use crate::regular_mode_context::bit_wise_sign;
+use crate::scan_state::ScanState;
+#[cfg(any(feature = "simd", test))]
+use crate::simd_algorithm;
+
+/// Encodes the samples of a single scan into entropy coded data (see ISO/IEC 14495-1, annex A).
+/// The encoder reconstructs the samples the same way the decoder does, as the predictions of
+/// near-lossless coded samples are based on the reconstructed values.
+pub(crate) struct ScanEncoder {
+    width: usize,
+    height: usize,
+    component_count: usize,
+    interleave_mode: InterleaveMode,
+    bytes_per_sample: usize,
+    input_endianness: Endianness,
+    state: ScanState,
+    line_contexts: Vec<i32>,
+
+    /// The predictions of the samples of the current line, when the SIMD code computed them in advance.
+    predictions: Vec<i32>,
+}
+
+impl ScanEncoder {
+    /// Creates an encoder for a scan. component_count is the number of components in the scan,
+    /// bits_per_sample the sample precision of the frame.
+    pub(crate) fn new(width: u32, height: u32, bits_per_sample: u8, component_count: u8,
+                      interleave_mode: InterleaveMode, near_lossless: u8,
+                      preset_coding_parameters: &JpeglsPcParameters) -> ScanEncoder {
+        let state = ScanState::new(preset_coding_parameters, near_lossless, component_count as usize);
+
+        ScanEncoder {
+            width: width as usize,
+            height: height as usize,
+            component_count: component_count as usize,
+            interleave_mode,
+            bytes_per_sample: if bits_per_sample <= 8 { 1 } else { 2 },
+            input_endianness: Endianness::NATIVE,
+            state,
+            line_contexts: vec![0; width as usize],
+            predictions: Vec::new(),
+        }
+    }
+
+    /// Sets the byte order of source samples with 2 bytes per sample. The default is native.
+    pub(crate) fn set_input_endianness(&mut self, input_endianness: Endianness) {
+        self.input_endianness = input_endianness;
+    }
+
+    /// Encodes the scan from source. Line n is read from offset n * stride, the samples of
+    /// the components in a line are stored interleaved.
+    pub(crate) fn encode(&mut self, bit_writer: &mut BitWriter, source: &[u8], stride: usize) -> Result<(), EncodingError> {
+        debug_assert!(stride >= self.row_length());
+        match self.interleave_mode {
+            InterleaveMode::None | InterleaveMode::Line => self.encode_lines(bit_writer, source, stride),
+            InterleaveMode::Sample => self.encode_sample_interleaved_lines(bit_writer, source, stride),
+        }
+    }
+
+    /// The number of bytes of a source line.
+    fn row_length(&self) -> usize {
+        self.width * self.component_count * self.bytes_per_sample
+    }
+
+    fn encode_lines(&mut self, bit_writer: &mut BitWriter, source: &[u8], stride: usize) -> Result<(), EncodingError> {
+        let line_size = self.width + 2;
+        let mut previous_lines = vec![vec![0; line_size]; self.component_count];
+        let mut current_lines = vec![vec![0; line_size]; self.component_count];
+        let row_length = self.row_length();
+
+        for line in 0..self.height {
+            let row = &source[line * stride..line * stride + row_length];
+            for (component, current_line) in current_lines.iter_mut().enumerate() {
+                for x in 0..self.width {
+                    current_line[x + 1] = self.load_sample(row, x * self.component_count + component);
+                }
+            }
+
+            for component in 0..self.component_count {
+                let previous_line = &mut previous_lines[component];
+                let current_line = &mut current_lines[component];
+
+                // Initialize the edge pixels used for prediction (see ISO/IEC 14495-1, A.2.1).
+                previous_line[self.width + 1] = previous_line[self.width];
+                current_line[0] = previous_line[1];
+
+                self.encode_line(bit_writer, component, previous_line, current_line)?;
+            }
+
+            std::mem::swap(&mut previous_lines, &mut current_lines);
+        }
+
+        Ok(())
+    }
+
+    fn encode_line(&mut self, bit_writer: &mut BitWriter, component: usize, previous_line: &[i32],
+                   current_line: &mut [i32]) -> Result<(), EncodingError> {
+        let mut index = 0;
+        let mut rb = previous_line[0];
+        let mut rd = previous_line[1];
+        self.state.compute_line_contexts(previous_line, &mut self.line_contexts);
+        let predicted_line = self.compute_line_predictions(previous_line, current_line);
+
+        while index < self.width {
+            let ra = current_line[index];
+            let rc = rb;
+            rb = rd;
+            rd = previous_line[index + 2];
+
+            let qs = self.state.complete_context_id(self.line_contexts[index], ra, rc);
+            if qs != 0 {
+                let predicted = if predicted_line { self.predictions[index] } else { get_predicted_value(ra, rb, rc) };
+                current_line[index + 1] = self.encode_regular(bit_writer, qs, current_line[index + 1], predicted)?;
+                index += 1;
+            } else {
+                index += self.encode_run_mode(bit_writer, component, index, previous_line, current_line)?;
+                rb = previous_line[index];
+                rd = previous_line[index + 1];
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Computes the predictions of the samples of a line in advance with the SIMD code, which is
+    /// only possible for lossless coding, and returns whether it did.
+    #[cfg(any(feature = "simd", test))]
+    fn compute_line_predictions(&mut self, previous_line: &[i32], current_line: &[i32]) -> bool {
+        if self.state.near_lossless != 0 || !simd_algorithm::enabled() {
+            return false;
+        }
+
+        simd_algorithm::compute_predictions(previous_line, &current_line[..=self.width], &mut self.predictions);
+        true
+    }
+
+    #[cfg(not(any(feature = "simd", test)))]
+    fn compute_line_predictions(&mut self, _previous_line: &[i32], _current_line: &[i32]) -> bool {
+        false
+    }
+
+    fn encode_run_mode(&mut self, bit_writer: &mut BitWriter, component: usize, start_index: usize,
+                       previous_line: &[i32], current_line: &mut [i32]) -> Result<usize, EncodingError> {
+        let ra = current_line[start_index];
+        let pixel_count = self.width - start_index;
+        let mut run_length = 0;
+        while (current_line[start_index + 1 + run_length] - ra).abs() <= self.state.near_lossless {
+            current_line[start_index + 1 + run_length] = ra;
+            run_length += 1;
+            if run_length == pixel_count {
+                break;
+            }
+        }
+
+        self.encode_run_pixels(bit_writer, component, run_length, run_length == pixel_count)?;
+        if run_length == pixel_count {
+            return Ok(run_length);
+        }
+
+        // Run interruption
+        let end_index = start_index + run_length;
+        let rb = previous_line[end_index + 1];
+        current_line[end_index + 1] =
+            self.encode_run_interruption_pixel(bit_writer, component, current_line[end_index + 1], ra, rb)?;
+        self.state.decrement_run_index(component);
+        Ok(run_length + 1)
+    }
+
+    /// Encodes the length of a run (see ISO/IEC 14495-1, A.7.1, code segment A.14).
+    fn encode_run_pixels(&mut self, bit_writer: &mut BitWriter, component: usize, mut run_length: usize,
+                         end_of_line: bool) -> Result<(), EncodingError> {
+        while run_length >= 1 << self.state.run_length_order(component) {
+            bit_writer.write_bits(1, 1)?;
+            run_length -= 1 << self.state.run_length_order(component);
+            self.state.increment_run_index(component);
+        }
+
+        if end_of_line {
+            if run_length != 0 {
+                bit_writer.write_bits(1, 1)?;
+            }
+
+            return Ok(());
+        }
+
+        // A 0 bit followed by the remainder of the run in J[RUNindex] bits.
+        bit_writer.write_bits(run_length as u32, self.state.run_length_order(component) as u32 + 1)
+    }
+
+    fn encode_run_interruption_pixel(&mut self, bit_writer: &mut BitWriter, component: usize, x: i32, ra: i32,
+                                     rb: i32) -> Result<i32, EncodingError> {
+        if (ra - rb).abs() <= self.state.near_lossless {
+            let error_value = self.compute_error_value(x - ra);
+            self.encode_run_interruption_error(bit_writer, component, 1, error_value)?;
+            return Ok(self.state.compute_reconstructed_sample(ra, error_value));
+        }
+
+        let error_value = self.compute_error_value((x - rb) * sign(rb - ra));
+        self.encode_run_interruption_error(bit_writer, component, 0, error_value)?;
+        Ok(self.state.compute_reconstructed_sample(rb, error_value * sign(rb - ra)))
+    }
+
+    /// Encodes the prediction error of a run interruption sample (see ISO/IEC 14495-1, A.7.2).
+    fn encode_run_interruption_error(&mut self, bit_writer: &mut BitWriter, component: usize, context_index: usize,
+                                     error_value: i32) -> Result<(), EncodingError> {
+        let context = &self.state.run_mode_contexts[context_index];
+        let k = context.get_golomb_code();
+        let map = context.compute_map(error_value, k);
+        let e_mapped_error_value = 2 * error_value.abs() - context.run_interruption_type() - map as i32;
+
+        let limit = self.state.limit - self.state.run_length_order(component) - 1;
+        self.encode_mapped_value(bit_writer, k, e_mapped_error_value, limit)?;
+        self.state.run_mode_contexts[context_index].update_variables(error_value, e_mapped_error_value,
+                                                                     self.state.reset_threshold);
+        Ok(())
+    }
+
+    fn encode_sample_interleaved_lines(&mut self, bit_writer: &mut BitWriter, source: &[u8],
+                                       stride: usize) -> Result<(), EncodingError> {
+        let component_count = self.component_count;
+        let line_size = (self.width + 2) * component_count;
+        let mut previous_line = vec![0; line_size];
+        let mut current_line = vec![0; line_size];
+        let row_length = self.row_length();
+
+        for line in 0..self.height {
+            let row = &source[line * stride..line * stride + row_length];
+            for index in 0..self.width * component_count {
+                current_line[component_count + index] = self.load_sample(row, index);
+            }
+
+            // Initialize the edge pixels used for prediction (see ISO/IEC 14495-1, A.2.1).
+            previous_line.copy_within(self.width * component_count..(self.width + 1) * component_count,
+                                      (self.width + 1) * component_count);
+            current_line[..component_count].copy_from_slice(&previous_line[component_count..2 * component_count]);
+
+            self.encode_sample_interleaved_line(bit_writer, &previous_line, &mut current_line)?;
+
+            std::mem::swap(&mut previous_line, &mut current_line);
+        }
+
+        Ok(())
+    }
+
+    fn encode_sample_interleaved_line(&mut self, bit_writer: &mut BitWriter, previous_line: &[i32],
+                                      current_line: &mut [i32]) -> Result<(), EncodingError> {
+        let component_count = self.component_count;
+        let mut qs = vec![0; component_count];
+        let mut index = 0;
+
+        while index < self.width {
+            let position = (index + 1) * component_count;
+            for (component, q) in qs.iter_mut().enumerate() {
+                let ra = current_line[position - component_count + component];
+                let rc = previous_line[position - component_count + component];
+                let rb = previous_line[position + component];
+                let rd = previous_line[position + component_count + component];
+                *q = self.state.compute_context_id(ra, rb, rc, rd);
+            }
+
+            if qs.iter().all(|&q| q == 0) {
+                index += self.encode_sample_interleaved_run_mode(bit_writer, index, previous_line, current_line)?;
+            } else {
+                for (component, &q) in qs.iter().enumerate() {
+                    let ra = current_line[position - component_count + component];
+                    let rc = previous_line[position - component_count + component];
+                    let rb = previous_line[position + component];
+                    current_line[position + component] = self.encode_regular(bit_writer, q,
+                                                                             current_line[position + component],
+                                                                             get_predicted_value(ra, rb, rc))?;
+                }
+                index += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn encode_sample_interleaved_run_mode(&mut self, bit_writer: &mut BitWriter, start_index: usize,
+                                          previous_line: &[i32],
+                                          current_line: &mut [i32]) -> Result<usize, EncodingError> {
+        let component_count = self.component_count;
+        let pixel_count = self.width - start_index;
+        let ra_position = start_index * component_count;
+        let mut run_length = 0;
+        loop {
+            let position = (start_index + 1 + run_length) * component_count;
+            let is_near = (0..component_count)
+                .all(|component| (current_line[position + component] - current_line[ra_position + component]).abs() <=
+                    self.state.near_lossless);
+            if !is_near {
+                break;
+            }
+
+            current_line.copy_within(ra_position..ra_position + component_count, position);
+            run_length += 1;
+            if run_length == pixel_count {
+                break;
+            }
+        }
+
+        self.encode_run_pixels(bit_writer, 0, run_length, run_length == pixel_count)?;
+        if run_length == pixel_count {
+            return Ok(run_length);
+        }
+
+        // Run interruption: all components use the run interruption context with RItype 0.
+        let position = (start_index + run_length + 1) * component_count;
+        for component in 0..component_count {
+            let ra = current_line[ra_position + component];
+            let rb = previous_line[position + component];
+            let error_value = self.compute_error_value((current_line[position + component] - rb) * sign(rb - ra));
+            self.encode_run_interruption_error(bit_writer, 0, 0, error_value)?;
+            current_line[position + component] =
+                self.state.compute_reconstructed_sample(rb, error_value * sign(rb - ra));
+        }
+
+        self.state.decrement_run_index(0);
+        Ok(run_length + 1)
+    }
+
+    /// Encodes a sample in regular mode (see ISO/IEC 14495-1, A.4 to A.6) and returns the
+    /// reconstructed sample.
+    fn encode_regular(&mut self, bit_writer: &mut BitWriter, qs: i32, x: i32,
+                      predicted: i32) -> Result<i32, EncodingError> {
+        let sign = bit_wise_sign(qs);
+        let context_index = apply_sign(qs, sign) as usize;
+        let k = self.state.regular_mode_contexts[context_index]
+            .get_golomb_coding_parameter()
+            .map_err(|_| EncodingError::UnknownError)?;
+        let c = self.state.regular_mode_contexts[context_index].c();
+        let predicted_value = self.state.correct_prediction(predicted + apply_sign(c, sign));
+        let error_value = self.compute_error_value(apply_sign(x - predicted_value, sign));
+
+        let context = &mut self.state.regular_mode_contexts[context_index];
+        let mapped_error_value = map_error_value(context.get_error_correction(k | self.state.near_lossless) ^ error_value);
+        self.encode_mapped_value(bit_writer, k, mapped_error_value, self.state.limit)?;
+
+        self.state.regular_mode_contexts[context_index]
+            .update_variables_and_bias(error_value, self.state.near_lossless, self.state.reset_threshold)
+            .map_err(|_| EncodingError::UnknownError)?;
+        Ok(self.state.compute_reconstructed_sample(predicted_value, apply_sign(error_value, sign)))
+    }
+
+    /// Encodes a mapped error value with a Golomb code, limited to limit bits (see ISO/IEC 14495-1, A.5.3).
+    fn encode_mapped_value(&self, bit_writer: &mut BitWriter, k: i32, mapped_error_value: i32,
+                           limit: i32) -> Result<(), EncodingError> {
+        let high_bits = mapped_error_value >> k;
+        if high_bits < limit - self.state.quantized_bits_per_pixel - 1 {
+            bit_writer.write_unary(high_bits as u32)?;
+            if k != 0 {
+                bit_writer.write_bits((mapped_error_value & ((1 << k) - 1)) as u32, k as u32)?;
+            }
+            return Ok(());
+        }
+
+        // Escape code: limit - qbpp - 1 zero bits, a 1 bit and the value - 1 in qbpp bits.
+        bit_writer.write_unary((limit - self.state.quantized_bits_per_pixel - 1) as u32)?;
+        bit_writer.write_bits(((mapped_error_value - 1) & ((1 << self.state.quantized_bits_per_pixel) - 1)) as u32,
+                              self.state.quantized_bits_per_pixel as u32)
+    }
+
+    /// Computes the quantized and modulo reduced prediction error (see ISO/IEC 14495-1, A.4.4, code segment A.9).
+    fn compute_error_value(&self, error_value: i32) -> i32 {
+        let quantized = if error_value > 0 {
+            (error_value + self.state.near_lossless) / (2 * self.state.near_lossless + 1)
+        } else {
+            -(self.state.near_lossless - error_value) / (2 * self.state.near_lossless + 1)
+        };
+
+        // Modulo reduction of the error to the range -RANGE/2..RANGE/2 (see ISO/IEC 14495-1, A.4.5).
+        let mut value = quantized;
+        if value < 0 {
+            value += self.state.range;
+        }
+        if value >= (self.state.range + 1) / 2 {
+            value -= self.state.range;
+        }
+
+        value
+    }
+
+    fn load_sample(&self, row: &[u8], index: usize) -> i32 {
+        if self.bytes_per_sample == 1 {
+            row[index] as i32
+        } else {
+            self.input_endianness.read_u16([row[index * 2], row[index * 2 + 1]]) as i32
+        }
+    }
+}
@@ -0,0 +1,49 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use crate::color_transformation::ColorTransformation;
+use crate::interleave_mode::InterleaveMode;
+
+/// Describes the parameters of a JPEG-LS scan, as defined by the SOS segment.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScanInfo {
+    pub(crate) component_ids: Vec<u8>,
+    pub(crate) mapping_table_ids: Vec<u8>,
+    pub(crate) near_lossless: u8,
+    pub(crate) interleave_mode: InterleaveMode,
+    pub(crate) transformation: ColorTransformation,
+    pub(crate) raw_transformation: u8,
+}
+
+impl ScanInfo {
+    /// The identifiers (Csj) of the components in the scan.
+    pub fn component_ids(&self) -> &[u8] {
+        &self.component_ids
+    }
+
+    /// The mapping table selectors (Tmj) of the components in the scan, 0 for no table.
+    pub fn mapping_table_ids(&self) -> &[u8] {
+        &self.mapping_table_ids
+    }
+
+    /// The NEAR parameter: the maximum difference between the original and decoded samples.
+    pub fn near_lossless(&self) -> u8 {
+        self.near_lossless
+    }
+
+    /// The ILV parameter of the scan.
+    pub fn interleave_mode(&self) -> InterleaveMode {
+        self.interleave_mode
+    }
+
+    /// The color transformation that applies to the components of the scan.
+    pub fn transformation(&self) -> ColorTransformation {
+        self.transformation
+    }
+
+    /// The unparsed transformation byte of the SOS segment, also when it isn't a value of
+    /// ColorTransformation (a color transformation segment then determines the transformation).
+    pub fn raw_transformation(&self) -> u8 {
+        self.raw_transformation
+    }
+}
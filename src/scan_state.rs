@@ -0,0 +1,268 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use crate::jpegls_algorithm::{compute_context_id, compute_range, fill_quantization_lut, log2_ceil, J,
+                              REGULAR_MODE_CONTEXT_COUNT};
+use crate::jpegls_preset_coding_parameters::JpeglsPcParameters;
+use crate::regular_mode_context::RegularModeContext;
+use crate::run_mode_context::RunModeContext;
+#[cfg(any(feature = "simd", test))]
+use crate::simd_algorithm;
+
+/// The state of the coding process of a scan: the parameters that are derived from the resolved
+/// preset coding parameters and NEAR (see ISO/IEC 14495-1, A.2.1), the context variables and the
+/// run indices. The scan decoder and the scan encoder use the same state and the same context
+/// modeling, as a difference would make the encoded streams undecodable.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ScanState {
+    pub(crate) near_lossless: i32,
+    pub(crate) maximum_sample_value: i32,
+    pub(crate) reset_threshold: i32,
+
+    /// RANGE: the number of possible values of the quantized prediction error.
+    pub(crate) range: i32,
+
+    /// qbpp: the number of bits needed to represent a mapped error value, ceil(log2(RANGE)).
+    pub(crate) quantized_bits_per_pixel: i32,
+
+    /// LIMIT: the maximum length of a Golomb code word, 2 * (bpp + max(8, bpp)) with
+    /// bpp = max(2, ceil(log2(MAXVAL + 1))).
+    pub(crate) limit: i32,
+
+    pub(crate) regular_mode_contexts: Vec<RegularModeContext>,
+    pub(crate) run_mode_contexts: [RunModeContext; 2],
+    quantization_lut: Vec<i8>,
+    run_index: Vec<usize>,
+
+    /// T1, T2 and T3, with which the SIMD line contexts computation quantizes the gradients.
+    #[cfg(any(feature = "simd", test))]
+    thresholds: [i32; 3],
+
+    /// The quantized gradients of the previous line, kept to reuse the memory for every line.
+    #[cfg(any(feature = "simd", test))]
+    gradients: Vec<i32>,
+}
+
+impl ScanState {
+    /// Creates the initial state of a scan with component_count components (see ISO/IEC 14495-1, A.2.1).
+    pub(crate) fn new(preset_coding_parameters: &JpeglsPcParameters, near_lossless: u8,
+                      component_count: usize) -> ScanState {
+        ScanState::with_buffers(preset_coding_parameters, near_lossless, component_count, Vec::new(), Vec::new())
+    }
+
+    /// Creates the initial state of a scan like new, reusing the memory of the context and lookup
+    /// table vectors of a previous scan.
+    pub(crate) fn with_buffers(preset_coding_parameters: &JpeglsPcParameters, near_lossless: u8,
+                               component_count: usize, mut regular_mode_contexts: Vec<RegularModeContext>,
+                               mut quantization_lut: Vec<i8>) -> ScanState {
+        let near_lossless = near_lossless as i32;
+        let maximum_sample_value = preset_coding_parameters.maximum_sample_value;
+        let range = compute_range(maximum_sample_value, near_lossless);
+        let bits_per_pixel = log2_ceil(maximum_sample_value + 1).max(2);
+        regular_mode_contexts.clear();
+        regular_mode_contexts.resize(REGULAR_MODE_CONTEXT_COUNT, RegularModeContext::new(range));
+        fill_quantization_lut(&mut quantization_lut, preset_coding_parameters, near_lossless);
+
+        ScanState {
+            near_lossless,
+            maximum_sample_value,
+            reset_threshold: preset_coding_parameters.reset_value,
+            range,
+            quantized_bits_per_pixel: log2_ceil(range),
+            limit: 2 * (bits_per_pixel + bits_per_pixel.max(8)),
+            regular_mode_contexts,
+            run_mode_contexts: [RunModeContext::new(0, range), RunModeContext::new(1, range)],
+            quantization_lut,
+            run_index: vec![0; component_count],
+            #[cfg(any(feature = "simd", test))]
+            thresholds: [preset_coding_parameters.threshold1, preset_coding_parameters.threshold2,
+                         preset_coding_parameters.threshold3],
+            #[cfg(any(feature = "simd", test))]
+            gradients: Vec::new(),
+        }
+    }
+
+    /// Returns the memory of the context and lookup table vectors, for reuse by with_buffers.
+    pub(crate) fn into_buffers(self) -> (Vec<RegularModeContext>, Vec<i8>) {
+        (self.regular_mode_contexts, self.quantization_lut)
+    }
+
+    /// Computes the context of a sample from its neighbors: Q1, Q2 and Q3 combined into a single
+    /// signed value, which is 0 for run mode (see ISO/IEC 14495-1, A.3).
+    pub(crate) fn compute_context_id(&self, ra: i32, rb: i32, rc: i32, rd: i32) -> i32 {
+        compute_context_id(self.quantize_gradient(rd - rb), self.quantize_gradient(rb - rc),
+                           self.quantize_gradient(rc - ra))
+    }
+
+    /// Computes for every sample of a line the part of its context that only depends on the
+    /// previous line: (Q1 * 9 + Q2) * 9, with Q1 = Q(Rd - Rb) and Q2 = Q(Rb - Rc). previous_line
+    /// contains the edge samples at index 0 and width + 1, contexts has one entry per sample.
+    pub(crate) fn compute_line_contexts(&mut self, previous_line: &[i32], contexts: &mut [i32]) {
+        #[cfg(any(feature = "simd", test))]
+        if simd_algorithm::enabled() {
+            simd_algorithm::compute_line_contexts(previous_line, contexts, &mut self.gradients, self.thresholds,
+                                                  self.near_lossless);
+            return;
+        }
+
+        self.compute_line_contexts_scalar(previous_line, contexts);
+    }
+
+    /// Completes a context computed by compute_line_contexts with Q3 = Q(Rc - Ra).
+    pub(crate) fn complete_context_id(&self, line_context: i32, ra: i32, rc: i32) -> i32 {
+        line_context + self.quantize_gradient(rc - ra)
+    }
+
+    fn compute_line_contexts_scalar(&self, previous_line: &[i32], contexts: &mut [i32]) {
+        debug_assert!(previous_line.len() >= contexts.len() + 2);
+        for (x, context) in contexts.iter_mut().enumerate() {
+            let (rc, rb, rd) = (previous_line[x], previous_line[x + 1], previous_line[x + 2]);
+            *context = (self.quantize_gradient(rd - rb) * 9 + self.quantize_gradient(rb - rc)) * 9;
+        }
+    }
+
+    /// Clamps a prediction to the range 0..=MAXVAL (see ISO/IEC 14495-1, A.4.2, code segment A.6).
+    pub(crate) fn correct_prediction(&self, predicted: i32) -> i32 {
+        predicted.clamp(0, self.maximum_sample_value)
+    }
+
+    /// Reconstructs a sample from its prediction and error (see ISO/IEC 14495-1, A.4.4, code segment A.8).
+    pub(crate) fn compute_reconstructed_sample(&self, predicted_value: i32, error_value: i32) -> i32 {
+        let mut value = predicted_value + error_value * (2 * self.near_lossless + 1);
+        if value < -self.near_lossless {
+            value += self.range * (2 * self.near_lossless + 1);
+        } else if value > self.maximum_sample_value + self.near_lossless {
+            value -= self.range * (2 * self.near_lossless + 1);
+        }
+
+        self.correct_prediction(value)
+    }
+
+    /// The run length order J[RUNindex] of a component (see ISO/IEC 14495-1, A.7.1).
+    pub(crate) fn run_length_order(&self, component: usize) -> i32 {
+        J[self.run_index[component]]
+    }
+
+    pub(crate) fn increment_run_index(&mut self, component: usize) {
+        self.run_index[component] = (self.run_index[component] + 1).min(J.len() - 1);
+    }
+
+    pub(crate) fn decrement_run_index(&mut self, component: usize) {
+        self.run_index[component] = self.run_index[component].saturating_sub(1);
+    }
+
+    fn quantize_gradient(&self, di: i32) -> i32 {
+        // Reconstructed samples are always in the range 0..=MAXVAL, which bounds the gradients.
+        debug_assert!(di.abs() <= self.maximum_sample_value);
+        self.quantization_lut[(di + self.maximum_sample_value) as usize] as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_scan_state(maximum_sample_value: i32, near_lossless: u8) -> ScanState {
+        let preset_coding_parameters = JpeglsPcParameters::compute_default(maximum_sample_value, near_lossless as i32);
+        ScanState::new(&preset_coding_parameters, near_lossless, 1)
+    }
+
+    #[test]
+    fn lossless_8_bit() {
+        let scan_state = create_scan_state(255, 0);
+
+        assert_eq!(scan_state.range, 256);
+        assert_eq!(scan_state.quantized_bits_per_pixel, 8);
+        assert_eq!(scan_state.limit, 32);
+        assert_eq!(scan_state.reset_threshold, 64);
+    }
+
+    #[test]
+    fn lossless_16_bit() {
+        let scan_state = create_scan_state(65535, 0);
+
+        assert_eq!(scan_state.range, 65536);
+        assert_eq!(scan_state.quantized_bits_per_pixel, 16);
+        assert_eq!(scan_state.limit, 64);
+    }
+
+    #[test]
+    fn near_lossless_reduces_qbpp_but_not_limit() {
+        // RANGE = (255 + 2 * 3) / (2 * 3 + 1) + 1 = 38 (see ISO/IEC 14495-1, A.2.1)
+        let scan_state = create_scan_state(255, 3);
+
+        assert_eq!(scan_state.range, 38);
+        assert_eq!(scan_state.quantized_bits_per_pixel, 6);
+        assert_eq!(scan_state.limit, 32);
+    }
+
+    #[test]
+    fn lossless_2_bit_uses_minimum_bpp_of_2() {
+        let scan_state = create_scan_state(3, 0);
+
+        assert_eq!(scan_state.quantized_bits_per_pixel, 2);
+        assert_eq!(scan_state.limit, 20);
+    }
+
+    #[test]
+    fn reconstructed_sample_wraps_around_modulo_range() {
+        let scan_state = create_scan_state(255, 0);
+
+        assert_eq!(scan_state.compute_reconstructed_sample(250, 10), 4);
+        assert_eq!(scan_state.compute_reconstructed_sample(5, -10), 251);
+        assert_eq!(scan_state.compute_reconstructed_sample(100, 20), 120);
+    }
+
+    #[test]
+    fn simd_line_contexts_equal_scalar_line_contexts() {
+        for (maximum_sample_value, near_lossless) in [(255, 0), (255, 3), (4095, 0), (65535, 0)] {
+            let mut scan_state = create_scan_state(maximum_sample_value, near_lossless);
+            let mut seed = 1_u32;
+            for width in [1, 7, 8, 9, 16, 100, 3] {
+                let previous_line: Vec<i32> = (0..width + 2)
+                    .map(|_| {
+                        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                        ((seed >> 8) % (maximum_sample_value as u32 + 1)) as i32
+                    })
+                    .collect();
+                let mut scalar = vec![0; width];
+                let mut simd = vec![0; width];
+
+                simd_algorithm::set_enabled(false);
+                scan_state.compute_line_contexts(&previous_line, &mut scalar);
+                simd_algorithm::set_enabled(true);
+                scan_state.compute_line_contexts(&previous_line, &mut simd);
+
+                assert_eq!(scalar, simd);
+                for x in 0..width {
+                    let (ra, rc) = (previous_line[x + 1] / 2, previous_line[x]);
+                    assert_eq!(scan_state.complete_context_id(scalar[x], ra, rc),
+                               scan_state.compute_context_id(ra, previous_line[x + 1], rc, previous_line[x + 2]));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn near_lossless_reconstructed_sample_is_dequantized_and_wraps_around() {
+        // RANGE = 52, the error is multiplied by 2 * NEAR + 1 = 5 (see ISO/IEC 14495-1, A.4.4).
+        let scan_state = create_scan_state(255, 2);
+
+        assert_eq!(scan_state.compute_reconstructed_sample(100, -3), 85);
+        assert_eq!(scan_state.compute_reconstructed_sample(250, 3), 5);
+        assert_eq!(scan_state.compute_reconstructed_sample(1, -1), 255);
+        assert_eq!(scan_state.compute_reconstructed_sample(1, 0), 1);
+    }
+
+    #[test]
+    fn run_index_stays_within_j() {
+        let mut scan_state = create_scan_state(255, 0);
+
+        scan_state.decrement_run_index(0);
+        assert_eq!(scan_state.run_length_order(0), 0);
+        for _ in 0..40 {
+            scan_state.increment_run_index(0);
+        }
+        assert_eq!(scan_state.run_length_order(0), 15);
+    }
+}
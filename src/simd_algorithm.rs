@@ -0,0 +1,161 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+#[cfg(test)]
+use std::cell::Cell;
+
+use wide::i32x8;
+
+use crate::jpegls_algorithm::{get_predicted_value, quantize_gradient};
+
+/// The number of samples that the SIMD line computations process together.
+const LANE_COUNT: usize = 8;
+
+#[cfg(test)]
+thread_local! {
+    static ENABLED: Cell<bool> = const { Cell::new(cfg!(feature = "simd")) };
+}
+
+/// Returns whether the scans are coded with the SIMD line computations instead of the scalar ones.
+#[cfg(not(test))]
+pub(crate) fn enabled() -> bool {
+    true
+}
+
+/// Returns whether the scans are coded with the SIMD line computations instead of the scalar ones.
+/// Tests select either with set_enabled, to compare the coded values.
+#[cfg(test)]
+pub(crate) fn enabled() -> bool {
+    ENABLED.get()
+}
+
+/// Selects the SIMD or the scalar line computations for the scans coded by the current thread.
+#[cfg(test)]
+pub(crate) fn set_enabled(enabled: bool) {
+    ENABLED.set(enabled);
+}
+
+/// Computes the line contexts like ScanState::compute_line_contexts, LANE_COUNT samples at a time.
+/// Every gradient between neighboring samples of the previous line is quantized once, as Rd - Rb
+/// of a sample is Rb - Rc of the next sample. gradients is a buffer that is reused for every line.
+pub(crate) fn compute_line_contexts(previous_line: &[i32], contexts: &mut [i32], gradients: &mut Vec<i32>,
+                                    thresholds: [i32; 3], near_lossless: i32) {
+    debug_assert!(previous_line.len() >= contexts.len() + 2);
+    let gradient_count = contexts.len() + 1;
+    gradients.resize(gradient_count, 0);
+
+    for (index, chunk) in gradients.chunks_exact_mut(LANE_COUNT).enumerate() {
+        let start = index * LANE_COUNT;
+        let gradient = load(&previous_line[start + 1..]) - load(&previous_line[start..]);
+        chunk.copy_from_slice(&quantize_gradients(gradient, thresholds, near_lossless).to_array());
+    }
+    let remainder_start = gradient_count / LANE_COUNT * LANE_COUNT;
+    for (index, gradient) in gradients.iter_mut().enumerate().skip(remainder_start) {
+        let [threshold1, threshold2, threshold3] = thresholds;
+        *gradient = quantize_gradient(previous_line[index + 1] - previous_line[index], threshold1, threshold2,
+                                      threshold3, near_lossless) as i32;
+    }
+
+    let nine = i32x8::splat(9);
+    for (index, chunk) in contexts.chunks_exact_mut(LANE_COUNT).enumerate() {
+        let start = index * LANE_COUNT;
+        let context = (load(&gradients[start + 1..]) * nine + load(&gradients[start..])) * nine;
+        chunk.copy_from_slice(&context.to_array());
+    }
+    let remainder_start = contexts.len() / LANE_COUNT * LANE_COUNT;
+    for (x, context) in contexts.iter_mut().enumerate().skip(remainder_start) {
+        *context = (gradients[x + 1] * 9 + gradients[x]) * 9;
+    }
+}
+
+/// Computes the predictions of the samples of a line with the median edge detecting predictor,
+/// LANE_COUNT samples at a time. Ra of a sample is its left neighbor in current_line, which is
+/// only known in advance for lossless encoding, as the reconstructed samples then are the source
+/// samples. current_line contains the edge sample at index 0 and one entry per sample.
+pub(crate) fn compute_predictions(previous_line: &[i32], current_line: &[i32], predictions: &mut Vec<i32>) {
+    debug_assert!(previous_line.len() > current_line.len());
+    let width = current_line.len() - 1;
+    predictions.resize(width, 0);
+
+    for (index, chunk) in predictions.chunks_exact_mut(LANE_COUNT).enumerate() {
+        let start = index * LANE_COUNT;
+        let ra = load(&current_line[start..]);
+        let (rb, rc) = (load(&previous_line[start + 1..]), load(&previous_line[start..]));
+
+        // The median of Ra, Rb and Ra + Rb - Rc, which is the value code segment A.5 selects.
+        let prediction = ra.min(rb).max(ra.max(rb).min(ra + rb - rc));
+        chunk.copy_from_slice(&prediction.to_array());
+    }
+    let remainder_start = width / LANE_COUNT * LANE_COUNT;
+    for (x, prediction) in predictions.iter_mut().enumerate().skip(remainder_start) {
+        *prediction = get_predicted_value(current_line[x], previous_line[x + 1], previous_line[x]);
+    }
+}
+
+/// Quantizes gradients like quantize_gradient (see ISO/IEC 14495-1, A.3.3, code segment A.4) by
+/// counting the thresholds they reach: a mask is -1 where its comparison is true. The result is
+/// the same as that of the quantization lookup table, as NEAR < T1 <= T2 <= T3.
+fn quantize_gradients(gradient: i32x8, thresholds: [i32; 3], near_lossless: i32) -> i32x8 {
+    let [threshold1, threshold2, threshold3] = thresholds.map(i32x8::splat);
+    let near_lossless = i32x8::splat(near_lossless);
+
+    let positive_masks = gradient.simd_gt(near_lossless) + gradient.simd_ge(threshold1) +
+                         gradient.simd_ge(threshold2) + gradient.simd_ge(threshold3);
+    let negative_masks = gradient.simd_lt(-near_lossless) + gradient.simd_le(-threshold1) +
+                         gradient.simd_le(-threshold2) + gradient.simd_le(-threshold3);
+    negative_masks - positive_masks
+}
+
+/// Loads the first LANE_COUNT values of values into a vector.
+fn load(values: &[i32]) -> i32x8 {
+    i32x8::new(values[..LANE_COUNT].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jpegls_preset_coding_parameters::JpeglsPcParameters;
+
+    /// Returns a pseudo-random line of count samples in the range 0..=maximum_sample_value.
+    fn create_line(count: usize, maximum_sample_value: i32, seed: &mut u32) -> Vec<i32> {
+        (0..count)
+            .map(|_| {
+                *seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                ((*seed >> 8) % (maximum_sample_value as u32 + 1)) as i32
+            })
+            .collect()
+    }
+
+    #[test]
+    fn quantize_gradients_equals_quantize_gradient() {
+        for (maximum_sample_value, near_lossless) in [(255, 0), (255, 3), (4095, 0), (65535, 10)] {
+            let defaults = JpeglsPcParameters::compute_default(maximum_sample_value, near_lossless);
+            let thresholds = [defaults.threshold1, defaults.threshold2, defaults.threshold3];
+            let gradients: Vec<i32> = (-maximum_sample_value..=maximum_sample_value).collect();
+            for chunk in gradients.chunks_exact(LANE_COUNT) {
+                let quantized = quantize_gradients(load(chunk), thresholds, near_lossless).to_array();
+                for (gradient, quantized) in chunk.iter().zip(quantized) {
+                    assert_eq!(quantized, quantize_gradient(*gradient, defaults.threshold1, defaults.threshold2,
+                                                            defaults.threshold3, near_lossless) as i32);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn compute_predictions_equals_get_predicted_value() {
+        let mut seed = 1_u32;
+        for width in [1, 7, 8, 9, 16, 100] {
+            let previous_line = create_line(width + 2, 255, &mut seed);
+            let current_line = create_line(width + 1, 255, &mut seed);
+            let mut predictions = Vec::new();
+
+            compute_predictions(&previous_line, &current_line, &mut predictions);
+
+            assert_eq!(predictions.len(), width);
+            for (x, prediction) in predictions.into_iter().enumerate() {
+                assert_eq!(prediction, get_predicted_value(current_line[x], previous_line[x + 1], previous_line[x]));
+            }
+        }
+    }
+}
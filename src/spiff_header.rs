@@ -0,0 +1,176 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use crate::frame_info::FrameInfo;
+
+/// The major revision number of the SPIFF version supported by this implementation.
+pub(crate) const SPIFF_MAJOR_REVISION_NUMBER: u8 = 2;
+
+/// The SPIFF directory entry tag of the end of directory entry (see ISO/IEC 10918-3, F.2.2.3).
+pub(crate) const SPIFF_END_OF_DIRECTORY_ENTRY_TYPE: u32 = 1;
+
+pub(crate) const SPIFF_MAGIC_ID: [u8; 6] = [b'S', b'P', b'I', b'F', b'F', 0];
+
+/// The SPIFF compression type of JPEG-LS (see ISO/IEC 10918-3, F.2.1.4).
+pub(crate) const SPIFF_COMPRESSION_TYPE_JPEG_LS: u8 = 6;
+
+/// The size in bytes of the SPIFF header segment and the end of directory entry, including the markers.
+pub(crate) const SPIFF_SEGMENTS_SIZE: usize = 2 + 2 + 30 + 2 + 2 + 6;
+
+/// The header of a Still Picture Interchange File Format (SPIFF) file (see ISO/IEC 10918-3, F.2.1).
+/// It is stored in an APP8 segment directly after the SOI marker.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SpiffHeader {
+    pub(crate) profile_id: u8,
+    pub(crate) component_count: u8,
+    pub(crate) height: u32,
+    pub(crate) width: u32,
+    pub(crate) color_space: u8,
+    pub(crate) bits_per_sample: u8,
+    pub(crate) compression_type: u8,
+    pub(crate) resolution_units: u8,
+    pub(crate) vertical_resolution: u32,
+    pub(crate) horizontal_resolution: u32,
+}
+
+impl SpiffHeader {
+    /// Creates a SPIFF header for a JPEG-LS image described by frame_info, with the color space
+    /// (S) as defined by ISO/IEC 10918-3, F.2.1.2. The resolution is an aspect ratio of 1:1.
+    pub fn new(frame_info: &FrameInfo, color_space: u8) -> SpiffHeader {
+        SpiffHeader {
+            profile_id: 0,
+            component_count: frame_info.component_count(),
+            height: frame_info.height(),
+            width: frame_info.width(),
+            color_space,
+            bits_per_sample: frame_info.bits_per_sample(),
+            compression_type: SPIFF_COMPRESSION_TYPE_JPEG_LS,
+            resolution_units: 0,
+            vertical_resolution: 1,
+            horizontal_resolution: 1,
+        }
+    }
+
+    /// Sets the resolution units (0 = aspect ratio, 1 = dots per inch, 2 = dots per centimeter)
+    /// and the vertical and horizontal resolution.
+    pub fn set_resolution(&mut self, resolution_units: u8, vertical_resolution: u32, horizontal_resolution: u32) {
+        self.resolution_units = resolution_units;
+        self.vertical_resolution = vertical_resolution;
+        self.horizontal_resolution = horizontal_resolution;
+    }
+
+    /// The application profile (P), 0 = not specified.
+    pub fn profile_id(&self) -> u8 {
+        self.profile_id
+    }
+
+    /// Number of color components (NC).
+    pub fn component_count(&self) -> u8 {
+        self.component_count
+    }
+
+    /// Number of lines (HEIGHT).
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Number of samples per line (WIDTH).
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The color space of the image data (S), as defined by ISO/IEC 10918-3, F.2.1.2.
+    pub fn color_space(&self) -> u8 {
+        self.color_space
+    }
+
+    /// Number of bits per sample (BPS).
+    pub fn bits_per_sample(&self) -> u8 {
+        self.bits_per_sample
+    }
+
+    /// The compression type (C), 6 = JPEG-LS.
+    pub fn compression_type(&self) -> u8 {
+        self.compression_type
+    }
+
+    /// The resolution units (R): 0 = aspect ratio, 1 = dots per inch, 2 = dots per centimeter.
+    pub fn resolution_units(&self) -> u8 {
+        self.resolution_units
+    }
+
+    /// The vertical resolution (VRES).
+    pub fn vertical_resolution(&self) -> u32 {
+        self.vertical_resolution
+    }
+
+    /// The horizontal resolution (HRES).
+    pub fn horizontal_resolution(&self) -> u32 {
+        self.horizontal_resolution
+    }
+
+    /// Parses the data of an APP8 segment. Returns None if the segment is not a SPIFF header
+    /// or has a version that is not supported.
+    pub(crate) fn try_parse(segment_data: &[u8]) -> Option<SpiffHeader> {
+        if segment_data.len() < 30 || segment_data[..6] != SPIFF_MAGIC_ID {
+            return None;
+        }
+
+        if segment_data[6] > SPIFF_MAJOR_REVISION_NUMBER {
+            return None;  // Treat unknown versions as if the SPIFF header doesn't exist.
+        }
+
+        let read_u32 = |offset: usize| {
+            u32::from_be_bytes([segment_data[offset], segment_data[offset + 1], segment_data[offset + 2],
+                                segment_data[offset + 3]])
+        };
+
+        Some(SpiffHeader {
+            profile_id: segment_data[8],
+            component_count: segment_data[9],
+            height: read_u32(10),
+            width: read_u32(14),
+            color_space: segment_data[18],
+            bits_per_sample: segment_data[19],
+            compression_type: segment_data[20],
+            resolution_units: segment_data[21],
+            vertical_resolution: read_u32(22),
+            horizontal_resolution: read_u32(26),
+        })
+    }
+
+    /// Returns the data of the APP8 segment of the header (see ISO/IEC 10918-3, F.2.1).
+    pub(crate) fn to_segment_data(&self) -> Vec<u8> {
+        let mut segment_data = SPIFF_MAGIC_ID.to_vec();
+        segment_data.push(SPIFF_MAJOR_REVISION_NUMBER);
+        segment_data.push(0); // Minor revision number
+        segment_data.push(self.profile_id);
+        segment_data.push(self.component_count);
+        segment_data.extend_from_slice(&self.height.to_be_bytes());
+        segment_data.extend_from_slice(&self.width.to_be_bytes());
+        segment_data.push(self.color_space);
+        segment_data.push(self.bits_per_sample);
+        segment_data.push(self.compression_type);
+        segment_data.push(self.resolution_units);
+        segment_data.extend_from_slice(&self.vertical_resolution.to_be_bytes());
+        segment_data.extend_from_slice(&self.horizontal_resolution.to_be_bytes());
+        segment_data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame_info::{Height, Width};
+
+    #[test]
+    fn segment_data_parses_back_equal() {
+        let mut spiff_header = SpiffHeader::new(&FrameInfo::new(Width(640), Height(480), 12, 3), 10);
+        spiff_header.set_resolution(1, 300, 150);
+
+        let segment_data = spiff_header.to_segment_data();
+
+        assert_eq!(segment_data.len(), 30);
+        assert_eq!(SpiffHeader::try_parse(&segment_data), Some(spiff_header));
+    }
+}
@@ -0,0 +1,78 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use crate::decoding_error::DecodingError;
+use crate::jpeg_stream_reader::JpegStreamReader;
+use crate::mapping_table::MappingTable;
+
+/// The mapping tables of a tables-only stream (the abbreviated format for table specification of
+/// ISO/IEC 10918-1, B.5), for the abbreviated image streams that select them without defining
+/// them. Pass it to Decoder::with_tables.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TableSet {
+    pub(crate) mapping_tables: Vec<MappingTable>,
+}
+
+impl TableSet {
+    /// The mapping tables, in the order in which they were defined.
+    pub fn mapping_tables(&self) -> &[MappingTable] {
+        &self.mapping_tables
+    }
+
+    /// Returns the mapping table with the given ID, or None when the stream didn't define it.
+    pub fn mapping_table(&self, table_id: u8) -> Option<&MappingTable> {
+        self.mapping_tables.iter().find(|table| table.table_id == table_id)
+    }
+}
+
+/// Reads a tables-only stream: the SOI marker, LSE segments with mapping tables (and optionally COM
+/// and APPn segments) and the EOI marker. A stream with a frame fails with UnexpectedMarkerFound.
+pub fn read_tables(data: &[u8]) -> Result<TableSet, DecodingError> {
+    let mut reader = JpegStreamReader::new(data);
+    reader.read_tables()?;
+
+    Ok(TableSet { mapping_tables: reader.take_mapping_tables() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SOI, a mapping table with ID 1 and 2 entries of 3 bytes, its continuation with 1 entry, a
+    /// mapping table with ID 5 and 1 entry of 1 byte and EOI.
+    const TABLES: [u8; 35] = [
+        0xFF, 0xD8,
+        0xFF, 0xF8, 0x00, 0x0B, 2, 1, 3, 10, 11, 12, 20, 21, 22,
+        0xFF, 0xF8, 0x00, 0x08, 3, 1, 3, 30, 31, 32,
+        0xFF, 0xF8, 0x00, 0x06, 2, 5, 1, 7,
+        0xFF, 0xD9,
+    ];
+
+    #[test]
+    fn read_tables_of_tables_only_stream() {
+        let table_set = read_tables(&TABLES).unwrap();
+
+        assert_eq!(table_set.mapping_tables().len(), 2);
+        assert_eq!(table_set.mapping_table(1).unwrap().data(), &[10, 11, 12, 20, 21, 22, 30, 31, 32]);
+        assert_eq!(table_set.mapping_table(5).unwrap().entry(0), Some(&[7][..]));
+        assert_eq!(table_set.mapping_table(2), None);
+    }
+
+    #[test]
+    fn read_tables_of_empty_tables_only_stream() {
+        assert_eq!(read_tables(&[0xFF, 0xD8, 0xFF, 0xD9]).unwrap(), TableSet::default());
+    }
+
+    #[test]
+    fn read_tables_of_stream_with_frame_fails() {
+        let mut stream = TABLES[..TABLES.len() - 2].to_vec();
+        stream.extend_from_slice(&[0xFF, 0xF7, 0x00, 0x0B, 8, 0, 1, 0, 1, 1, 1, 0x11, 0]);
+
+        assert_eq!(read_tables(&stream).unwrap_err(), DecodingError::UnexpectedMarkerFound);
+    }
+
+    #[test]
+    fn read_tables_without_end_of_image_fails() {
+        assert_eq!(read_tables(&TABLES[..TABLES.len() - 2]).unwrap_err(), DecodingError::UnexpectedEndOfStream);
+    }
+}
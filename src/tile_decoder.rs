@@ -0,0 +1,120 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+use std::io::Read;
+
+use crate::decoder::Decoder;
+use crate::decoding_error::DecodingError;
+use crate::frame_info::FrameInfo;
+
+/// Decodes an image encoded with interleave mode None (planar) in bands of rows, for images that
+/// are too large to hold in memory. Only a single band of a component is stored at a time; the
+/// decoder state of a scan (the previous row and the context variables) carries over from band to
+/// band, so the bands are identical to the rows of a complete decode.
+#[derive(Debug)]
+pub struct TileDecoder<R: Read> {
+    decoder: Decoder<R>,
+    band_height: u32,
+}
+
+impl<R: Read> TileDecoder<R> {
+    /// Creates a decoder that decodes bands of band_height rows (at least 1); the last band of
+    /// every component has the remaining rows.
+    pub fn new(r: R, band_height: u32) -> TileDecoder<R> {
+        TileDecoder {
+            decoder: Decoder::new(r),
+            band_height,
+        }
+    }
+
+    /// Reads the header of the JPEG-LS stream, up to the start of the first scan.
+    pub fn read_header(&mut self) -> Result<(), DecodingError> {
+        self.decoder.read_header()
+    }
+
+    /// The frame info of the image. Only valid after the header has been read.
+    pub fn frame_info(&self) -> &FrameInfo {
+        self.decoder.frame_info()
+    }
+
+    /// Decodes the image and calls callback for every band with the component (in frame order),
+    /// the index of the first row of the band and the samples of its rows, stored as by
+    /// Decoder::decode. Images with another interleave mode fail with InvalidParameterInterleaveMode.
+    pub fn decode<F: FnMut(u8, u32, &[u8])>(&mut self, mut callback: F) -> Result<(), DecodingError> {
+        self.decoder.decode_bands(self.band_height, &mut callback)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::Encoder;
+    use crate::frame_info::{Height, Width};
+
+    const RGB8_PLANAR_16X16: &[u8] = include_bytes!("../tests/fixtures/rgb8_planar_16x16.jls");
+    const RGB8_SAMPLE_16X16: &[u8] = include_bytes!("../tests/fixtures/rgb8_sample_16x16.jls");
+
+    /// Decodes the image in bands and stores every band at its position in the complete image.
+    fn decode_in_bands(encoded: &[u8], band_height: u32) -> Vec<u8> {
+        let mut tile_decoder = TileDecoder::new(encoded, band_height);
+        tile_decoder.read_header().unwrap();
+        let frame_info = tile_decoder.frame_info().clone();
+        let stride = frame_info.width() as usize * frame_info.bytes_per_sample();
+        let plane_size = stride * frame_info.height() as usize;
+        let mut decoded = vec![0; plane_size * frame_info.component_count() as usize];
+
+        let mut band_count = 0;
+        tile_decoder.decode(|component, first_row, samples| {
+            assert!(samples.len() <= stride * band_height.max(1) as usize);
+            let start = component as usize * plane_size + first_row as usize * stride;
+            decoded[start..start + samples.len()].copy_from_slice(samples);
+            band_count += 1;
+        }).unwrap();
+
+        let bands_per_component = frame_info.height().div_ceil(band_height.clamp(1, frame_info.height()));
+        assert_eq!(band_count, bands_per_component * frame_info.component_count() as u32);
+        decoded
+    }
+
+    #[test]
+    fn decode_in_bands_matches_decode() {
+        let expected = Decoder::new(RGB8_PLANAR_16X16).decode().unwrap();
+
+        for band_height in [0, 1, 3, 5, 16, 100] {
+            assert_eq!(decode_in_bands(RGB8_PLANAR_16X16, band_height), expected);
+        }
+    }
+
+    #[test]
+    fn decode_near_lossless_16_bit_image_in_bands_matches_decode() {
+        let frame_info = FrameInfo::new(Width(37), Height(29), 12, 2);
+        let source: Vec<u8> = (0..37 * 29 * 2u32)
+            .flat_map(|i| (((i / 7 * 131 + i % 37 * 3) % 4096) as u16).to_ne_bytes())
+            .collect();
+        let mut encoder = Encoder::new(frame_info);
+        encoder.set_near_lossless(3);
+        let encoded = encoder.encode(&source).unwrap();
+        let expected = Decoder::new(encoded.as_slice()).decode().unwrap();
+
+        assert_eq!(decode_in_bands(&encoded, 4), expected);
+    }
+
+    #[test]
+    fn decode_interleaved_image_in_bands_fails() {
+        let mut tile_decoder = TileDecoder::new(RGB8_SAMPLE_16X16, 4);
+
+        assert_eq!(tile_decoder.decode(|_, _, _| {}), Err(DecodingError::InvalidParameterInterleaveMode));
+    }
+
+    #[test]
+    fn decode_truncated_image_in_bands_fails_after_decoded_bands() {
+        let truncated = &RGB8_PLANAR_16X16[..RGB8_PLANAR_16X16.len() / 2];
+        let mut tile_decoder = TileDecoder::new(truncated, 4);
+        let mut components = Vec::new();
+
+        let result = tile_decoder.decode(|component, _, _| components.push(component));
+
+        assert_eq!(result, Err(DecodingError::UnexpectedEndOfStream));
+        assert!(!components.is_empty());
+    }
+}
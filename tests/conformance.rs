@@ -0,0 +1,124 @@
+// Copyright (c) Team CharLS.
+// SPDX-License-Identifier: BSD-3-Clause
+
+//! Decodes the .jls files in tests/fixtures and compares the result with the .raw reference file
+//! that has the same name. The reference files contain the samples as decoded by CharLS, with
+//! 1 byte per sample for 2..=8 bits and 2 bytes (little endian) for 9..=16 bits per sample.
+//! The release of CharLS used for the other cases ignores a MAXVAL in the LSE segment that is
+//! smaller than 2^bits_per_sample - 1: the maxval100 cases were created with a CharLS build
+//! that derives RANGE and the clamping of predictions from that MAXVAL as ISO/IEC 14495-1 requires.
+//! To add a conformance case, put a .jls file and its .raw reference file in tests/fixtures.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use charls_rs::Decoder;
+
+struct ConformanceCase {
+    name: String,
+    encoded: PathBuf,
+    reference: PathBuf,
+}
+
+fn fixtures_directory() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures")
+}
+
+/// Returns all .jls files in the fixtures directory that have a .raw reference file, sorted by name.
+fn conformance_cases() -> Vec<ConformanceCase> {
+    let mut cases: Vec<ConformanceCase> = fs::read_dir(fixtures_directory())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "jls"))
+        .filter_map(|encoded| {
+            let reference = encoded.with_extension("raw");
+            if !reference.is_file() {
+                return None;
+            }
+
+            Some(ConformanceCase {
+                name: encoded.file_stem().unwrap().to_string_lossy().into_owned(),
+                encoded,
+                reference,
+            })
+        })
+        .collect();
+
+    cases.sort_by(|a, b| a.name.cmp(&b.name));
+    cases
+}
+
+/// Converts the little endian samples of a reference file to the native endian decoder output.
+fn to_native_endian(reference: Vec<u8>, bits_per_sample: u8) -> Vec<u8> {
+    if bits_per_sample <= 8 {
+        return reference;
+    }
+
+    reference
+        .chunks_exact(2)
+        .flat_map(|sample| u16::from_le_bytes([sample[0], sample[1]]).to_ne_bytes())
+        .collect()
+}
+
+fn decode_case(case: &ConformanceCase) -> Result<(), String> {
+    let encoded = fs::read(&case.encoded).unwrap();
+    let mut decoder = Decoder::new(encoded.as_slice());
+    let decoded = decoder.decode().map_err(|error| format!("{}: decoding failed with {:?}", case.name, error))?;
+
+    let reference = to_native_endian(fs::read(&case.reference).unwrap(), decoder.frame_info().bits_per_sample());
+    if decoded.len() != reference.len() {
+        return Err(format!("{}: decoded {} bytes, expected {}", case.name, decoded.len(), reference.len()));
+    }
+
+    if let Some(index) = decoded.iter().zip(&reference).position(|(a, b)| a != b) {
+        return Err(format!("{}: first difference at byte {}", case.name, index));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn decode_conformance_fixtures() {
+    let cases = conformance_cases();
+    let failures: Vec<String> = cases.iter().filter_map(|case| decode_case(case).err()).collect();
+
+    assert!(failures.is_empty(), "{} of {} cases failed:\n{}", failures.len(), cases.len(), failures.join("\n"));
+}
+
+/// The conformance cases that must be in the fixtures directory, grouped by what they cover.
+const REQUIRED_CASES: [&str; 11] = [
+    // Grayscale and the interleave modes of RGB.
+    "gray8_16x16", "rgb8_sample_16x16", "rgb8_line_16x16", "rgb8_planar_16x16",
+    // Errors that are coded with the escape code.
+    "escape8_16x16", "escape8_16x16_near3", "escape16_16x16",
+    // A MAXVAL in the LSE segment that is smaller than 2^bits_per_sample - 1.
+    "maxval100_16x16", "maxval100_16x16_near2",
+    // Gradient thresholds T1, T2 and T3 from the LSE segment.
+    "gray8_16x16_t5_10_30", "gray8_16x16_t5_10_30_near1",
+];
+
+#[test]
+fn conformance_fixtures_include_required_cases() {
+    let names: Vec<String> = conformance_cases().into_iter().map(|case| case.name).collect();
+
+    for required in REQUIRED_CASES {
+        assert!(names.iter().any(|name| name == required), "missing conformance case {}", required);
+    }
+}
+
+#[test]
+fn decode_corrupted_conformance_fixtures_returns_without_panic() {
+    for case in conformance_cases() {
+        let encoded = fs::read(&case.encoded).unwrap();
+        // All bytes of the header and a sample of the entropy coded data.
+        for position in (0..encoded.len().min(64)).chain((64..encoded.len()).step_by(17)) {
+            for value in [0x00, 0xFF, encoded[position] ^ 0x55] {
+                let mut corrupted = encoded.clone();
+                corrupted[position] = value;
+
+                let result = std::panic::catch_unwind(|| Decoder::new(corrupted.as_slice()).decode().is_ok());
+                assert!(result.is_ok(), "{}: decoding panicked with byte {} set to {:#04x}", case.name, position, value);
+            }
+        }
+    }
+}